@@ -0,0 +1,217 @@
+use anyhow::Result;
+use git2::Oid;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use std::sync::Arc;
+use tigrs_core::{ColumnType, CommandVars, LineType, Request, ViewColumn};
+use tigrs_git::{blame_file, blame_parent, short_id, uncommitted_diff_text, BlameCommit, DiffParent, FileBlame};
+use tigrs_tui::{Transition, TuiFrame, View};
+
+use crate::{
+    columns::{column_width, date_text, fit, ident_text, line_number_text},
+    commit_vars, generic_request, goto_commit, key_hints, list_state, move_selection, view_block, AppState, DiffView, ViewData,
+};
+
+/// Per-line commit attribution of one file, with the columns of the
+/// `blame-view` setting.
+pub(crate) struct BlameView {
+    blame: FileBlame,
+    /// Earlier (revision, path, cursor) states, for going back.
+    history: Vec<(Option<Oid>, String, usize)>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+}
+
+impl BlameView {
+    pub(crate) fn new(blame: FileBlame, lineno: usize) -> Self {
+        let idx = lineno.saturating_sub(1).min(blame.lines.len().saturating_sub(1));
+        Self { blame, history: Vec::new(), idx, offset: 0, height: 0, message: None }
+    }
+
+    /// Blame `path` at `rev` (the work tree if `None`) with the cursor on `lineno`.
+    pub(crate) fn open(state: &AppState, path: &str, rev: Option<Oid>, lineno: usize) -> Result<Self> {
+        let Some(repo) = state.repo.as_ref() else { anyhow::bail!("Not a git repository") };
+        Ok(Self::new(blame_file(repo, path, rev)?, lineno))
+    }
+
+    fn go_to(&mut self, state: &AppState, rev: Option<Oid>, path: String, lineno: usize) {
+        if rev == self.blame.rev && path == self.blame.path {
+            self.message = Some("The selected commit is already displayed".into());
+            return;
+        }
+        let previous = (self.blame.rev, self.blame.path.clone(), self.idx);
+        match BlameView::open(state, &path, rev, lineno) {
+            Ok(view) => {
+                self.blame = view.blame;
+                self.idx = view.idx;
+                self.history.push(previous);
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    fn go_back(&mut self, state: &AppState) {
+        let Some((rev, path, idx)) = self.history.pop() else {
+            self.message = Some("Already at start of history".into());
+            return;
+        };
+        match BlameView::open(state, &path, rev, idx + 1) {
+            Ok(view) => { self.blame = view.blame; self.idx = view.idx; }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    /// Blame the selected line's commit, or with `parent` the one before it.
+    fn go_forward(&mut self, state: &AppState, parent: bool) {
+        let (Some(repo), Some(line)) = (state.repo.as_ref(), self.blame.lines.get(self.idx)) else { return };
+        let target = if parent {
+            state.diff_settings()
+                .and_then(|settings| blame_parent(repo, line, &settings))
+                .map(|(id, path, lineno)| (Some(id), path, lineno))
+        } else if line.commit.is_uncommitted() {
+            Err(anyhow::anyhow!("Cannot blame uncommitted changes"))
+        } else {
+            Ok((Some(line.commit.id), line.commit.path.clone(), line.orig_lineno))
+        };
+        match target {
+            Ok((rev, path, lineno)) => self.go_to(state, rev, path, lineno),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    /// The text `/` searches on line `i`.
+    fn search_line(&self, i: usize) -> Option<String> {
+        let l = self.blame.lines.get(i)?;
+        Some(format!("{} {} {}", short_id(&l.commit.id), l.commit.author.name, l.text))
+    }
+}
+
+impl View<AppState> for BlameView {
+    fn keymap(&self) -> &'static str { "blame" }
+    fn title(&self) -> String {
+        match self.blame.rev {
+            Some(rev) => format!("tig-rs — blame: {} @ {}", self.blame.path, short_id(&rev)),
+            None => format!("tig-rs — blame: {}", self.blame.path),
+        }
+    }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let mut fs = key_hints(state, self.keymap(), &[(&["enter"], "diff"), (&["parent"], "parent"), (&["view-blame"], "blame commit"), (&["back"], "back"), (&["view-close"], "close")]);
+        if let Some(msg) = &self.message {
+            fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+        }
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        let lines = &self.blame.lines;
+        let view_width = chunks[0].width as usize;
+        let columns = state.settings.view_columns("blame");
+        // file-name:auto shows the column only when some line comes from another name.
+        let renamed = lines.iter().any(|l| l.commit.path != self.blame.path);
+        // Lines next to each other mostly share their commit.
+        let mut commits: Vec<&Arc<BlameCommit>> = lines.iter().map(|l| &l.commit).collect();
+        commits.dedup_by(|a, b| Arc::ptr_eq(a, b));
+        let shown: Vec<(&ViewColumn, usize)> = columns.shown()
+            .filter(|c| c.kind != ColumnType::FileName || c.display != "auto" || renamed)
+            .map(|column| {
+                let commits = commits.iter();
+                let width = match column.kind {
+                    ColumnType::Id => column_width(column, view_width, std::iter::once(7)),
+                    ColumnType::FileName => column_width(column, view_width, commits.map(|c| c.path.chars().count())),
+                    ColumnType::Author => column_width(column, view_width, commits.map(|c| ident_text(column, &c.author).chars().count())),
+                    ColumnType::Committer => column_width(column, view_width, commits.map(|c| ident_text(column, &c.committer).chars().count())),
+                    ColumnType::Date => column_width(column, view_width, commits.map(|c| date_text(column, c.time).chars().count())),
+                    ColumnType::LineNumber => column_width(column, view_width, std::iter::once(lines.len().to_string().len())),
+                    _ => 0,
+                };
+                (column, width)
+            })
+            .collect();
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let view = self.keymap();
+        let items: Vec<ListItem> = lines.iter().skip(self.offset).take(self.height).map(|line| {
+            let commit = &line.commit;
+            let spans: Vec<Span> = shown.iter().map(|&(column, width)| match column.kind {
+                ColumnType::Id => {
+                    let id = if commit.is_uncommitted() { String::new() } else { short_id(&commit.id) };
+                    let ty = if column.flag("color") { LineType::palette(commit.id.as_bytes()[0] as usize) } else { LineType::Id };
+                    Span::styled(format!("{} ", fit(&id, width)), state.theme.style(view, ty))
+                }
+                ColumnType::FileName => Span::styled(format!("{} ", fit(&commit.path, width)), state.theme.style(view, LineType::File)),
+                ColumnType::Author => Span::styled(format!("{} ", fit(&ident_text(column, &commit.author), width)), state.theme.style(view, LineType::Author)),
+                ColumnType::Committer => Span::styled(format!("{} ", fit(&ident_text(column, &commit.committer), width)), state.theme.style(view, LineType::Committer)),
+                ColumnType::Date => {
+                    let date = if commit.is_uncommitted() { String::new() } else { date_text(column, commit.time) };
+                    Span::styled(format!("{} ", fit(&date, width)), state.theme.style(view, LineType::Date))
+                }
+                ColumnType::LineNumber => Span::styled(format!("{}│ ", line_number_text(column, line.lineno, width)), state.theme.style(view, LineType::LineNumber)),
+                _ => Span::raw(line.text.clone()),
+            }).collect();
+            ListItem::new(state.highlight(view, Line::from(spans)))
+        }).collect();
+        let list = List::new(items)
+            .block(view_block(state, view, self.title()))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        let selected = if lines.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.blame.lines.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::Command(argv) if argv[0] == "goto" => match goto_commit(state, argv, self.blame.lines.iter().map(|l| l.commit.id)) {
+                Ok(idx) => self.idx = idx,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+            Request::Enter => {
+                let (Some(repo), Some(line)) = (state.repo.as_ref(), self.blame.lines.get(self.idx)) else { return Transition::None };
+                let commit = &line.commit;
+                let data = if commit.is_uncommitted() {
+                    uncommitted_diff_text(repo, &commit.path).map(|text| ViewData::new(format!("Uncommitted changes to {}", commit.path), text))
+                } else {
+                    ViewData::commit(state, commit.id, DiffParent::Combined)
+                };
+                match data {
+                    Ok(data) => return Transition::Split(Box::new(DiffView { data })),
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+            }
+            Request::Parent => self.go_forward(state, true),
+            Request::ViewBlame => self.go_forward(state, false),
+            Request::Back => self.go_back(state),
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.blame.lines.len()).filter_map(|i| self.search_line(i)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.blame.lines.len()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.search_line(self.idx) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { true }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        vars.set("file", self.blame.path.clone());
+        if let Some(rev) = self.blame.rev { vars.set("ref", rev.to_string()); }
+        if let Some(line) = self.blame.lines.get(self.idx) {
+            if !line.commit.is_uncommitted() { commit_vars(state, line.commit.id, vars); }
+            vars.set("file_old", line.commit.path.clone());
+            vars.set("lineno", line.lineno.to_string());
+            vars.set("lineno_old", line.orig_lineno.to_string());
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::Result;
+use git2::Oid;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use std::path::Path;
+use tigrs_core::{ColumnType, CommandVars, LineType, Request};
+use tigrs_git::short_id;
+use tigrs_tui::{Transition, TuiFrame, View};
+
+use crate::{
+    columns::{column_width, line_number_text},
+    commit_vars, editor_command, generic_request, highlight_code, key_hints, list_state, move_selection, view_block, AppState, BlameView,
+    Foreground,
+};
+
+/// Binary blobs are dumped up to this many bytes.
+const HEX_DUMP_LIMIT: usize = 64 * 1024;
+
+/// `xxd`-style dump: offset, 16 hex bytes and their printable characters.
+fn hex_dump(data: &[u8]) -> Vec<String> {
+    data.chunks(16).enumerate().map(|(i, chunk)| {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let text: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        format!("{:08x}  {:<47}  {text}", i * 16, hex.join(" "))
+    }).collect()
+}
+
+pub(crate) fn expand_tabs(line: &str, tab_size: usize) -> String {
+    if !line.contains('\t') { return line.to_string(); }
+    let mut out = String::with_capacity(line.len() + tab_size);
+    let mut col = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let n = tab_size.max(1) - col % tab_size.max(1);
+            out.extend(std::iter::repeat_n(' ', n));
+            col += n;
+        } else {
+            out.push(c);
+            col += 1;
+        }
+    }
+    out
+}
+
+/// Contents of a file at some revision, or in the work tree.
+pub(crate) struct BlobView {
+    /// `None` for the work tree.
+    commit: Option<Oid>,
+    path: String,
+    id: Oid,
+    lines: Vec<String>,
+    binary: bool,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+}
+
+impl BlobView {
+    pub(crate) fn open(state: &AppState, commit: Oid, path: String, id: Oid) -> Result<Self> {
+        let Some(repo) = state.repo.as_ref() else { anyhow::bail!("Not a git repository") };
+        let blob = repo.find_blob(id)?;
+        Ok(Self::new(state, Some(commit), path, id, blob.content(), blob.is_binary()))
+    }
+
+    pub(crate) fn open_workdir(state: &AppState, path: String) -> Result<Self> {
+        let Some(workdir) = state.repo.as_ref().and_then(|r| r.workdir()) else { anyhow::bail!("The work tree is not available") };
+        let content = std::fs::read(workdir.join(&path))?;
+        let id = Oid::hash_object(git2::ObjectType::Blob, &content)?;
+        let binary = content.contains(&0);
+        Ok(Self::new(state, None, path, id, &content, binary))
+    }
+
+    fn new(state: &AppState, commit: Option<Oid>, path: String, id: Oid, content: &[u8], binary: bool) -> Self {
+        let lines = if binary {
+            let mut lines = vec![format!("Binary file, {} bytes", content.len()), String::new()];
+            lines.extend(hex_dump(&content[..content.len().min(HEX_DUMP_LIMIT)]));
+            if content.len() > HEX_DUMP_LIMIT { lines.push(format!("… {} more bytes", content.len() - HEX_DUMP_LIMIT)); }
+            lines
+        } else {
+            String::from_utf8_lossy(content).lines().map(|l| expand_tabs(l, state.settings.tab_size)).collect()
+        };
+        Self { commit, path, id, lines, binary, idx: 0, offset: 0, height: 0, message: None }
+    }
+
+    /// Put the cursor on `lineno`, starting at 1.
+    pub(crate) fn at_line(mut self, lineno: usize) -> Self {
+        self.idx = lineno.saturating_sub(1).min(self.lines.len().saturating_sub(1));
+        self
+    }
+
+    /// Open the file in the editor at the current line: the work tree copy
+    /// when it matches the blob, otherwise a temporary copy.
+    fn edit(&mut self, state: &mut AppState) -> Result<()> {
+        let Some(repo) = state.repo.as_ref() else { return Ok(()) };
+        let workdir_file = repo.workdir().map(|d| d.join(&self.path));
+        let unchanged = workdir_file.as_ref()
+            .and_then(|f| std::fs::read(f).ok())
+            .is_some_and(|data| Oid::hash_object(git2::ObjectType::Blob, &data).ok() == Some(self.id));
+        let lineno = if self.binary { 0 } else { self.idx + 1 };
+        let foreground = match workdir_file {
+            Some(file) if unchanged => Foreground { command: editor_command(state, &file, lineno), cleanup: None, pause: false },
+            _ => {
+                let name = Path::new(&self.path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                let file = std::env::temp_dir().join(format!("tigblob.{}.{name}", short_id(&self.id)));
+                std::fs::write(&file, repo.find_blob(self.id)?.content())?;
+                Foreground { command: editor_command(state, &file, lineno), cleanup: Some(file), pause: false }
+            }
+        };
+        state.foreground = Some(foreground);
+        Ok(())
+    }
+
+    fn ext(&self) -> Option<&str> { Path::new(&self.path).extension().and_then(|e| e.to_str()) }
+}
+
+impl View<AppState> for BlobView {
+    fn keymap(&self) -> &'static str { "blob" }
+    fn title(&self) -> String {
+        match self.commit {
+            Some(commit) => format!("tig-rs — blob: {} @ {}", self.path, short_id(&commit)),
+            None => format!("tig-rs — blob: {}", self.path),
+        }
+    }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let mut fs = key_hints(state, self.keymap(), &[
+            (&["edit"], "edit"), (&["view-blame"], "blame"), (&[":toggle blob-view-line-number"], "line numbers"),
+            (&[":toggle syntax-highlight"], "syntax"), (&["view-close"], "close"),
+        ]);
+        if let Some(msg) = &self.message {
+            fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+        }
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let columns = state.settings.view_columns("blob");
+        let numbers = columns.get(ColumnType::LineNumber).filter(|column| column.shown() && !self.binary);
+        let width = numbers.map_or(0, |column| column_width(column, chunks[0].width as usize, std::iter::once(self.lines.len().to_string().len())));
+        let highlight = state.settings.syntax_highlight && !self.binary;
+        let view = self.keymap();
+        let items: Vec<ListItem> = self.lines.iter().enumerate().skip(self.offset).take(self.height).map(|(i, text)| {
+            let mut spans = Vec::new();
+            if let Some(column) = numbers {
+                spans.push(Span::styled(format!("{}│ ", line_number_text(column, i + 1, width)), state.theme.style(view, LineType::LineNumber)));
+            }
+            let code = if highlight { Line::from(highlight_code(text, self.ext(), &state.theme, view)) } else { Line::raw(text.clone()) };
+            spans.extend(state.highlight(view, code).spans);
+            ListItem::new(Line::from(spans))
+        }).collect();
+        let list = List::new(items)
+            .block(view_block(state, view, self.title()))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        let selected = if self.lines.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.lines.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::Edit => {
+                if let Err(e) = self.edit(state) { self.message = Some(e.to_string()); }
+            }
+            Request::ViewBlame if self.binary => self.message = Some("Cannot blame binary files".into()),
+            Request::ViewBlame => match BlameView::open(state, &self.path, self.commit, self.idx + 1) {
+                Ok(view) => return Transition::Push(Box::new(view)),
+                Err(e) => self.message = Some(e.to_string()),
+            },
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> { Some((self.lines.clone(), self.idx)) }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.lines.len()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.lines.get(self.idx).cloned() }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        if let Some(commit) = self.commit { commit_vars(state, commit, vars); }
+        vars.set("blob", self.id.to_string());
+        vars.set("file", self.path.clone());
+        if !self.binary { vars.set("lineno", (self.idx + 1).to_string()); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tigrs_core::{Keymaps, Settings, Theme};
+    use tigrs_git::test_repo::{checkout, commit, test_repo};
+    use tigrs_git::RevSpec;
+
+    #[test]
+    fn hex_dump_lines() {
+        let dump = hex_dump(b"\x00\x01abc\tdefghijklmnopq");
+        assert_eq!(dump, [
+            "00000000  00 01 61 62 63 09 64 65 66 67 68 69 6a 6b 6c 6d  ..abc.defghijklm",
+            "00000010  6e 6f 70 71                                      nopq",
+        ]);
+    }
+
+    #[test]
+    fn expand_tabs_to_stops() {
+        assert_eq!(expand_tabs("a\tb\t\tc", 4), "a   b       c");
+        assert_eq!(expand_tabs("abcd\te", 4), "abcd    e");
+        assert_eq!(expand_tabs("\tx", 0), " x");
+    }
+
+    #[test]
+    fn binary_summary() {
+        let state = AppState::new(Settings::default(), Theme::default(), Keymaps::default(), None, None, RevSpec::default());
+        let id = Oid::zero();
+        let small = BlobView::new(&state, None, "bin".into(), id, b"ab\0", true);
+        assert_eq!(small.lines, ["Binary file, 3 bytes", "", "00000000  61 62 00                                         ab."]);
+        let large = vec![0u8; HEX_DUMP_LIMIT + 5];
+        let large = BlobView::new(&state, None, "bin".into(), id, &large, true);
+        assert_eq!(large.lines.len(), 2 + HEX_DUMP_LIMIT / 16 + 1);
+        assert_eq!(large.lines.last().map(String::as_str), Some("… 5 more bytes"));
+    }
+
+    #[test]
+    fn edit_work_tree_file_or_temp_copy() {
+        let repo = test_repo("blob-edit");
+        let head = commit(&repo, "first", &[], &[("file", "one\ntwo\nthree\n"), ("bin", "a\0b")]);
+        checkout(&repo, head);
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let mut state = AppState::new(Settings::default(), Theme::default(), Keymaps::default(), Some(git2::Repository::open(repo.path()).unwrap()), None, RevSpec::default());
+        let blob_id = |path: &str| repo.find_commit(head).unwrap().tree().unwrap().get_name(path).unwrap().id();
+        let args = |state: &mut AppState| {
+            let fg = state.foreground.take().unwrap();
+            (fg.command.get_args().skip(3).map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>(), fg.cleanup)
+        };
+
+        assert!(BlobView::open_workdir(&state, "bin".into()).unwrap().binary);
+        let mut view = BlobView::open(&state, head, "file".into(), blob_id("file")).unwrap().at_line(2);
+        view.edit(&mut state).unwrap();
+        assert_eq!(args(&mut state), (vec!["+2".to_string(), workdir.join("file").display().to_string()], None));
+
+        state.settings.editor_line_number = false;
+        view.edit(&mut state).unwrap();
+        assert_eq!(args(&mut state).0, [workdir.join("file").display().to_string()]);
+
+        // Once the work tree file differs, a copy of the blob is edited.
+        state.settings.editor_line_number = true;
+        std::fs::write(workdir.join("file"), "changed\n").unwrap();
+        view.edit(&mut state).unwrap();
+        let (argv, cleanup) = args(&mut state);
+        let copy = cleanup.unwrap();
+        let content = std::fs::read_to_string(&copy).unwrap();
+        std::fs::remove_file(&copy).unwrap();
+        assert_eq!(argv, ["+2".to_string(), copy.display().to_string()]);
+        assert_eq!(content, "one\ntwo\nthree\n");
+
+        // Binary files are opened without a line number.
+        let mut view = BlobView::open(&state, head, "bin".into(), blob_id("bin")).unwrap();
+        view.edit(&mut state).unwrap();
+        assert_eq!(args(&mut state), (vec![workdir.join("bin").display().to_string()], None));
+    }
+}
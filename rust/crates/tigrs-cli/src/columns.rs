@@ -0,0 +1,70 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use tigrs_core::ViewColumn;
+use tigrs_git::{format_date, relative_date, Ident};
+
+/// Width of `column` given the widths of its cells: the `width` option
+/// when set, or else the widest cell up to `maxwidth`.
+pub(crate) fn column_width(column: &ViewColumn, view_width: usize, cells: impl Iterator<Item = usize>) -> usize {
+    match column.number("width", view_width) {
+        Some(width) if width > 0 => width,
+        _ => {
+            let widest = cells.max().unwrap_or(0);
+            column.number("maxwidth", view_width).filter(|max| *max > 0).map_or(widest, |max| widest.min(max))
+        }
+    }
+}
+
+/// `text` padded to `width` characters, or cut with a `~` marking the cut.
+pub(crate) fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width { return format!("{text:width$}"); }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 { cut.push('~'); }
+    cut
+}
+
+/// An author or committer as the column's `display` asks; names in
+/// columns 1 to 10 wide are abbreviated as in tig.
+pub(crate) fn ident_text(column: &ViewColumn, ident: &Ident) -> String {
+    match column.display.as_str() {
+        "email" => ident.email.to_string(),
+        "email-user" => ident.email_user().to_string(),
+        "abbreviated" => ident.initials(),
+        _ if column.number("width", 0).is_some_and(|width| (1..=10).contains(&width)) => ident.initials(),
+        _ => ident.name.to_string(),
+    }
+}
+
+/// A date as the column's `display` asks; `default` is tig's
+/// `%Y-%m-%d %H:%M %z`.
+pub(crate) fn date_text(column: &ViewColumn, secs: i64) -> String {
+    match column.display.as_str() {
+        "relative" | "relative-compact" => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+            relative_date(secs, now, column.display == "relative-compact")
+        }
+        "custom" => format_date(secs, column.option("format").unwrap_or("%Y-%m-%d %H:%M %z")),
+        _ => format_date(secs, "%Y-%m-%d %H:%M %z"),
+    }
+}
+
+/// Line `lineno` in a line number column `width` wide, numbered on the
+/// first line and every `interval` lines.
+pub(crate) fn line_number_text(column: &ViewColumn, lineno: usize, width: usize) -> String {
+    let interval = column.number("interval", 0).filter(|n| *n > 0).unwrap_or(1);
+    let label = if lineno == 1 || lineno.is_multiple_of(interval) { lineno.to_string() } else { String::new() };
+    format!("{label:>width$}")
+}
+
+/// A file size as the column's `display` asks: in bytes, or for `units`
+/// with binary prefixes such as `12.2K`.
+pub(crate) fn file_size_text(column: &ViewColumn, size: usize) -> String {
+    const UNITS: [char; 6] = ['B', 'K', 'M', 'G', 'T', 'P'];
+    if column.display != "units" { return size.to_string(); }
+    let (mut value, mut unit) = (size as f64, 0);
+    while value > 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let tenths = (value * 10.0) as u64;
+    if tenths.is_multiple_of(10) { format!("{value:.0}{}", UNITS[unit]) } else { format!("{value:.1}{}", UNITS[unit]) }
+}
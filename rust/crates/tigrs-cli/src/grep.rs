@@ -0,0 +1,277 @@
+use anyhow::Result;
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use std::path::Path;
+use tigrs_core::{ColumnType, CommandVars, LineType, Request, ViewColumn};
+use tigrs_git::{short_id, GrepMatch, GrepQuery, GrepSearch};
+use tigrs_tui::{Prompt, PromptResult, Transition, TuiFrame, View};
+
+use crate::{
+    columns::{column_width, fit, line_number_text},
+    commit_vars, editor_command, generic_request, key_hints, list_state, move_selection, view_block, AppState, BlameView, BlobView, Foreground,
+};
+
+enum GrepRow {
+    /// Group header, unless the file-name column is shown.
+    File(String),
+    Match(GrepMatch),
+}
+
+impl GrepRow {
+    fn location(&self) -> (&str, usize) {
+        match self {
+            GrepRow::File(path) => (path, 1),
+            GrepRow::Match(m) => (&m.path, m.lineno),
+        }
+    }
+}
+
+/// Lines matching a pattern, with the columns of the `grep-view` setting.
+/// Without a file-name column, file names head each group of matches.
+pub(crate) struct GrepView {
+    query: Option<GrepQuery>,
+    search: Option<GrepSearch>,
+    rows: Vec<GrepRow>,
+    matches: usize,
+    prompt: Option<Prompt>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+}
+
+/// Whether the `grep-view` setting shows file names on every line.
+fn file_names(state: &AppState) -> bool {
+    state.settings.view_columns("grep").get(ColumnType::FileName).is_some_and(|c| c.shown())
+}
+
+impl GrepView {
+    /// Search right away when `query` is given, else ask for a pattern.
+    pub(crate) fn new(state: &AppState, query: Option<GrepQuery>) -> Self {
+        let mut view = Self {
+            query: None, search: None, rows: Vec::new(), matches: 0, prompt: None,
+            idx: 0, offset: 0, height: 0, message: None,
+        };
+        match query {
+            Some(query) => view.start(state, query),
+            None => view.prompt = Some(Prompt::new("grep: ")),
+        }
+        view
+    }
+
+    fn start(&mut self, state: &AppState, query: GrepQuery) {
+        let Some(repo) = state.repo.as_ref() else {
+            self.message = Some("Not a git repository".into());
+            return;
+        };
+        match GrepSearch::spawn(repo.path().to_path_buf(), query.clone()) {
+            Ok(search) => {
+                self.search = Some(search);
+                self.query = Some(query);
+                self.rows.clear();
+                self.matches = 0;
+                self.idx = 0;
+                self.offset = 0;
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    fn poll(&mut self, state: &AppState) {
+        let Some(search) = self.search.as_mut() else { return };
+        for m in search.poll() {
+            let new_file = match self.rows.last() {
+                Some(row) => row.location().0 != m.path,
+                None => true,
+            };
+            if new_file && !file_names(state) { self.rows.push(GrepRow::File(m.path.clone())); }
+            // Start on the first match rather than its group header.
+            if self.matches == 0 { self.idx = self.rows.len(); }
+            self.matches += 1;
+            self.rows.push(GrepRow::Match(m));
+        }
+        if !search.is_loading() {
+            if let Some(e) = search.error() {
+                self.message = Some(e.to_string());
+            } else if self.rows.is_empty() {
+                self.message = Some("No matches found".into());
+            }
+            self.search = None;
+        }
+    }
+
+    fn open_blob(&self, state: &AppState, path: &str, lineno: usize) -> Result<Transition<AppState>> {
+        let view = match self.query.as_ref().and_then(|q| q.rev) {
+            Some(rev) => {
+                let Some(repo) = state.repo.as_ref() else { return Ok(Transition::None) };
+                let id = repo.find_commit(rev)?.tree()?.get_path(Path::new(path))?.id();
+                BlobView::open(state, rev, path.to_string(), id)?
+            }
+            None => BlobView::open_workdir(state, path.to_string())?,
+        };
+        Ok(Transition::Split(Box::new(view.at_line(lineno))))
+    }
+
+    fn title_text(&self) -> String {
+        let Some(query) = &self.query else { return "tig-rs — grep".into() };
+        let mut flags = String::new();
+        if query.ignore_case { flags.push_str("-i "); }
+        if query.fixed { flags.push_str("-F "); }
+        match query.rev {
+            Some(rev) => format!("tig-rs — grep: {flags}{} @ {}", query.pattern, short_id(&rev)),
+            None => format!("tig-rs — grep: {flags}{}", query.pattern),
+        }
+    }
+
+    /// The text `/` searches on line `i`.
+    fn search_line(&self, i: usize, state: &AppState) -> Option<String> {
+        Some(match self.rows.get(i)? {
+            GrepRow::File(path) => path.clone(),
+            GrepRow::Match(m) if file_names(state) => format!("{} {}", m.path, m.text),
+            GrepRow::Match(m) => m.text.clone(),
+        })
+    }
+}
+
+impl View<AppState> for GrepView {
+    fn keymap(&self) -> &'static str { "grep" }
+    fn title(&self) -> String { self.title_text() }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        self.poll(state);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let fs = if let Some(prompt) = &self.prompt {
+            vec![Span::raw(prompt.prompt())]
+        } else {
+            let mut fs = key_hints(state, self.keymap(), &[
+                (&["enter"], "open"), (&["edit"], "edit"), (&["view-blame"], "blame"), (&["view-grep"], "new search"), (&["view-close"], "close"),
+            ]);
+            fs.push(Span::raw(format!("{} matches  ", self.matches)));
+            if let Some(msg) = &self.message {
+                fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+            }
+            fs
+        };
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let view_width = chunks[0].width as usize;
+        let columns = state.settings.view_columns("grep");
+        let shown: Vec<(&ViewColumn, usize)> = columns.shown().map(|column| {
+            let width = match column.kind {
+                ColumnType::FileName => column_width(column, view_width, self.rows.iter().map(|r| r.location().0.chars().count())),
+                ColumnType::LineNumber => column_width(column, view_width, self.rows.iter().map(|r| r.location().1.to_string().len())),
+                _ => 0,
+            };
+            (column, width)
+        }).collect();
+        let view = self.keymap();
+        let items: Vec<ListItem> = self.rows.iter().skip(self.offset).take(self.height).map(|row| match row {
+            GrepRow::File(path) => {
+                ListItem::new(state.highlight(view, Line::from(Span::styled(path.clone(), state.theme.style(view, LineType::File)))))
+            }
+            GrepRow::Match(m) => {
+                let spans: Vec<Span> = shown.iter().map(|&(column, width)| match column.kind {
+                    ColumnType::FileName => Span::styled(format!("{} ", fit(&m.path, width)), state.theme.style(view, LineType::File)),
+                    ColumnType::LineNumber => {
+                        Span::styled(format!("{}│ ", line_number_text(column, m.lineno, width)), state.theme.style(view, LineType::LineNumber))
+                    }
+                    _ => Span::raw(m.text.clone()),
+                }).collect();
+                ListItem::new(state.highlight(view, Line::from(spans)))
+            }
+        }).collect();
+        let title = if self.search.is_some() { format!("{} (searching…)", self.title()) } else { self.title() };
+        let list = List::new(items)
+            .block(view_block(state, view, title))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        let selected = if self.rows.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn wants_input(&self) -> bool { self.prompt.is_some() }
+    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
+        if let Some(prompt) = self.prompt.as_mut() {
+            match prompt.handle(ev) {
+                PromptResult::Pending => {}
+                PromptResult::Cancel => {
+                    self.prompt = None;
+                    if self.query.is_none() { return Transition::Back; }
+                }
+                PromptResult::Submit(text) => {
+                    self.prompt = None;
+                    let args: Vec<&str> = text.split_whitespace().collect();
+                    if args.is_empty() {
+                        if self.query.is_none() { return Transition::Back; }
+                        return Transition::None;
+                    }
+                    let parsed = state.repo.as_ref().map(|repo| GrepQuery::parse(repo, &args));
+                    match parsed {
+                        Some(Ok(query)) => self.start(state, query),
+                        Some(Err(e)) => self.message = Some(e.to_string()),
+                        None => self.message = Some("Not a git repository".into()),
+                    }
+                }
+            }
+        }
+        Transition::None
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.rows.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::ViewGrep => self.prompt = Some(Prompt::new("grep: ")),
+            Request::Enter | Request::ViewBlob => if let Some(row) = self.rows.get(self.idx) {
+                let (path, lineno) = row.location();
+                match self.open_blob(state, path, lineno) {
+                    Ok(t) => return t,
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+            },
+            Request::Edit => if let Some(row) = self.rows.get(self.idx) {
+                let (path, lineno) = row.location();
+                match state.repo.as_ref().and_then(|r| r.workdir()) {
+                    Some(workdir) => {
+                        let command = editor_command(state, &workdir.join(path), lineno);
+                        state.foreground = Some(Foreground { command, cleanup: None, pause: false });
+                    }
+                    None => self.message = Some("The work tree is not available".into()),
+                }
+            },
+            Request::ViewBlame => if let Some(row) = self.rows.get(self.idx) {
+                let (path, lineno) = row.location();
+                let rev = self.query.as_ref().and_then(|q| q.rev);
+                match BlameView::open(state, path, rev, lineno) {
+                    Ok(view) => return Transition::Push(Box::new(view)),
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+            },
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.rows.len()).filter_map(|i| self.search_line(i, state)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.rows.len()) }
+    fn cursor_text(&self, state: &AppState) -> Option<String> { self.search_line(self.idx, state) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { true }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        if let Some(rev) = self.query.as_ref().and_then(|q| q.rev) { commit_vars(state, rev, vars); }
+        if let Some((path, lineno)) = self.rows.get(self.idx).map(GrepRow::location) {
+            vars.set("file", path);
+            vars.set("lineno", lineno.to_string());
+        }
+    }
+}
@@ -1,42 +1,108 @@
 use anyhow::Result;
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style, Stylize},
+    style::Style,
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, Paragraph},
     Terminal,
 };
-use std::io::{self, stdout};
-use tigrs_core::Settings;
-use tigrs_git::{discover_repo, recent_commits, commit_diff_text, oid_from_str, CommitInfo};
-use tigrs_tui::{Router, Transition, View, TuiFrame};
+use std::{
+    io::{self, stdout},
+    process::Stdio,
+};
+use tigrs_core::{history_path, CommandVars, Config, ExternalCommand, IgnoreSpace, Keymaps, LineGraphics, LineType, Request, Settings, Severity, Theme};
+use tigrs_git::graph::{Graph, GraphRow, GraphStyle};
+use git2::Oid;
+use tigrs_git::{blame_file, discover_repo, commit_diff_text, short_id, CommitInfo, DiffParent, DiffSettings, FileBlame, CommitLoader, GrepQuery, RefDb, RefFormat, RefKind, RevSpec};
+use tigrs_tui::{History, Router, RouterState, RunOutput, Search, Transition, View, TuiFrame};
+
+mod blame;
+mod blob;
+mod columns;
+mod grep;
+mod log;
+mod reflog;
+mod refs;
+mod stage;
+mod stash;
+mod status;
+mod tree;
+
+use blame::BlameView;
+use blob::BlobView;
+use grep::GrepView;
+use log::LogView;
+use reflog::ReflogView;
+use refs::RefsView;
+use stash::StashView;
+use status::StatusView;
+use tree::TreeView;
 
 #[derive(Debug, Parser)]
 #[command(name = "tig-rs", version, about = "Experimental Rust rewrite scaffold for Tig")]
 pub struct Args {
-    /// Number of commits to show
-    #[arg(short = 'n', long, default_value_t = 50)]
-    limit: usize,
-    /// Start path for repository discovery
-    #[arg()] 
+    /// Maximum number of commits to load (default: all)
+    #[arg(short = 'n', long)]
+    limit: Option<usize>,
+    /// Start path for repository discovery; a directory given as the first
+    /// positional argument still works when it is the root of a repository
+    /// or not inside the current one
+    #[arg(short = 'C', long = "repo")]
     path: Option<String>,
+    /// Revisions and paths to show, as for `git log` (e.g. `main..topic -- src`),
+    /// `blame [rev] [--] <file>` to start in the blame view,
+    /// `grep [-i] [-F] <pattern> [rev]` to start in the grep view, or
+    /// `log [--follow] [revs] [--] [paths]` to start in the log view
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    revs: Vec<String>,
+}
+
+impl Args {
+    /// Older versions took the repository as the only positional argument;
+    /// keep accepting it there unless it reads as a path of this repository.
+    fn take_legacy_repo_path(&mut self) {
+        let Some(first) = self.revs.first().filter(|_| self.path.is_none()) else { return };
+        if !std::path::Path::new(first).is_dir() { return; }
+        let Ok(repo) = discover_repo(Some(first)) else { return };
+        let canonical = |p: &std::path::Path| p.canonicalize().ok();
+        let other_repo = discover_repo(None).map_or(true, |cwd| canonical(cwd.path()) != canonical(repo.path()));
+        let repo_root = repo.workdir().and_then(canonical) == canonical(std::path::Path::new(first));
+        if other_repo || repo_root { self.path = Some(self.revs.remove(0)); }
+    }
 }
 
 pub fn run() -> Result<()> {
-    let args = Args::parse();
-    let settings = Settings::load().unwrap_or_default();
+    let mut args = Args::parse();
+    args.take_legacy_repo_path();
+    let mut config = Config::new(Settings::load().unwrap_or_default());
+    config.load_tigrc();
+    for d in &config.diagnostics {
+        let kind = match d.severity { Severity::Warning => "warning", Severity::Error => "error" };
+        eprintln!("tig-rs {kind}: {d}");
+    }
 
     let repo = discover_repo(args.path.as_deref()).ok();
-    let commits = match repo.as_ref().and_then(|r| recent_commits(r, args.limit).ok()) {
-        Some(list) => list,
-        None => Vec::new(),
+    let start = match (repo.as_ref(), args.revs.split_first()) {
+        (Some(r), Some((cmd, rest))) if cmd == "blame" => StartView::Blame(blame_args(r, rest)?),
+        (Some(r), Some((cmd, rest))) if cmd == "grep" => StartView::Grep(GrepQuery::parse(r, rest)?),
+        (Some(r), Some((cmd, rest))) if cmd == "log" => { let (spec, follow) = log_args(r, rest)?; StartView::Log(spec, follow) }
+        (None, Some((cmd, _))) if ["blame", "grep", "log"].contains(&cmd.as_str()) => anyhow::bail!("Not a git repository"),
+        _ => StartView::Main,
+    };
+    let spec = match (repo.as_ref(), &start) {
+        (Some(r), StartView::Main) => RevSpec::parse(r, &args.revs)?,
+        _ => RevSpec::default(),
+    };
+    let loader = match (repo.as_ref(), &start) {
+        (Some(r), StartView::Main) => Some(CommitLoader::spawn(r.path().to_path_buf(), spec.clone(), args.limit)),
+        _ => None,
     };
 
     enable_raw_mode()?;
@@ -45,7 +111,7 @@ pub fn run() -> Result<()> {
     let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal, commits, settings, repo);
+    let res = run_app(&mut terminal, loader, spec, config, repo, start);
 
     disable_raw_mode()?;
     execute!(stdout, LeaveAlternateScreen, DisableMouseCapture)?;
@@ -53,30 +119,298 @@ pub fn run() -> Result<()> {
     Ok(())
 }
 
+/// Apply git's `color.*` settings as mapped by the `git-colors` option.
+fn load_git_colors(theme: &mut Theme, config: &git2::Config, mappings: &str) {
+    let Ok(entries) = config.entries(Some("color\\..*")) else { return };
+    let _ = entries.for_each(|entry| {
+        if let (Some(name), Some(value)) = (entry.name().and_then(|n| n.strip_prefix("color.")), entry.value()) {
+            theme.git_color(name, value, mappings);
+        }
+    });
+}
+
+/// The view tig-rs starts in, picked by the first argument.
+enum StartView {
+    Main,
+    Blame(FileBlame),
+    Grep(GrepQuery),
+    Log(RevSpec, bool),
+}
+
+/// The spec of `log [--follow] [revs] [--] [paths]`, and whether to follow
+/// the file across renames.
+fn log_args(repo: &git2::Repository, args: &[String]) -> Result<(RevSpec, bool)> {
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let follow = args[..end].iter().any(|a| a == "--follow");
+    let args: Vec<String> = args.iter().enumerate().filter(|(i, a)| *i >= end || *a != "--follow").map(|(_, a)| a.clone()).collect();
+    Ok((RevSpec::parse(repo, &args)?, follow))
+}
+
+/// Blame the file named by `blame [rev] [--] <file>`.
+fn blame_args(repo: &git2::Repository, args: &[String]) -> Result<FileBlame> {
+    let words: Vec<&String> = args.iter().filter(|a| *a != "--").collect();
+    let (rev, file) = match words.as_slice() {
+        [file] => (None, *file),
+        [rev, file] => (Some(repo.revparse_single(rev)?.peel_to_commit()?.id()), *file),
+        _ => anyhow::bail!("usage: tig-rs blame [rev] [--] <file>"),
+    };
+    blame_file(repo, &repo_relative_path(repo, file), rev)
+}
+
+/// `path`, given relative to the current directory, relative to the work tree.
+fn repo_relative_path(repo: &git2::Repository, path: &str) -> String {
+    let cwd = std::env::current_dir().and_then(|d| d.canonicalize()).unwrap_or_default();
+    let workdir = repo.workdir().and_then(|d| d.canonicalize().ok()).unwrap_or_default();
+    match cwd.join(path).strip_prefix(&workdir) {
+        Ok(rel) => rel.to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
 fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    commits: Vec<CommitInfo>,
-    settings: Settings,
+    loader: Option<CommitLoader>,
+    spec: RevSpec,
+    config: Config,
     repo: Option<git2::Repository>,
+    start: StartView,
 ) -> Result<()> {
-    let mut state = AppState { settings, repo, commits };
-    let root: Box<dyn View<AppState>> = Box::new(ListView { idx: 0 });
-    let mut router = Router::new(root);
+    let Config { settings, keymaps, mut theme, .. } = config;
+    if let Some(git_config) = repo.as_ref().and_then(|r| r.config().ok()) {
+        load_git_colors(&mut theme, &git_config, &settings.git_colors);
+    }
+    let mut state = AppState::new(settings, theme, keymaps.clone(), repo, loader, spec);
+    let root: Box<dyn View<AppState>> = match start {
+        StartView::Main => Box::new(ListView::new()),
+        StartView::Blame(blame) => Box::new(BlameView::new(blame, 1)),
+        StartView::Grep(query) => Box::new(GrepView::new(&state, Some(query))),
+        StartView::Log(spec, follow) => Box::new(LogView::new(&state, spec, follow)),
+    };
+    let history = History::load(history_path(), state.settings.history_size);
+    let mut router = Router::new(root, keymaps, history);
 
     loop {
+        state.poll_loader();
         terminal.draw(|f| {
             let area = f.size();
+            f.render_widget(Block::default().style(state.theme.style("", LineType::Default)), area);
             router.render(f, area, &state);
         })?;
 
+        let mut quit = false;
         if event::poll(std::time::Duration::from_millis(100))? {
             let ev = event::read()?;
-            if router.handle_event(&ev, &mut state) { break; }
+            quit = router.handle_event(&ev, &mut state);
+        }
+        for argv in std::mem::take(&mut state.tigrc) {
+            if let Err(e) = apply_tigrc(&mut state, router.keymaps_mut(), &argv) { router.report(e.to_string()); }
+            state.keymaps = router.keymaps().clone();
         }
+        // Programs run even when quitting, as `<` commands want.
+        if let Some(fg) = state.foreground.take() {
+            run_foreground(terminal, fg)?;
+            quit = quit || router.refresh(&mut state);
+        }
+        quit = finish_background(&mut router, &mut state) || quit;
+        if quit { break; }
     }
     Ok(())
 }
 
+/// Refresh the views once `@` commands exit, reporting the first that
+/// failed; returns whether to quit.
+fn finish_background(router: &mut Router<AppState>, state: &mut AppState) -> bool {
+    let finished = state.reap_background();
+    if finished.is_empty() { return false; }
+    let quit = router.refresh(state);
+    if let Some(Err(e)) = finished.into_iter().find(Result::is_err) { router.report(e); }
+    quit
+}
+
+/// Run a `bind` or `source` command from the prompt.
+fn apply_tigrc(state: &mut AppState, keymaps: &mut Keymaps, argv: &[String]) -> Result<()> {
+    match argv {
+        [cmd, keymap, key, request @ ..] if cmd == "bind" && !request.is_empty() => keymaps.bind(keymap, key, request),
+        [cmd, ..] if cmd == "bind" => anyhow::bail!("Invalid key binding: bind keymap key action"),
+        [cmd, path] if cmd == "source" => {
+            let mut config = Config::new(state.settings.clone());
+            config.keymaps = keymaps.clone();
+            config.theme = state.theme.clone();
+            config.source(std::path::Path::new(path)).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => anyhow::anyhow!("File does not exist: {path}"),
+                _ => anyhow::anyhow!("Error loading file {path}: {e}"),
+            })?;
+            state.settings = config.settings;
+            state.theme = config.theme;
+            *keymaps = config.keymaps;
+            match config.diagnostics.first() {
+                Some(diagnostic) => anyhow::bail!("{diagnostic}"),
+                None => Ok(()),
+            }
+        }
+        _ => anyhow::bail!("Invalid source command: source <path>"),
+    }
+}
+
+/// A program that takes over the terminal, like the editor.
+struct Foreground {
+    command: std::process::Command,
+    /// Temporary file to remove once the program exits.
+    cleanup: Option<std::path::PathBuf>,
+    /// Wait for Enter before going back to the views, so the output can be read.
+    pause: bool,
+}
+
+/// Suspend the UI while `fg` runs, then restore and redraw it.
+fn run_foreground(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut fg: Foreground) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    let res = fg.command.status();
+    if let Err(e) = &res { eprintln!("Failed to run {}: {e}", fg.command.get_program().to_string_lossy()); }
+    if fg.pause || res.is_err() {
+        eprint!("Press Enter to continue");
+        let _ = io::stdin().read_line(&mut String::new());
+    }
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+    if let Some(path) = fg.cleanup { let _ = std::fs::remove_file(path); }
+    Ok(())
+}
+
+/// The editor git would use: `$GIT_EDITOR`, `core.editor`, `$VISUAL`,
+/// `$EDITOR`, then `vi`.
+fn editor_command(state: &AppState, file: &std::path::Path, lineno: usize) -> std::process::Command {
+    let configured = state.repo.as_ref().and_then(|r| r.config().ok()).and_then(|c| c.get_string("core.editor").ok());
+    let editor = std::env::var("GIT_EDITOR").ok()
+        .or(configured)
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|e| !e.is_empty())
+        .unwrap_or_else(|| "vi".into());
+    let mut command = std::process::Command::new("sh");
+    command.arg("-c").arg(format!("{editor} \"$@\"")).arg(editor);
+    if state.settings.editor_line_number && lineno > 0 { command.arg(format!("+{lineno}")); }
+    command.arg(file);
+    if let Some(dir) = state.repo.as_ref().and_then(|r| r.workdir()) { command.current_dir(dir); }
+    command
+}
+
+/// Footer spans for `(requests, description)` pairs, with each request
+/// named as in a `bind` command and shown by the keys bound to it in
+/// `keymap`. Hints with none of their requests bound are left out.
+fn key_hints(state: &AppState, keymap: &str, hints: &[(&[&str], &str)]) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for (requests, desc) in hints {
+        let keys: Vec<String> = requests.iter().filter_map(|request| {
+            let words: Vec<String> = request.split(' ').map(str::to_string).collect();
+            state.keymaps.key_for(keymap, &Request::parse(&words).ok()?)
+        }).collect();
+        if keys.is_empty() { continue; }
+        spans.push(Span::styled(keys.join("/"), state.theme.style("", LineType::HelpAction)));
+        spans.push(Span::raw(format!(": {desc}  ")));
+    }
+    spans
+}
+
+/// Move a list selection for the `move-*` requests, returning whether `req`
+/// was one of them.
+fn move_selection(req: &Request, idx: &mut usize, height: usize, last: usize) -> bool {
+    let half = (height / 2).max(1);
+    *idx = match req {
+        Request::MoveDown => idx.saturating_add(1).min(last),
+        Request::MoveUp => idx.saturating_sub(1),
+        Request::MovePageDown => idx.saturating_add(height).min(last),
+        Request::MovePageUp => idx.saturating_sub(height),
+        Request::MoveHalfPageDown => idx.saturating_add(half).min(last),
+        Request::MoveHalfPageUp => idx.saturating_sub(half),
+        Request::MoveFirstLine => 0,
+        Request::MoveLastLine => last,
+        _ => return false,
+    };
+    true
+}
+
+/// Requests every view handles the same way: closing views, opening the
+/// views that need no selection, and `:` commands.
+fn generic_request(req: &Request, state: &mut AppState) -> Result<Transition<AppState>> {
+    let view: Box<dyn View<AppState>> = match req {
+        Request::Quit => return Ok(Transition::Quit),
+        Request::ViewClose => return Ok(Transition::Back),
+        Request::ViewStatus => Box::new(StatusView::new(state)),
+        Request::ViewRefs => Box::new(RefsView::new(state)),
+        Request::ViewGrep => Box::new(GrepView::new(state, None)),
+        Request::ViewReflog => Box::new(ReflogView::new(state, "HEAD")),
+        Request::ViewLog => Box::new(LogView::new(state, state.spec.clone(), false)),
+        Request::ViewStash => Box::new(StashView::new(state)),
+        Request::ViewTree => {
+            let head = state.repo.as_ref().map(|r| r.head()?.peel_to_commit()).transpose()?;
+            let Some(head) = head else { return Ok(Transition::None) };
+            Box::new(TreeView::open(state, head.id())?)
+        }
+        Request::ViewMain => {
+            // Views opened from the command line start without commits.
+            if state.loader.is_none() && state.commits.is_empty() && state.repo.is_some() {
+                state.reload_commits(state.spec.clone());
+            }
+            return Ok(Transition::Show(Box::new(ListView::new())));
+        }
+        Request::ViewBlob => anyhow::bail!("No file chosen, open one from the tree view"),
+        Request::StopLoading => {
+            if let Some(loader) = state.loader.as_ref() { loader.stop(); }
+            return Ok(Transition::None);
+        }
+        Request::Command(argv) => { state.run_command(argv)?; return Ok(Transition::None); }
+        _ => return Ok(Transition::None),
+    };
+    Ok(Transition::Push(view))
+}
+
+/// The line of the commit `:goto <rev>` names among `ids`, for views
+/// listing commits.
+fn goto_commit(state: &AppState, argv: &[String], mut ids: impl Iterator<Item = Oid>) -> Result<usize> {
+    let [_, rev] = argv else { anyhow::bail!("goto requires an argument") };
+    let Some(repo) = state.repo.as_ref() else { anyhow::bail!("Not a git repository") };
+    let id = repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| anyhow::anyhow!("Failed to parse expression '{rev}'"))?
+        .id();
+    ids.position(|i| i == id).ok_or_else(|| anyhow::anyhow!("Unable to find commit '{rev}'"))
+}
+
+/// Set `%(commit)` to `id`, and `%(head)`, `%(branch)`, `%(remote)` and
+/// `%(tag)` from the references at it, as tig does for the selected commit.
+fn commit_vars(state: &AppState, id: Oid, vars: &mut CommandVars) {
+    vars.set("commit", id.to_string());
+    let refs = state.refs.get(&id);
+    // Detached HEAD names no branch.
+    if let Some(branch) = refs.iter().find(|r| matches!(r.kind, RefKind::Head | RefKind::Branch) && r.full_name != "HEAD") {
+        vars.set("head", id.to_string());
+        vars.set("branch", branch.name.clone());
+    }
+    if let Some(remote) = refs.iter().find(|r| r.kind.is_remote()) {
+        let (name, branch) = remote.name.split_once('/').unwrap_or(("", &remote.name));
+        vars.set("remote", name);
+        if vars.get("branch") == Some("") { vars.set("branch", branch); }
+    }
+    if let Some(tag) = refs.iter().find(|r| r.kind.is_tag()) { vars.set("tag", tag.name.clone()); }
+}
+
+/// [`generic_request`] for views showing a diff, calling `reload` when the
+/// request changed how diffs are shown.
+fn diff_view_request(req: &Request, state: &mut AppState, reload: impl FnOnce(&mut AppState)) -> Result<Transition<AppState>> {
+    let before = state.diff_settings().ok();
+    // A command can fail to save an option it did change.
+    let transition = generic_request(req, state);
+    if state.diff_settings().ok() != before { reload(state); }
+    transition
+}
+
+/// The bordered block around a view, with its title in `title-focus` colors.
+fn view_block(state: &AppState, view: &str, title: String) -> Block<'static> {
+    Block::default().title(title).title_style(state.theme.style(view, LineType::TitleFocus)).borders(Borders::ALL)
+}
+
 fn list_state(selected: Option<usize>) -> ratatui::widgets::ListState {
     let mut s = ratatui::widgets::ListState::default();
     s.select(selected);
@@ -87,124 +421,258 @@ fn list_state(selected: Option<usize>) -> ratatui::widgets::ListState {
 struct ViewData {
     title: String,
     content: String,
-    lines: Vec<Line<'static>>,
+    /// `content` colored, and whether with syntax highlighting.
+    lines: Option<(bool, Vec<Line<'static>>)>,
     scroll_pager: u16,
     scroll_diff: u16,
+    /// Lines on screen as last drawn, for paging.
+    height: usize,
+    /// Commit shown and the parent it is diffed against.
+    commit: Option<(Oid, DiffParent)>,
+    message: Option<String>,
 }
 
-fn colorize_diff(input: &str) -> Vec<Line<'static>> {
-    let mut lang: Option<String> = None;
-    let mut out = Vec::new();
-    for l in input.lines() {
-        if l.starts_with("diff --git ") {
-            out.push(Line::from(Span::styled(l.to_string(), Style::new().bold())));
-            continue;
-        }
-        if l.starts_with("+++") || l.starts_with("---") {
-            // Try to infer language from file path (b/<path> or a/<path>)
-            if let Some(path) = l.split_whitespace().nth(1) {
-                // strip a/ or b/
-                let p = path.trim_start_matches("a/").trim_start_matches("b/");
-                if let Some(ext) = p.rsplit('.').next() {
-                    lang = Some(ext.to_string());
-                }
-            }
-            out.push(Line::from(Span::styled(l.to_string(), Style::new().bold())));
-            continue;
-        }
-        if l.starts_with("@@") {
-            out.push(Line::from(Span::styled(l.to_string(), Style::new().yellow())));
-            continue;
+impl ViewData {
+    fn new(title: String, content: String) -> Self {
+        Self { title, content, lines: None, scroll_pager: 0, scroll_diff: 0, height: 0, commit: None, message: None }
+    }
+
+    /// The footer of the pager and diff views.
+    fn footer(&self, state: &AppState, view: &str) -> Line<'static> {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let mut fs = key_hints(state, view, &[
+            (&["view-close"], "back"), (&["move-down", "move-up"], "scroll"), (&["move-first-line", "move-last-line"], "top/bottom"),
+            (&[":toggle wrap-lines"], &format!("wrap={}", on_off(state.settings.wrap_lines))),
+            (&[":toggle syntax-highlight"], &format!("syn={}", on_off(state.settings.syntax_highlight))),
+            (&["next-merge-parent"], "merge parent"),
+            (&[":toggle ignore-space"], &format!("space={}", state.settings.ignore_space.name())),
+            (&[":toggle diff-context -1", ":toggle diff-context +1"], &format!("context={}", state.settings.diff_context)),
+            (&["view-next", "view-pager", "view-diff"], "switch"),
+        ]);
+        if let Some(msg) = &self.message {
+            fs.push(Span::styled(msg.clone(), state.theme.style(view, LineType::Status)));
         }
+        Line::from(fs)
+    }
 
-        // Content lines
-        if let Some(rest) = l.strip_prefix('+') {
-            let mut spans = Vec::new();
-            spans.push(Span::styled("+".to_string(), Style::new().green()));
-            spans.extend(highlight_code(rest, lang.as_deref()));
-            out.push(Line::from(spans));
-            continue;
+    /// [`diff_view_request`] for the pager and diff views, reloading the
+    /// diff and keeping what went wrong for the footer.
+    fn request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        let reload = |state: &mut AppState| if let Err(e) = self.reload(state) { self.message = Some(e.to_string()) };
+        match diff_view_request(req, state, reload) {
+            Ok(transition) => transition,
+            Err(e) => { self.message = Some(e.to_string()); Transition::None }
         }
-        if let Some(rest) = l.strip_prefix('-') {
-            let mut spans = Vec::new();
-            spans.push(Span::styled("-".to_string(), Style::new().red()));
-            spans.extend(highlight_code(rest, lang.as_deref()));
-            out.push(Line::from(spans));
-            continue;
+    }
+
+    /// The diff of commit `id`; merges are labelled with what they are
+    /// diffed against.
+    fn commit(state: &AppState, id: Oid, against: DiffParent) -> Result<Self> {
+        let Some(repo) = state.repo.as_ref() else { anyhow::bail!("Not a git repository") };
+        let commit = repo.find_commit(id)?;
+        let mut title = format!("{} {}", short_id(&id), commit.summary().unwrap_or(""));
+        match (against, commit.parent_count()) {
+            (_, 0 | 1) => {}
+            (DiffParent::Combined, _) => title.push_str(" [combined]"),
+            (DiffParent::Parent(i), n) => title.push_str(&format!(" [parent {}/{n}]", i + 1)),
         }
-        if let Some(rest) = l.strip_prefix(' ') {
-            let mut spans = Vec::new();
-            spans.push(Span::raw(" ".to_string()));
-            spans.extend(highlight_code(rest, lang.as_deref()));
-            out.push(Line::from(spans));
-            continue;
+        let mut data = Self::new(title, commit_diff_text(repo, id, against, &state.diff_settings()?)?);
+        data.commit = Some((id, against));
+        Ok(data)
+    }
+
+    /// `scroll` moved by a `move-*` request, or `None` for other requests.
+    /// Scrolling stops once the last line is on screen; with `wrap`, once
+    /// it is at the top, as wrapped lines take more rows.
+    fn scrolled(&self, req: &Request, scroll: u16, wrap: bool) -> Option<u16> {
+        let mut top = scroll as usize;
+        let last = self.content.lines().count().saturating_sub(if wrap { 1 } else { self.height });
+        if !move_selection(req, &mut top, self.height, last) { return None; }
+        Some(top.min(last).min(u16::MAX as usize) as u16)
+    }
+
+    /// The lines to search, and the one scrolled to the top.
+    fn search_lines(&self, scroll: u16) -> (Vec<String>, usize) {
+        let lines: Vec<String> = self.content.lines().map(str::to_string).collect();
+        let top = (scroll as usize).min(lines.len().saturating_sub(1));
+        (lines, top)
+    }
+
+    /// The line [`ViewData::search_lines`] puts the cursor on.
+    fn line_at(&self, scroll: u16) -> Option<String> {
+        let last = self.content.lines().count().checked_sub(1)?;
+        self.content.lines().nth((scroll as usize).min(last)).map(str::to_string)
+    }
+
+    /// Set the `%(name)` variables for the commit shown and the file line
+    /// at `scroll`.
+    fn selected_vars(&self, scroll: u16, vars: &mut CommandVars, state: &AppState) {
+        if let Some((id, _)) = self.commit { commit_vars(state, id, vars); }
+        if let Some(Some((file, lineno))) = diff_positions(&self.content).into_iter().nth(scroll as usize) {
+            vars.set("file", file);
+            vars.set("lineno", lineno.to_string());
         }
+    }
 
-        // Fallback raw line
-        out.push(Line::from(Span::raw(l.to_string())));
+    /// Switch a merge to the combined diff or its next parent's diff.
+    fn cycle_parent(&mut self, state: &AppState) {
+        let (Some(repo), Some((id, against))) = (state.repo.as_ref(), self.commit) else { return };
+        let parents = repo.find_commit(id).map_or(0, |c| c.parent_count());
+        if parents < 2 { return; }
+        if let Ok(data) = Self::commit(state, id, against.next(parents)) { *self = data; }
+    }
+
+    /// Re-read a commit diff after the diff settings changed, keeping both
+    /// scroll positions at the same place in the same file.
+    fn reload(&mut self, state: &AppState) -> Result<()> {
+        let Some((id, against)) = self.commit else { return Ok(()) };
+        let mut data = Self::commit(state, id, against)?;
+        let rescroll = |scroll: u16| {
+            let line = same_diff_position(&self.content, &data.content, scroll as usize).unwrap_or(scroll as usize);
+            line.min(u16::MAX as usize) as u16
+        };
+        data.scroll_pager = rescroll(self.scroll_pager);
+        data.scroll_diff = rescroll(self.scroll_diff);
+        *self = data;
+        Ok(())
     }
-    out
 }
 
-fn colorize_diff_basic(input: &str) -> Vec<Line<'static>> {
+/// File and new-side line number of each line of a diff; removed lines
+/// count as the line after them, file headers as line 0.
+fn diff_positions(text: &str) -> Vec<Option<(String, usize)>> {
+    let mut pos: Option<(String, usize)> = None;
+    let mut columns = 1;
     let mut out = Vec::new();
-    for l in input.lines() {
-        if l.starts_with("diff --git ") || l.starts_with("+++") || l.starts_with("---") {
-            out.push(Line::from(Span::styled(l.to_string(), Style::new().bold())));
-            continue;
-        }
-        if l.starts_with("@@") {
-            out.push(Line::from(Span::styled(l.to_string(), Style::new().yellow())));
+    for l in text.lines() {
+        if let Some(names) = l.strip_prefix("diff --git ") {
+            let name = names.rsplit_once(" b/").map_or(names, |(_, b)| b);
+            pos = Some((name.to_string(), 0));
+        } else if let Some(name) = l.strip_prefix("diff --cc ") {
+            pos = Some((name.to_string(), 0));
+        } else if l.starts_with("@@") {
+            columns = hunk_columns(l);
+            let start = l.split_whitespace().find_map(|r| r.strip_prefix('+'))
+                .and_then(|r| r.split(',').next()?.parse().ok())
+                .unwrap_or(0);
+            if let Some((_, n)) = pos.as_mut() { *n = start; }
+            out.push(pos.clone());
             continue;
         }
-        if let Some(rest) = l.strip_prefix('+') {
-            out.push(Line::from(vec![
-                Span::styled("+".to_string(), Style::new().green()),
-                Span::raw(rest.to_string()),
-            ]));
-            continue;
+        out.push(pos.clone());
+        if let (Some((_, n)), Some((prefix, _))) = (pos.as_mut(), diff_columns(l, columns)) {
+            if !prefix.contains('-') { *n += 1; }
         }
-        if let Some(rest) = l.strip_prefix('-') {
-            out.push(Line::from(vec![
-                Span::styled("-".to_string(), Style::new().red()),
-                Span::raw(rest.to_string()),
-            ]));
-            continue;
+    }
+    out
+}
+
+/// Line of `new` closest to where line `idx` of `old` was: the same line
+/// if it is still there, otherwise the first at or after the same position
+/// in the same file.
+fn same_diff_position(old: &str, new: &str, idx: usize) -> Option<usize> {
+    // Hunk headers share their position with the line below them, as
+    // removed lines do with the line after them; they are told apart by
+    // being headers and by their order.
+    let keys = |text: &str| -> Vec<Option<(String, usize, bool)>> {
+        diff_positions(text).into_iter().zip(text.lines()).map(|(pos, l)| pos.map(|(f, n)| (f, n, l.starts_with("@@")))).collect()
+    };
+    let old = keys(old);
+    let target = old.get(idx)?.clone()?;
+    let nth = old[..idx].iter().filter(|key| key.as_ref() == Some(&target)).count();
+    let (mut same, mut seen, mut last) = (None, 0, None);
+    for (i, key) in keys(new).into_iter().enumerate() {
+        let Some(key) = key else { continue };
+        if key.0 != target.0 { continue; }
+        if key == target {
+            if seen == nth { return Some(i); }
+            seen += 1;
+            same = Some(i);
+        } else if key.1 > target.1 {
+            return same.or(Some(i));
+        } else {
+            last = Some(i);
         }
-        if let Some(rest) = l.strip_prefix(' ') {
-            out.push(Line::from(vec![
-                Span::raw(" ".to_string()),
-                Span::raw(rest.to_string()),
-            ]));
+    }
+    same.or(last)
+}
+
+/// Color a diff with the theme: hunk lines by their `+`/`-` columns, with
+/// the code syntax highlighted if `syntax` is set, and other lines by the
+/// line rules.
+fn colorize_diff(input: &str, theme: &Theme, view: &str, syntax: bool) -> Vec<Line<'static>> {
+    let mut lang: Option<String> = None;
+    let mut columns = None;
+    let mut out = Vec::new();
+    for l in input.lines() {
+        if l.starts_with("diff --") {
+            columns = None;
+        } else if l.starts_with("@@") {
+            columns = Some(hunk_columns(l));
+        } else if columns.is_none() && (l.starts_with("+++ ") || l.starts_with("--- ")) {
+            // Infer the language from the file path (b/<path> or a/<path>).
+            if let Some(path) = l.split_whitespace().nth(1) {
+                let p = path.trim_start_matches("a/").trim_start_matches("b/");
+                lang = p.rsplit('.').next().map(str::to_string);
+            }
+        } else if let Some((prefix, rest)) = columns.and_then(|columns| diff_columns(l, columns)) {
+            let mut spans = vec![Span::styled(prefix.to_string(), column_style(theme, view, prefix))];
+            if syntax {
+                spans.extend(highlight_code(rest, lang.as_deref(), theme, view));
+            } else {
+                spans.push(Span::raw(rest.to_string()));
+            }
+            out.push(Line::from(spans));
             continue;
         }
-        out.push(Line::from(Span::raw(l.to_string())));
+        out.push(Line::from(Span::styled(l.to_string(), theme.line_style(view, l).unwrap_or_default())));
     }
     out
 }
 
-fn highlight_code(line: &str, ext: Option<&str>) -> Vec<Span<'static>> {
-    match ext.unwrap_or("") {
-        "rs" => highlight_with_rules(line, Lang::Rust),
-        "c" | "h" | "hpp" | "hh" | "cpp" | "cc" | "cxx" => highlight_with_rules(line, Lang::Cfamily),
-        "py" => highlight_with_rules(line, Lang::Python),
-        "js" | "jsx" | "ts" | "tsx" => highlight_with_rules(line, Lang::JsTs),
-        "go" => highlight_with_rules(line, Lang::Go),
-        "sh" | "bash" | "zsh" => highlight_with_rules(line, Lang::Shell),
-        _ => vec![Span::raw(line.to_string())],
+/// Number of `+`/`-` columns below a hunk header: one per parent, so more
+/// than one in combined diffs (`@@@ -a -b +c @@@`).
+fn hunk_columns(header: &str) -> usize { header.chars().take_while(|&c| c == '@').count().saturating_sub(1).max(1) }
+
+/// Split a content line into its `+`/`-`/` ` columns and the text.
+fn diff_columns(l: &str, columns: usize) -> Option<(&str, &str)> {
+    let prefix = l.get(..columns)?;
+    prefix.chars().all(|c| matches!(c, '+' | '-' | ' ')).then(|| (prefix, &l[columns..]))
+}
+
+fn column_style(theme: &Theme, view: &str, prefix: &str) -> Style {
+    if prefix.contains('-') {
+        theme.style(view, LineType::DiffDel)
+    } else if prefix.contains('+') {
+        theme.style(view, LineType::DiffAdd)
+    } else {
+        Style::new()
     }
 }
 
+fn highlight_code(line: &str, ext: Option<&str>, theme: &Theme, view: &str) -> Vec<Span<'static>> {
+    let lang = match ext.unwrap_or("") {
+        "rs" => Lang::Rust,
+        "c" | "h" | "hpp" | "hh" | "cpp" | "cc" | "cxx" => Lang::Cfamily,
+        "py" => Lang::Python,
+        "js" | "jsx" | "ts" | "tsx" => Lang::JsTs,
+        "go" => Lang::Go,
+        "sh" | "bash" | "zsh" => Lang::Shell,
+        _ => return vec![Span::raw(line.to_string())],
+    };
+    highlight_with_rules(line, lang, &|ty| theme.style(view, ty))
+}
+
 #[derive(Copy, Clone)]
 enum Lang { Rust, Cfamily, Python, JsTs, Go, Shell }
 
-fn highlight_with_rules(line: &str, lang: Lang) -> Vec<Span<'static>> {
+fn highlight_with_rules(line: &str, lang: Lang, style: &dyn Fn(LineType) -> Style) -> Vec<Span<'static>> {
     // Simple, single-line highlighter: strings, comments, keywords, numbers.
     // Comments (//, #) take precedence over keyword/number highlighting.
     // Strings are highlighted as a whole; no escapes handling.
-    let (comment_start, comment_marker) = match lang {
-        Lang::Python | Lang::Shell => (line.find('#'), '#'),
-        _ => (line.find("//"), '/'),
+    let comment_start = match lang {
+        Lang::Python | Lang::Shell => line.find('#'),
+        _ => line.find("//"),
     };
 
     let (code_part, comment_part) = match comment_start {
@@ -213,17 +681,16 @@ fn highlight_with_rules(line: &str, lang: Lang) -> Vec<Span<'static>> {
     };
 
     let mut spans = Vec::new();
-    spans.extend(highlight_code_tokens(code_part, lang));
+    spans.extend(highlight_code_tokens(code_part, lang, style));
     if let Some(comment) = comment_part {
-        // Color comments faintly using blue to stand out
-        spans.push(Span::styled(comment.to_string(), Style::new().blue()));
+        spans.push(Span::styled(comment.to_string(), style(LineType::CodeComment)));
     }
     spans
 }
 
 fn is_ident_char(c: char) -> bool { c.is_ascii_alphanumeric() || c == '_' }
 
-fn highlight_code_tokens(s: &str, lang: Lang) -> Vec<Span<'static>> {
+fn highlight_code_tokens(s: &str, lang: Lang, style: &dyn Fn(LineType) -> Style) -> Vec<Span<'static>> {
     let keywords: &'static [&'static str] = match lang {
         Lang::Rust => &[
             "as","break","const","continue","crate","else","enum","extern","false","fn","for","if","impl","in","let","loop","match","mod","move","mut","pub","ref","return","self","Self","static","struct","super","trait","true","type","unsafe","use","where","while","async","await","dyn",
@@ -261,7 +728,7 @@ fn highlight_code_tokens(s: &str, lang: Lang) -> Vec<Span<'static>> {
                 j += ch.len_utf8();
                 if end { break; }
             }
-            spans.push(Span::styled(s[i..j].to_string(), Style::new().yellow()));
+            spans.push(Span::styled(s[i..j].to_string(), style(LineType::CodeString)));
             i = j;
             continue;
         }
@@ -272,7 +739,7 @@ fn highlight_code_tokens(s: &str, lang: Lang) -> Vec<Span<'static>> {
                 let ch = s[j..].chars().next().unwrap();
                 if ch.is_ascii_digit() || ch == '.' { j += ch.len_utf8(); } else { break; }
             }
-            spans.push(Span::styled(s[i..j].to_string(), Style::new().cyan()));
+            spans.push(Span::styled(s[i..j].to_string(), style(LineType::CodeNumber)));
             i = j;
             continue;
         }
@@ -285,7 +752,7 @@ fn highlight_code_tokens(s: &str, lang: Lang) -> Vec<Span<'static>> {
             }
             let tok = &s[i..j];
             if keywords.contains(&tok) {
-                spans.push(Span::styled(tok.to_string(), Style::new().magenta()));
+                spans.push(Span::styled(tok.to_string(), style(LineType::CodeKeyword)));
             } else {
                 spans.push(Span::raw(tok.to_string()));
             }
@@ -303,13 +770,249 @@ fn highlight_code_tokens(s: &str, lang: Lang) -> Vec<Span<'static>> {
 
 struct AppState {
     settings: Settings,
+    theme: Theme,
     repo: Option<git2::Repository>,
     commits: Vec<CommitInfo>,
+    graph: Vec<GraphRow>,
+    graph_layout: Graph,
+    loader: Option<CommitLoader>,
+    /// Revisions and paths the main view shows.
+    spec: RevSpec,
+    refs: RefDb,
+    ref_format: RefFormat,
+    /// Program to run in the foreground after the current event.
+    foreground: Option<Foreground>,
+    search: Search,
+    /// `bind` and `source` commands from the prompt, run after the current
+    /// event as they change the keymaps.
+    tigrc: Vec<Vec<String>>,
+    /// The router's keymaps, for the keys shown in footers.
+    keymaps: Keymaps,
+    /// `@` commands running in the background, by program name.
+    background: Vec<(String, std::process::Child)>,
+}
+
+impl AppState {
+    fn new(settings: Settings, theme: Theme, keymaps: Keymaps, repo: Option<git2::Repository>, loader: Option<CommitLoader>, spec: RevSpec) -> Self {
+        let refs = repo.as_ref().and_then(|r| RefDb::load(r).ok()).unwrap_or_default();
+        let ref_format = RefFormat::parse(&settings.reference_format).unwrap_or_default();
+        Self {
+            settings, theme, repo, commits: Vec::new(), graph: Vec::new(), graph_layout: Graph::new(), loader, spec,
+            refs, ref_format, foreground: None, search: Search::default(), tigrc: Vec::new(), keymaps,
+            background: Vec::new(),
+        }
+    }
+
+    /// What became of the `@` commands that exited since the last call.
+    fn reap_background(&mut self) -> Vec<Result<(), String>> {
+        let mut done = Vec::new();
+        self.background.retain_mut(|(program, child)| {
+            done.push(match child.try_wait() {
+                Ok(None) => return true,
+                Ok(Some(status)) if status.success() => Ok(()),
+                Ok(Some(status)) => Err(format!("{program} failed: {status}")),
+                Err(e) => Err(format!("Failed to run {program}: {e}")),
+            });
+            false
+        });
+        done
+    }
+
+    /// Append commits streamed in by the background loader.
+    fn poll_loader(&mut self) {
+        let Some(loader) = self.loader.as_mut() else { return };
+        for commit in loader.poll() {
+            self.graph.push(self.graph_layout.add_commit(commit.id, &commit.parents, commit.root));
+            self.commits.push(commit);
+        }
+    }
+
+    fn is_loading(&self) -> bool { self.loader.as_ref().is_some_and(CommitLoader::is_loading) }
+
+    /// Restart the main view's commit walk on `spec`.
+    fn reload_commits(&mut self, spec: RevSpec) {
+        self.commits.clear();
+        self.graph.clear();
+        self.graph_layout = Graph::new();
+        self.loader = self.repo.as_ref().map(|r| CommitLoader::spawn(r.path().to_path_buf(), spec.clone(), None));
+        self.spec = spec;
+    }
+
+    /// Highlight what the last search matches in a line of `view`.
+    fn highlight(&self, view: &str, line: Line<'static>) -> Line<'static> {
+        self.search.highlight(line, self.theme.style(view, LineType::SearchResult))
+    }
+
+    /// How diffs are shown, from the diff settings and `diff-options`.
+    fn diff_settings(&self) -> Result<DiffSettings> {
+        let threshold = |t: u16| (t > 0).then_some(t);
+        let ignore = self.settings.ignore_space;
+        let mut settings = DiffSettings {
+            renames: threshold(self.settings.diff_rename_threshold),
+            copies: threshold(self.settings.diff_copy_threshold),
+            context: self.settings.diff_context,
+            ignore_whitespace: ignore == IgnoreSpace::All,
+            ignore_whitespace_change: ignore == IgnoreSpace::Some,
+            ignore_whitespace_eol: ignore == IgnoreSpace::AtEol,
+            ..DiffSettings::default()
+        };
+        settings.parse_options(&self.settings.diff_options)?;
+        Ok(settings)
+    }
+
+    /// Run a `:` command, given without the colon.
+    fn run_command(&mut self, argv: &[String]) -> Result<()> {
+        let name = match argv {
+            [cmd, name, arg @ ..] if cmd == "toggle" && arg.len() <= 1 => {
+                self.settings.toggle_option(name, arg.first().map(String::as_str))?;
+                name
+            }
+            [cmd, ..] if cmd == "toggle" => anyhow::bail!("Usage: toggle <option> [+N|-N]"),
+            [cmd, name, eq, values @ ..] if cmd == "set" && eq == "=" && !values.is_empty() => {
+                // Set on a copy, so a format that does not parse leaves both as they were.
+                let mut settings = self.settings.clone();
+                settings.set_option(name, values)?;
+                if name == "reference-format" { self.ref_format = RefFormat::parse(&settings.reference_format)?; }
+                self.settings = settings;
+                name
+            }
+            [cmd, ..] if cmd == "set" => anyhow::bail!("Invalid set command: set option = value"),
+            [cmd, area, fg, bg, attrs @ ..] if cmd == "color" => { self.theme.color(area, fg, bg, attrs)?; return Ok(()); }
+            [cmd, ..] if cmd == "color" => anyhow::bail!("Invalid color mapping: color area fgcolor bgcolor [attrs]"),
+            [cmd, ..] if cmd == "bind" || cmd == "source" => { self.tigrc.push(argv.to_vec()); return Ok(()); }
+            [cmd, ..] if cmd == "goto" => anyhow::bail!("Jumping to ID is not supported by this view"),
+            _ => anyhow::bail!("Unknown command: {}", argv.join(" ")),
+        };
+        // Only the option changed is saved, so values from tigrc files stay out of the config.
+        self.settings.save_option(name)
+    }
+
+    /// Re-read references after they changed.
+    fn reload_refs(&mut self) {
+        self.refs = self.repo.as_ref().and_then(|r| RefDb::load(r).ok()).unwrap_or_default();
+    }
+}
+
+impl RouterState for AppState {
+    fn settings(&self) -> &Settings { &self.settings }
+    fn search(&self) -> &Search { &self.search }
+    fn search_mut(&mut self) -> &mut Search { &mut self.search }
+    fn style(&self, view: &str, ty: LineType) -> Style { self.theme.style(view, ty) }
+
+    fn command_vars(&self) -> CommandVars {
+        let mut vars = CommandVars::default();
+        let Some(repo) = self.repo.as_ref() else { return vars };
+        vars.set("repo:git-dir", repo.path().to_string_lossy());
+        if let Ok(head) = repo.head() {
+            if let Some(id) = head.target() { vars.set("repo:head-id", id.to_string()); }
+            if let (true, Some(name), Some(branch)) = (head.is_branch(), head.name(), head.shorthand()) {
+                vars.set("repo:head", branch);
+                if let Some(remote) = repo.branch_upstream_remote(name).ok().as_ref().and_then(|b| b.as_str()) {
+                    vars.set("repo:remote", remote);
+                }
+                if let Some(upstream) = repo.branch_upstream_name(name).ok().as_ref().and_then(|b| b.as_str()) {
+                    vars.set("repo:upstream", upstream.strip_prefix("refs/remotes/").unwrap_or(upstream));
+                }
+            }
+        }
+        if let Some(workdir) = repo.workdir() {
+            vars.set("repo:worktree", workdir.to_string_lossy().trim_end_matches('/'));
+            let cwd = std::env::current_dir().and_then(|d| d.canonicalize()).unwrap_or_default();
+            if let Some(prefix) = workdir.canonicalize().ok().and_then(|w| cwd.strip_prefix(w).ok().map(|p| p.to_path_buf())) {
+                vars.set("repo:is-inside-work-tree", "true");
+                let depth = prefix.components().count();
+                if depth > 0 {
+                    vars.set("repo:prefix", format!("{}/", prefix.to_string_lossy()));
+                    vars.set("repo:cdup", "../".repeat(depth));
+                }
+            }
+        }
+        vars
+    }
+
+    /// Run from the top of the work tree, as tig does.
+    fn run(&mut self, command: &ExternalCommand, argv: Vec<String>) -> Result<RunOutput<Self>, String> {
+        let Some((program, args)) = argv.split_first() else { return Ok(RunOutput::None) };
+        let mut process = std::process::Command::new(program);
+        process.args(args);
+        if let Some(repo) = self.repo.as_ref() { process.current_dir(repo.workdir().unwrap_or(repo.path())); }
+        let failed = |e: io::Error| format!("Failed to run {program}: {e}");
+        if command.echo || command.pager {
+            let output = process.stdin(Stdio::null()).output().map_err(failed)?;
+            let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+            if command.echo { return Ok(RunOutput::Echo(text.lines().next().unwrap_or_default().to_string())); }
+            return Ok(RunOutput::View(Box::new(PagerView { data: ViewData::new(argv.join(" "), text) })));
+        }
+        if command.silent {
+            process.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+            // Before quitting there is nothing to keep drawing.
+            if command.exit {
+                process.status().map_err(failed)?;
+                return Ok(RunOutput::None);
+            }
+            self.background.push((program.clone(), process.spawn().map_err(failed)?));
+            return Ok(RunOutput::Deferred);
+        }
+        self.foreground = Some(Foreground { command: process, cleanup: None, pause: !command.quick && !command.exit });
+        Ok(RunOutput::Deferred)
+    }
+}
+
+fn graph_style(setting: LineGraphics) -> GraphStyle {
+    match setting.resolve() {
+        LineGraphics::Ascii => GraphStyle::Ascii,
+        LineGraphics::Utf8 => GraphStyle::Utf8,
+        _ => GraphStyle::Default,
+    }
+}
+
+fn graph_spans(state: &AppState, view: &str, row: &GraphRow, style: GraphStyle) -> Vec<Span<'static>> {
+    row.cells.iter().map(|cell| {
+        let text: String = cell.symbol(style).iter().collect();
+        let ty = if cell.is_commit() { LineType::GraphCommit } else { LineType::palette(cell.color as usize) };
+        Span::styled(text, state.theme.style(view, ty))
+    }).collect()
+}
+
+fn ref_style(state: &AppState, view: &str, kind: RefKind) -> Style {
+    let ty = match kind {
+        RefKind::Head => LineType::MainHead,
+        RefKind::Branch => LineType::MainRef,
+        RefKind::TrackedRemote => LineType::MainTracked,
+        RefKind::Remote => LineType::MainRemote,
+        RefKind::Tag => LineType::MainTag,
+        RefKind::LocalTag => LineType::MainLocalTag,
+        RefKind::Replace => LineType::MainReplace,
+        RefKind::Stash => LineType::MainStash,
+        RefKind::Note => LineType::MainNote,
+        RefKind::Prefetch => LineType::MainPrefetch,
+        RefKind::Other => LineType::MainOther,
+    };
+    state.theme.style(view, ty)
+}
+
+fn ref_spans(state: &AppState, view: &str, commit: &CommitInfo) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for r in state.refs.get(&commit.id) {
+        if let Some(text) = state.ref_format.format(r) {
+            spans.push(Span::styled(text, ref_style(state, view, r.kind)));
+            spans.push(Span::raw(" "));
+        }
+    }
+    spans
+}
+
+/// The text `/` searches for a commit of the main view.
+fn commit_search_line(commit: &CommitInfo) -> String { format!("{} {} {}", commit.short_id(), commit.summary, commit.author) }
+
+struct ListView { idx: usize, offset: usize, height: usize, message: Option<String> }
+impl ListView {
+    fn new() -> Self { Self { idx: 0, offset: 0, height: 0, message: None } }
 }
 
-struct ListView { idx: usize }
 impl View<AppState> for ListView {
     fn title(&self) -> String { "tig-rs — commits".into() }
+    fn keymap(&self) -> &'static str { "main" }
     fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
         // Layout: content + footer (1 line)
         let chunks = Layout::default()
@@ -317,189 +1020,405 @@ impl View<AppState> for ListView {
             .constraints([Constraint::Min(1), Constraint::Length(1)])
             .split(area);
 
-        // Colored footer
-        let mut fs = Vec::new();
-        fs.push(Span::styled("Enter", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": open  "));
-        fs.push(Span::styled("q", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": quit  "));
-        fs.push(Span::styled("j/k", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": move  "));
-        fs.push(Span::styled("w", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(format!(": wrap={}  ", if state.settings.wrap_lines { "on" } else { "off" })));
-        fs.push(Span::styled("y", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(format!(": syn={}  ", if state.settings.syntax_highlight { "on" } else { "off" })));
-        fs.push(Span::raw(format!("{} commits", state.commits.len())));
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let mut fs = key_hints(state, self.keymap(), &[
+            (&["enter"], "open"), (&["view-close"], "quit"), (&["move-down", "move-up"], "move"),
+            (&[":toggle wrap-lines"], &format!("wrap={}", on_off(state.settings.wrap_lines))),
+            (&[":toggle syntax-highlight"], &format!("syn={}", on_off(state.settings.syntax_highlight))),
+            (&[":toggle commit-title-graph"], &format!("graph={}", on_off(state.settings.commit_title_graph))),
+        ]);
+        if state.is_loading() {
+            fs.extend(key_hints(state, self.keymap(), &[(&["stop-loading"], "stop")]));
+            fs.push(Span::raw(format!("{} commits, loading…", state.commits.len())));
+        } else {
+            fs.push(Span::raw(format!("{} commits", state.commits.len())));
+        }
+        if let Some(err) = state.loader.as_ref().and_then(CommitLoader::error) {
+            fs.push(Span::styled(format!("  {err}"), state.theme.style(self.keymap(), LineType::Status)));
+        }
+        if let Some(msg) = &self.message {
+            fs.push(Span::styled(format!("  {msg}"), state.theme.style(self.keymap(), LineType::Status)));
+        }
         f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
 
-        let items: Vec<ListItem> = state.commits.iter().map(|c| {
+        // Only build items for the visible window; the list may be huge.
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        // The commits may have been reloaded since, e.g. from the refs view;
+        // while they load, keep the cursor where it was for a refresh.
+        if !state.is_loading() { self.idx = self.idx.min(state.commits.len().saturating_sub(1)); }
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let visible = state.commits.iter().enumerate().skip(self.offset).take(self.height);
+
+        let style = graph_style(state.settings.line_graphics);
+        let view = self.keymap();
+        let theme = &state.theme;
+        let items: Vec<ListItem> = visible.map(|(i, c)| {
             let mut spans: Vec<Span> = Vec::new();
-            // ID highlighted
-            let id_style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
-            spans.push(Span::styled(c.id.clone(), id_style));
+            spans.push(Span::styled(c.short_id(), theme.style(view, LineType::Id)));
             spans.push(Span::raw(" "));
 
-            // Summary with simple keyword-based coloring
-            let sum_lower = c.summary.to_lowercase();
-            let mut sum_style = Style::default();
-            if sum_lower.starts_with("feat") {
-                sum_style = Style::default().fg(Color::Green);
-            } else if sum_lower.starts_with("fix") {
-                sum_style = Style::default().fg(Color::Red);
-            } else if sum_lower.starts_with("docs") {
-                sum_style = Style::default().fg(Color::Blue);
-            } else if sum_lower.starts_with("refactor") {
-                sum_style = Style::default().fg(Color::Magenta);
+            if state.settings.commit_title_graph {
+                if let Some(row) = state.graph.get(i) {
+                    spans.extend(graph_spans(state, view, row, style));
+                    spans.push(Span::raw(" "));
+                }
             }
-            spans.push(Span::styled(c.summary.clone(), sum_style));
 
-            // Author dimmed
+            if state.settings.commit_title_refs {
+                spans.extend(ref_spans(state, view, c));
+            }
+
+            // Titles matching a `color main."/regex/"` rule get its colors.
+            let title_style = theme.view_line_style(view, &c.summary).unwrap_or_else(|| theme.style(view, LineType::MainCommit));
+            spans.push(Span::styled(c.summary.to_string(), title_style));
+
             spans.push(Span::raw(" — "));
-            spans.push(Span::styled(c.author.clone(), Style::default().fg(Color::DarkGray)));
+            spans.push(Span::styled(c.author.to_string(), theme.style(view, LineType::Author)));
 
-            ListItem::new(Line::from(spans))
+            ListItem::new(state.highlight(view, Line::from(spans)))
         }).collect();
+        let title = if state.is_loading() { format!("{} (loading…)", self.title()) } else { self.title() };
         let list = List::new(items)
-            .block(Block::default().title(self.title()).borders(Borders::ALL))
+            .block(view_block(state, view, title))
             .highlight_symbol("> ")
-            .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
-        let selected = if state.commits.is_empty() { None } else { Some(self.idx) };
+            .highlight_style(theme.style(view, LineType::Cursor));
+        let selected = if state.commits.is_empty() { None } else { Some(self.idx - self.offset) };
         let mut selection = list_state(selected);
         f.render_stateful_widget(list, chunks[0], &mut selection);
     }
-    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
-        if let Event::Key(key) = ev {
-            match key.code {
-                KeyCode::Char('w') => {
-                    state.settings.wrap_lines = !state.settings.wrap_lines;
-                    let _ = state.settings.save();
-                }
-                KeyCode::Char('q') => return Transition::Quit,
-                KeyCode::Enter => {
-                    if let (Some(repo), Some(commit)) = (state.repo.as_ref(), state.commits.get(self.idx)) {
-                        if let Ok(oid) = oid_from_str(repo, &commit.full_id) {
-                            if let Ok(text) = commit_diff_text(repo, oid) {
-                                let title = format!("{} {}", commit.id, commit.summary);
-                                let data = ViewData {
-                                    title,
-                                    content: text.clone(),
-                                    lines: colorize_diff(&text),
-                                    scroll_pager: 0,
-                                    scroll_diff: 0,
-                                };
-                                // Open Diff view by default so highlighting is visible immediately
-                                return Transition::Push(Box::new(DiffView { data }));
-                            }
-                        }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, state.commits.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::Command(argv) if argv[0] == "goto" => match goto_commit(state, argv, state.commits.iter().map(|c| c.id)) {
+                Ok(idx) => self.idx = idx,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+            Request::Refresh => {
+                state.reload_refs();
+                state.reload_commits(state.spec.clone());
+            }
+            Request::Enter | Request::ViewDiff => {
+                if let Some(commit) = state.commits.get(self.idx) {
+                    match ViewData::commit(state, commit.id, DiffParent::Combined) {
+                        // Open Diff view by default so highlighting is visible immediately
+                        Ok(data) => return Transition::Split(Box::new(DiffView { data })),
+                        Err(e) => self.message = Some(e.to_string()),
                     }
                 }
-                KeyCode::Char('j') | KeyCode::Down => {
-                    self.idx = self.idx.saturating_add(1).min(state.commits.len().saturating_sub(1));
-                }
-                KeyCode::Char('k') | KeyCode::Up => {
-                    self.idx = self.idx.saturating_sub(1);
-                }
-                KeyCode::Char('y') => {
-                    state.settings.syntax_highlight = !state.settings.syntax_highlight;
-                    let _ = state.settings.save();
+            }
+            Request::ViewTree => {
+                if let Some(id) = state.commits.get(self.idx).map(|c| c.id) {
+                    match TreeView::open(state, id) {
+                        Ok(view) => return Transition::Push(Box::new(view)),
+                        Err(e) => self.message = Some(e.to_string()),
+                    }
                 }
-                _ => {}
             }
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
         }
         Transition::None
     }
+    fn search_lines(&self, state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some((state.commits.iter().map(commit_search_line).collect(), self.idx))
+    }
+    fn line_count(&self, state: &AppState) -> Option<usize> { Some(state.commits.len()) }
+    fn cursor_text(&self, state: &AppState) -> Option<String> { state.commits.get(self.idx).map(commit_search_line) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { true }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        if let Some(commit) = state.commits.get(self.idx) { commit_vars(state, commit.id, vars); }
+    }
 }
 
 struct PagerView { data: ViewData }
 impl View<AppState> for PagerView {
     fn title(&self) -> String { self.data.title.clone() }
+    fn keymap(&self) -> &'static str { "pager" }
     fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Min(1), Constraint::Length(1)])
             .split(area);
-        // Colored footer
-        let mut fs = Vec::new();
-        fs.push(Span::styled("q", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": back  "));
-        fs.push(Span::styled("j/k", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": scroll  "));
-        fs.push(Span::styled("g/G", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": top/bottom  "));
-        fs.push(Span::styled("w", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(format!(": wrap={}  ", if state.settings.wrap_lines { "on" } else { "off" })));
-        fs.push(Span::styled("y", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(format!(": syn={}  ", if state.settings.syntax_highlight { "on" } else { "off" })));
-        fs.push(Span::styled("Tab/p/d", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-        fs.push(Span::raw(": switch"));
-        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+        f.render_widget(Paragraph::new(self.data.footer(state, self.keymap())), chunks[1]);
 
-        let block = Block::default().title(self.title()).borders(Borders::ALL);
-        let mut para = Paragraph::new(self.data.content.as_str()).block(block);
+        let block = view_block(state, self.keymap(), self.title());
+        let view = self.keymap();
+        let lines: Vec<Line> = self.data.content.lines().map(|l| state.highlight(view, Line::raw(l.to_string()))).collect();
+        let mut para = Paragraph::new(lines).block(block);
         if state.settings.wrap_lines {
             para = para.wrap(ratatui::widgets::Wrap { trim: false });
         }
+        self.data.height = chunks[0].height.saturating_sub(2).max(1) as usize;
         para = para.scroll((self.data.scroll_pager, 0));
         f.render_widget(para, chunks[0]);
     }
-    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
-        if let Event::Key(key) = ev {
-            match key.code {
-                KeyCode::Char('w') => { state.settings.wrap_lines = !state.settings.wrap_lines; let _ = state.settings.save(); }
-                KeyCode::Char('q') => return Transition::Back,
-                KeyCode::Tab | KeyCode::Char('d') | KeyCode::Char('D') => return Transition::Replace(Box::new(DiffView { data: self.data.clone() })),
-                KeyCode::Char('p') => { /* already pager */ }
-                KeyCode::Char('j') | KeyCode::Down => { self.data.scroll_pager = self.data.scroll_pager.saturating_add(1); }
-                KeyCode::Char('k') | KeyCode::Up => { self.data.scroll_pager = self.data.scroll_pager.saturating_sub(1); }
-                KeyCode::Char('g') => { self.data.scroll_pager = 0; }
-                KeyCode::Char('G') => { self.data.scroll_pager = u16::MAX; }
-                _ => {}
-            }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.data.message = None;
+        if let Some(scroll) = self.data.scrolled(req, self.data.scroll_pager, state.settings.wrap_lines) {
+            self.data.scroll_pager = scroll;
+            return Transition::None;
+        }
+        match req {
+            Request::ViewNext | Request::ViewDiff => return Transition::Replace(Box::new(DiffView { data: self.data.clone() })),
+            Request::ViewPager => { /* already pager */ }
+            Request::NextMergeParent => self.data.cycle_parent(state),
+            req => return self.data.request(req, state),
         }
         Transition::None
     }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> { Some(self.data.search_lines(self.data.scroll_pager)) }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.data.content.lines().count()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.data.line_at(self.data.scroll_pager) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.data.scroll_pager = u16::try_from(line).unwrap_or(u16::MAX); }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) { self.data.selected_vars(self.data.scroll_pager, vars, state); }
 }
 
 struct DiffView { data: ViewData }
 impl View<AppState> for DiffView {
     fn title(&self) -> String { self.data.title.clone() }
+    fn keymap(&self) -> &'static str { "diff" }
     fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Min(1), Constraint::Length(1)])
             .split(area);
-        let footer = Paragraph::new(Span::raw("q: back  j/k: scroll  g/G: top/bottom  w: wrap  Tab/p/d: switch"));
-        f.render_widget(footer, chunks[1]);
+        f.render_widget(Paragraph::new(self.data.footer(state, self.keymap())), chunks[1]);
 
-        let block = Block::default().title(self.title()).borders(Borders::ALL);
-        // Always color diff headers and +/-; add code syntax when enabled
-        let lines = if state.settings.syntax_highlight {
-            self.data.lines.clone()
-        } else {
-            colorize_diff_basic(&self.data.content)
-        };
+        let block = view_block(state, self.keymap(), self.title());
+        let syntax = state.settings.syntax_highlight;
+        if self.data.lines.as_ref().is_none_or(|(s, _)| *s != syntax) {
+            self.data.lines = Some((syntax, colorize_diff(&self.data.content, &state.theme, self.keymap(), syntax)));
+        }
+        let view = self.keymap();
+        let lines: Vec<Line> = self.data.lines.iter().flat_map(|(_, lines)| lines).map(|l| state.highlight(view, l.clone())).collect();
         let mut para = Paragraph::new(lines).block(block);
         if state.settings.wrap_lines {
             para = para.wrap(ratatui::widgets::Wrap { trim: false });
         }
+        self.data.height = chunks[0].height.saturating_sub(2).max(1) as usize;
         para = para.scroll((self.data.scroll_diff, 0));
         f.render_widget(para, chunks[0]);
     }
-    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
-        if let Event::Key(key) = ev {
-            match key.code {
-                KeyCode::Char('w') => { state.settings.wrap_lines = !state.settings.wrap_lines; let _ = state.settings.save(); }
-                KeyCode::Char('y') => { state.settings.syntax_highlight = !state.settings.syntax_highlight; let _ = state.settings.save(); }
-                KeyCode::Char('q') => return Transition::Back,
-                KeyCode::Tab | KeyCode::Char('p') | KeyCode::Char('P') => return Transition::Replace(Box::new(PagerView { data: self.data.clone() })),
-                KeyCode::Char('d') => { /* already diff */ }
-                KeyCode::Char('j') | KeyCode::Down => { self.data.scroll_diff = self.data.scroll_diff.saturating_add(1); }
-                KeyCode::Char('k') | KeyCode::Up => { self.data.scroll_diff = self.data.scroll_diff.saturating_sub(1); }
-                KeyCode::Char('g') => { self.data.scroll_diff = 0; }
-                KeyCode::Char('G') => { self.data.scroll_diff = u16::MAX; }
-                _ => {}
-            }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.data.message = None;
+        if let Some(scroll) = self.data.scrolled(req, self.data.scroll_diff, state.settings.wrap_lines) {
+            self.data.scroll_diff = scroll;
+            return Transition::None;
+        }
+        match req {
+            Request::ViewNext | Request::ViewPager => return Transition::Replace(Box::new(PagerView { data: self.data.clone() })),
+            Request::ViewDiff => { /* already diff */ }
+            Request::NextMergeParent => self.data.cycle_parent(state),
+            req => return self.data.request(req, state),
         }
         Transition::None
     }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> { Some(self.data.search_lines(self.data.scroll_diff)) }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.data.content.lines().count()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.data.line_at(self.data.scroll_diff) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.data.scroll_diff = u16::try_from(line).unwrap_or(u16::MAX); }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) { self.data.selected_vars(self.data.scroll_diff, vars, state); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tigrs_git::test_repo::{branch, commit, test_repo};
+
+    fn words(values: &[&str]) -> Vec<String> { values.iter().map(|v| v.to_string()).collect() }
+
+    fn app_state() -> AppState { AppState::new(Settings::default(), Theme::default(), Keymaps::default(), None, None, RevSpec::default()) }
+
+    #[test]
+    fn bad_reference_format_changes_nothing() {
+        let mut state = app_state();
+        let before = state.settings.reference_format.clone();
+        assert!(state.run_command(&words(&["set", "reference-format", "=", "[branch]", "<label>"])).is_err());
+        assert_eq!(state.settings.reference_format, before);
+        assert_eq!(state.ref_format, RefFormat::parse(&before).unwrap());
+    }
+
+    /// Lines of a diff, given without their trailing newlines.
+    fn diff(lines: &[&str]) -> String { lines.iter().map(|l| format!("{l}\n")).collect() }
+
+    #[test]
+    fn reload_keeps_the_same_hunk() {
+        let old = diff(&[
+            "diff --git a/f b/f", "index 1111111..2222222 100644", "--- a/f", "+++ b/f",
+            "@@ -7,6 +7,7 @@", " l7", " l8", " l9", "+new", " l10", " l11", " l12",
+        ]);
+        assert!((0..12).all(|i| same_diff_position(&old, &old, i) == Some(i)));
+        // With one line of context the hunk starts later.
+        let narrow = diff(&[
+            "diff --git a/f b/f", "index 1111111..2222222 100644", "--- a/f", "+++ b/f",
+            "@@ -9,2 +9,3 @@", " l9", "+new", " l10",
+        ]);
+        assert_eq!(same_diff_position(&old, &narrow, 8), Some(6));
+        assert_eq!(same_diff_position(&old, &narrow, 5), Some(4));
+        assert_eq!(same_diff_position(&old, &narrow, 11), Some(7));
+        assert_eq!(same_diff_position(&narrow, &old, 6), Some(8));
+    }
+
+    #[test]
+    fn reload_after_a_hunk_vanished() {
+        let old = diff(&[
+            "diff --git a/f b/f", "--- a/f", "+++ b/f", "@@ -2 +2 @@", "-  x", "+x", "@@ -20 +20,2 @@", " y", "+z",
+            "diff --git a/g b/g", "--- a/g", "+++ b/g", "@@ -0,0 +1 @@", "+g",
+        ]);
+        // A removed line and the added line after it stay apart.
+        assert!((0..14).all(|i| same_diff_position(&old, &old, i) == Some(i)));
+        // Ignoring whitespace drops the first hunk of `f`.
+        let ignoring = diff(&[
+            "diff --git a/f b/f", "--- a/f", "+++ b/f", "@@ -20 +20,2 @@", " y", "+z",
+            "diff --git a/g b/g", "--- a/g", "+++ b/g", "@@ -0,0 +1 @@", "+g",
+        ]);
+        assert_eq!(same_diff_position(&old, &ignoring, 5), Some(3));
+        assert_eq!(same_diff_position(&old, &ignoring, 8), Some(5));
+        assert_eq!(same_diff_position(&old, &ignoring, 13), Some(10));
+        // Or all of it.
+        let only_g = diff(&["diff --git a/g b/g", "--- a/g", "+++ b/g", "@@ -0,0 +1 @@", "+g"]);
+        assert_eq!(same_diff_position(&old, &only_g, 5), None);
+        assert_eq!(same_diff_position(&old, &only_g, 13), Some(4));
+    }
+
+    #[test]
+    fn reload_combined_diffs() {
+        let old = diff(&[
+            "diff --cc f", "index 1111111,2222222..3333333", "--- a/f", "+++ b/f",
+            "@@@ -1,4 -1,4 +1,5 @@@", "  a", "  b", "+ c", " +d", "  e",
+        ]);
+        let positions: Vec<usize> = diff_positions(&old).into_iter().map(|p| p.unwrap().1).collect();
+        assert_eq!(positions, [0, 0, 0, 0, 1, 1, 2, 3, 4, 5]);
+        let narrow = diff(&["diff --cc f", "index 1111111,2222222..3333333", "--- a/f", "+++ b/f", "@@@ -2,0 -2,0 +3,2 @@@", "+ c", " +d"]);
+        assert_eq!(same_diff_position(&old, &narrow, 7), Some(5));
+        assert_eq!(same_diff_position(&old, &narrow, 8), Some(6));
+        assert_eq!(same_diff_position(&old, &narrow, 9), Some(6));
+    }
+
+    fn run(state: &mut AppState, binding: &[&str]) -> Result<RunOutput<AppState>, String> {
+        let command = ExternalCommand::parse(&words(binding)).unwrap();
+        let argv = command.expand(&state.command_vars(), &[]).unwrap();
+        state.run(&command, argv)
+    }
+
+    /// Wait for the `@` commands to exit, returning what became of them.
+    fn reap(state: &mut AppState) -> Vec<Result<(), String>> {
+        let mut done = Vec::new();
+        while !state.background.is_empty() {
+            done.extend(state.reap_background());
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        done
+    }
+
+    #[test]
+    fn run_by_flags() {
+        let mut state = app_state();
+        let Ok(RunOutput::Echo(line)) = run(&mut state, &["+sh", "-c", "printf 'first\\nsecond\\n'"]) else { panic!("no echo") };
+        assert_eq!(line, "first");
+        let Ok(Request::Run(command)) = Request::from_prompt("!echo a b") else { panic!("not a command") };
+        let Ok(RunOutput::View(view)) = state.run(&command, command.argv.clone()) else { panic!("no pager") };
+        assert_eq!(view.title(), "echo a b");
+
+        // `@` commands run in the background, unless tig-rs quits after them.
+        assert!(matches!(run(&mut state, &["@true"]), Ok(RunOutput::Deferred)));
+        assert_eq!(reap(&mut state), [Ok(())]);
+        assert!(matches!(run(&mut state, &["@false"]), Ok(RunOutput::Deferred)));
+        assert_eq!(reap(&mut state), [Err("false failed: exit status: 1".to_string())]);
+        assert!(matches!(run(&mut state, &["@<true"]), Ok(RunOutput::None)));
+        assert!(state.background.is_empty());
+        let Err(e) = run(&mut state, &["@no-such-program"]) else { panic!("ran a missing program") };
+        assert!(e.starts_with("Failed to run no-such-program: "));
+
+        // The rest take over the terminal, and wait for Enter unless `>` or `<`.
+        for (binding, pause) in [("!true", true), (">true", false), ("<true", false)] {
+            assert!(matches!(run(&mut state, &[binding]), Ok(RunOutput::Deferred)));
+            assert_eq!(state.foreground.take().map(|fg| fg.pause), Some(pause), "{binding}");
+        }
+    }
+
+    fn status_line(router: &mut Router<AppState>, state: &AppState) -> String {
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(60, 8)).unwrap();
+        terminal.draw(|f| router.render(f, f.size(), state)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..60).map(|x| buffer.get(x, 7).symbol()).collect::<String>().trim_end().to_string()
+    }
+
+    #[test]
+    fn run_from_bindings() {
+        let mut config = Config::default();
+        for line in ["bind main x +echo hello world", "bind main y ?<true", "bind main z @sh -c 'exit 3'"] { config.command(line).unwrap(); }
+        let mut state = app_state();
+        let mut router = Router::new(Box::new(ListView::new()), config.keymaps, History::load(None, 0));
+        let press = |router: &mut Router<AppState>, state: &mut AppState, c| {
+            router.handle_event(&event::Event::Key(event::KeyEvent::from(event::KeyCode::Char(c))), state)
+        };
+
+        assert!(!press(&mut router, &mut state, 'x'));
+        assert_eq!(status_line(&mut router, &state), "hello world");
+
+        assert!(!press(&mut router, &mut state, 'z'));
+        loop {
+            assert!(!finish_background(&mut router, &mut state));
+            if state.background.is_empty() { break; }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(status_line(&mut router, &state), "sh failed: exit status: 3");
+
+        assert!(!press(&mut router, &mut state, 'y'));
+        assert_eq!(status_line(&mut router, &state), "Run `true` and exit? [y/N]");
+        assert!(press(&mut router, &mut state, 'y'));
+        assert_eq!(state.foreground.map(|fg| fg.pause), Some(false));
+    }
+
+    #[test]
+    fn commit_vars_from_refs() {
+        let repo = test_repo("commit-vars");
+        let first = commit(&repo, "first", &[], &[]);
+        let second = commit(&repo, "second", &[first], &[]);
+        let third = commit(&repo, "third", &[first], &[]);
+        branch(&repo, "topic", first);
+        repo.reference("refs/remotes/origin/feature", second, false, "").unwrap();
+        repo.reference("refs/tags/v1", first, false, "").unwrap();
+        let state = AppState::new(Settings::default(), Theme::default(), Keymaps::default(), Some(git2::Repository::open(repo.path()).unwrap()), None, RevSpec::default());
+        let vars = |id: Oid| {
+            let mut vars = CommandVars::default();
+            commit_vars(&state, id, &mut vars);
+            ["commit", "head", "branch", "remote", "tag"].map(|name| vars.get(name).unwrap().to_string())
+        };
+        assert_eq!(vars(first), [first.to_string().as_str(), &first.to_string(), "topic", "origin", "v1"]);
+        assert_eq!(vars(second), [second.to_string().as_str(), "HEAD", "feature", "origin", ""]);
+        assert_eq!(vars(third), [third.to_string().as_str(), "HEAD", "", "origin", ""]);
+    }
+
+    #[test]
+    fn follow_in_log_arguments() {
+        let repo = test_repo("log-follow");
+        let first = commit(&repo, "first", &[], &[("README", "")]);
+        branch(&repo, "main", first);
+        let (spec, follow) = log_args(&repo, &words(&["--follow", "main", "--", "README"])).unwrap();
+        assert!(follow && spec.revs.len() == 1 && spec.paths == ["README"]);
+        // After `--` it is a path.
+        let (spec, follow) = log_args(&repo, &words(&["--", "--follow"])).unwrap();
+        assert!(!follow && spec.paths == ["--follow"]);
+    }
+
+    #[test]
+    fn main_view_errors_show_in_the_footer() {
+        let repo = test_repo("main-view-errors");
+        commit(&repo, "first", &[], &[]);
+        let mut state = AppState::new(Settings::default(), Theme::default(), Keymaps::default(), Some(git2::Repository::open(repo.path()).unwrap()), None, RevSpec::default());
+        let missing = Oid::from_bytes(&[1; 20]).unwrap();
+        state.commits.push(CommitInfo { id: missing, parents: Box::new([]), root: true, summary: "".into(), author: "".into(), time: 0 });
+        let mut view = ListView::new();
+        for req in [Request::ViewTree, Request::Enter] {
+            assert!(matches!(view.on_request(&req, &mut state), Transition::None));
+            assert!(view.message.as_deref().is_some_and(|m| m.contains(&missing.to_string())), "{:?}", view.message);
+        }
+    }
 }
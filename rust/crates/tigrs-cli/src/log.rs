@@ -0,0 +1,185 @@
+use git2::Oid;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use tigrs_core::{CommandVars, LineType, Request};
+use tigrs_git::{DiffParent, LogLoader, LogOptions, RevSpec};
+use tigrs_tui::{Transition, TuiFrame, View};
+
+use crate::{blob::expand_tabs, commit_vars, generic_request, goto_commit, key_hints, list_state, move_selection, ref_style, view_block, AppState, DiffView, ViewData};
+
+/// `git log` output for a revision spec, with the stat, file list or
+/// patch of each commit as set by `log-options`. `follow` adds `--follow`
+/// to them.
+pub(crate) struct LogView {
+    spec: RevSpec,
+    loader: Option<LogLoader>,
+    lines: Vec<String>,
+    /// First line and id of each commit, in order.
+    commits: Vec<(usize, Oid)>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+}
+
+impl LogView {
+    pub(crate) fn new(state: &AppState, spec: RevSpec, follow: bool) -> Self {
+        let mut view = Self {
+            spec, loader: None, lines: Vec::new(), commits: Vec::new(),
+            idx: 0, offset: 0, height: 0, message: None,
+        };
+        let opts = match LogOptions::parse(&state.settings.log_options) {
+            Ok(opts) => LogOptions { follow: opts.follow || follow, ..opts },
+            Err(e) => { view.message = Some(e.to_string()); LogOptions { follow, ..LogOptions::default() } }
+        };
+        match (state.repo.as_ref(), state.diff_settings()) {
+            (Some(repo), Ok(settings)) => view.loader = Some(LogLoader::spawn(repo.path().to_path_buf(), view.spec.clone(), opts, settings)),
+            (Some(_), Err(e)) => view.message = Some(e.to_string()),
+            (None, _) => view.message = Some("Not a git repository".into()),
+        }
+        view
+    }
+
+    fn poll(&mut self, state: &AppState) {
+        let Some(loader) = self.loader.as_mut() else { return };
+        for commit in loader.poll() {
+            if !self.lines.is_empty() { self.lines.push(String::new()); }
+            self.commits.push((self.lines.len(), commit.id));
+            self.lines.extend(commit.lines.iter().map(|l| expand_tabs(l, state.settings.tab_size)));
+        }
+        if !loader.is_loading() {
+            if let Some(e) = loader.error() {
+                self.message = Some(e.to_string());
+            } else if self.commits.is_empty() {
+                self.message = Some("No commits to show".into());
+            }
+            self.loader = None;
+        }
+    }
+
+    /// Index in `commits` of the commit the cursor is in.
+    fn current(&self) -> Option<usize> { self.commits.partition_point(|(start, _)| *start <= self.idx).checked_sub(1) }
+
+    fn line_spans(&self, state: &AppState, i: usize) -> Vec<Span<'static>> {
+        let line = &self.lines[i];
+        if let Ok(n) = self.commits.binary_search_by_key(&i, |(start, _)| *start) {
+            let mut spans = vec![Span::styled(line.clone(), state.theme.style(self.keymap(), LineType::Commit))];
+            if state.settings.commit_title_refs {
+                for r in state.refs.get(&self.commits[n].1) {
+                    if let Some(text) = state.ref_format.format(r) {
+                        spans.push(Span::raw(" "));
+                        spans.push(Span::styled(text, ref_style(state, self.keymap(), r.kind)));
+                    }
+                }
+            }
+            return spans;
+        }
+        vec![Span::styled(line.clone(), state.theme.line_style(self.keymap(), line).unwrap_or_default())]
+    }
+
+    fn open_diff(&mut self, state: &AppState) -> Transition<AppState> {
+        let Some(n) = self.current() else { return Transition::None };
+        match ViewData::commit(state, self.commits[n].1, DiffParent::Combined) {
+            Ok(data) => Transition::Split(Box::new(DiffView { data })),
+            Err(e) => { self.message = Some(e.to_string()); Transition::None }
+        }
+    }
+}
+
+impl View<AppState> for LogView {
+    fn keymap(&self) -> &'static str { "log" }
+    fn title(&self) -> String {
+        if self.spec.paths.is_empty() { "tig-rs — log".into() } else { format!("tig-rs — log -- {}", self.spec.paths.join(" ")) }
+    }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        self.poll(state);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let mut fs = key_hints(state, self.keymap(), &[(&["enter"], "diff"), (&["next", "previous"], "next/previous commit"), (&["view-close"], "close")]);
+        fs.push(Span::raw(format!("{} commits  ", self.commits.len())));
+        if let Some(msg) = &self.message {
+            fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+        }
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let end = (self.offset + self.height).min(self.lines.len());
+        let items: Vec<ListItem> = (self.offset..end).map(|i| ListItem::new(state.highlight(self.keymap(), Line::from(self.line_spans(state, i))))).collect();
+        let title = if self.loader.is_some() { format!("{} (loading…)", self.title()) } else { self.title() };
+        let list = List::new(items)
+            .block(view_block(state, self.keymap(), title))
+            .highlight_style(state.theme.style(self.keymap(), LineType::Cursor));
+        let selected = if self.lines.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.lines.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::Enter => return self.open_diff(state),
+            Request::Command(argv) if argv[0] == "goto" => match goto_commit(state, argv, self.commits.iter().map(|(_, id)| *id)) {
+                Ok(n) => self.idx = self.commits[n].0,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+            Request::Next => {
+                let next = self.current().map_or(0, |n| n + 1);
+                if let Some((start, _)) = self.commits.get(next) { self.idx = *start; self.offset = *start; }
+            }
+            Request::Previous => if let Some(n) = self.current() {
+                // From inside a commit go to its start, from its start to the previous one.
+                let n = if self.commits[n].0 == self.idx { n.saturating_sub(1) } else { n };
+                self.idx = self.commits[n].0;
+                self.offset = self.idx;
+            },
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> { Some((self.lines.clone(), self.idx)) }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.lines.len()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.lines.get(self.idx).cloned() }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { true }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        if let Some(n) = self.current() { commit_vars(state, self.commits[n].1, vars); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tigrs_core::{Keymaps, Settings, Theme};
+
+    #[test]
+    fn next_and_previous_commit() {
+        let mut state = AppState::new(Settings::default(), Theme::default(), Keymaps::default(), None, None, RevSpec::default());
+        let mut view = LogView {
+            spec: RevSpec::default(), loader: None, lines: vec![String::new(); 10],
+            commits: vec![(0, Oid::zero()), (4, Oid::zero()), (7, Oid::zero())],
+            idx: 0, offset: 0, height: 3, message: None,
+        };
+        let mut request = |view: &mut LogView, req| { view.on_request(&req, &mut state); (view.idx, view.current()) };
+
+        assert_eq!(request(&mut view, Request::Next), (4, Some(1)));
+        assert_eq!(request(&mut view, Request::Next), (7, Some(2)));
+        assert_eq!(request(&mut view, Request::Next), (7, Some(2)));
+        // From inside a commit, Previous goes to its start first.
+        view.idx = 9;
+        assert_eq!(request(&mut view, Request::Previous), (7, Some(2)));
+        assert_eq!(request(&mut view, Request::Previous), (4, Some(1)));
+        view.idx = 2;
+        assert_eq!(request(&mut view, Request::Previous), (0, Some(0)));
+        assert_eq!(request(&mut view, Request::Previous), (0, Some(0)));
+        assert_eq!(view.offset, 0);
+    }
+}
@@ -0,0 +1,186 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use tigrs_core::{CommandVars, LineType, Request};
+use tigrs_git::{checkout_commit, checkout_ref, reflog_entries, reset_hard, short_id, ReflogEntry, RefKind, RevSpec};
+use tigrs_tui::{Answer, Confirm, Transition, TuiFrame, View};
+
+use crate::{commit_vars, generic_request, goto_commit, key_hints, list_state, move_selection, ref_style, view_block, AppState, ListView};
+
+enum ReflogAction {
+    Checkout(ReflogEntry),
+    Reset(ReflogEntry),
+}
+
+/// Where a reference has been, newest first, with the selector, new id,
+/// old→new transition and message of each move.
+pub(crate) struct ReflogView {
+    /// `HEAD` or a reference name.
+    name: String,
+    entries: Vec<ReflogEntry>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+    confirm: Option<Confirm<ReflogAction>>,
+}
+
+impl ReflogView {
+    pub(crate) fn new(state: &AppState, name: &str) -> Self {
+        let mut view = Self {
+            name: name.to_string(), entries: Vec::new(), idx: 0, offset: 0, height: 0, message: None, confirm: None,
+        };
+        view.reload(state);
+        view
+    }
+
+    fn reload(&mut self, state: &AppState) {
+        let Some(repo) = state.repo.as_ref() else {
+            self.message = Some("Not a git repository".into());
+            return;
+        };
+        match reflog_entries(repo, &self.name) {
+            Ok(entries) => self.entries = entries,
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        if self.entries.is_empty() && self.message.is_none() { self.message = Some(format!("No reflog for {}", self.name)); }
+        self.idx = self.idx.min(self.entries.len().saturating_sub(1));
+    }
+
+    fn act(&mut self, state: &mut AppState, action: ReflogAction) {
+        let Some(repo) = state.repo.as_ref() else { return };
+        let res = match &action {
+            // Like `git checkout %(branch)`: a branch at the commit if there
+            // is one, the bare commit otherwise.
+            ReflogAction::Checkout(entry) => {
+                let branch = state.refs.get(&entry.new).iter().find(|r| matches!(r.kind, RefKind::Head | RefKind::Branch));
+                match branch {
+                    Some(info) => checkout_ref(repo, info),
+                    None => checkout_commit(repo, entry.new),
+                }
+            }
+            ReflogAction::Reset(entry) => reset_hard(repo, entry.new),
+        };
+        // The move just made shows up as the newest entry.
+        state.reload_refs();
+        self.reload(state);
+        self.message = Some(match (res, &action) {
+            (Ok(()), ReflogAction::Checkout(entry)) => format!("Checked out {}", entry.selector),
+            (Ok(()), ReflogAction::Reset(entry)) => format!("Reset to {}", entry.selector),
+            (Err(e), _) => e.to_string(),
+        });
+    }
+
+    /// The text `/` searches on line `i`.
+    fn search_line(&self, i: usize) -> Option<String> {
+        let e = self.entries.get(i)?;
+        Some(format!("{} {} {}", e.selector, short_id(&e.new), e.message))
+    }
+}
+
+impl View<AppState> for ReflogView {
+    fn keymap(&self) -> &'static str { "reflog" }
+    fn title(&self) -> String { format!("tig-rs — reflog: {}", self.name) }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let fs = if let Some(confirm) = &self.confirm {
+            vec![Span::styled(confirm.prompt(), state.theme.style(self.keymap(), LineType::Prompt))]
+        } else {
+            let mut fs = key_hints(state, self.keymap(), &[(&["enter"], "main view"), (&["checkout"], "checkout"), (&["reset-hard"], "reset --hard"), (&["view-close"], "close")]);
+            if let Some(msg) = &self.message {
+                fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+            }
+            fs
+        };
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        let selector_width = self.entries.iter().map(|e| e.selector.chars().count()).max().unwrap_or(0);
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let view = self.keymap();
+        let items: Vec<ListItem> = self.entries.iter().skip(self.offset).take(self.height).map(|entry| {
+            let old = if entry.old.is_zero() { " ".repeat(7) } else { short_id(&entry.old) };
+            let mut spans = vec![
+                Span::styled(format!("{:selector_width$} ", entry.selector), state.theme.style(view, LineType::Delimiter)),
+                Span::styled(format!("{} ", short_id(&entry.new)), state.theme.style(view, LineType::Id)),
+                Span::styled(format!("{old}→{} ", short_id(&entry.new)), state.theme.style(view, LineType::LineNumber)),
+            ];
+            if state.settings.commit_title_refs {
+                for r in state.refs.get(&entry.new) {
+                    if let Some(text) = state.ref_format.format(r) {
+                        spans.push(Span::styled(text, ref_style(state, view, r.kind)));
+                        spans.push(Span::raw(" "));
+                    }
+                }
+            }
+            spans.push(Span::raw(entry.message.clone()));
+            ListItem::new(state.highlight(view, Line::from(spans)))
+        }).collect();
+        let list = List::new(items)
+            .block(view_block(state, view, self.title()))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        let selected = if self.entries.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn wants_input(&self) -> bool { self.confirm.is_some() }
+    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
+        if let Some(confirm) = self.confirm.take() {
+            match confirm.answer(ev) {
+                Answer::Pending(confirm) => self.confirm = Some(confirm),
+                Answer::No => {}
+                Answer::Yes(action) => self.act(state, action),
+            }
+        }
+        Transition::None
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.entries.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::Command(argv) if argv[0] == "goto" => match goto_commit(state, argv, self.entries.iter().map(|e| e.new)) {
+                Ok(idx) => self.idx = idx,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+            Request::Enter => {
+                let (Some(repo), Some(entry)) = (state.repo.as_ref(), self.entries.get(self.idx)) else { return Transition::None };
+                match RevSpec::parse(repo, &[entry.new.to_string()]) {
+                    Ok(spec) => {
+                        state.reload_commits(spec);
+                        return Transition::Push(Box::new(ListView::new()));
+                    }
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+            }
+            Request::Checkout => if let Some(entry) = self.entries.get(self.idx) {
+                let question = format!("Checkout {} ({})?", entry.selector, short_id(&entry.new));
+                self.confirm = Some(Confirm::new(question, ReflogAction::Checkout(entry.clone())));
+            },
+            Request::ResetHard => if let Some(entry) = self.entries.get(self.idx) {
+                let question = format!("Reset --hard to {} ({})? Local changes will be lost", entry.selector, short_id(&entry.new));
+                self.confirm = Some(Confirm::new(question, ReflogAction::Reset(entry.clone())));
+            },
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.entries.len()).filter_map(|i| self.search_line(i)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.entries.len()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.search_line(self.idx) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        vars.set("refname", self.name.clone());
+        if let Some(entry) = self.entries.get(self.idx) { commit_vars(state, entry.new, vars); }
+    }
+}
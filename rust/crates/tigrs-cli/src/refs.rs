@@ -0,0 +1,267 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use tigrs_core::{ColumnType, CommandVars, LineType, Request, ViewColumn};
+use tigrs_git::{checkout_ref, create_branch, delete_branch, ref_entries, short_id, RefEntry, RefInfo, RefKind, RevSpec};
+use tigrs_tui::{Answer, Confirm, Prompt, PromptResult, Transition, TuiFrame, View};
+
+use crate::{
+    columns::{column_width, date_text, fit, ident_text, line_number_text},
+    commit_vars, generic_request, key_hints, list_state, move_selection, ref_style, view_block, AppState, ListView, ReflogView,
+};
+
+enum RefAction {
+    Checkout(RefInfo),
+    Delete(RefInfo),
+}
+
+/// Branches, remotes and tags with the columns of the `refs-view`
+/// setting. The first line stands for all references.
+pub(crate) struct RefsView {
+    entries: Vec<RefEntry>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+    confirm: Option<Confirm<RefAction>>,
+    /// Name of the branch to create at the selected reference.
+    prompt: Option<Prompt>,
+}
+
+impl RefsView {
+    pub(crate) fn new(state: &AppState) -> Self {
+        let mut view = Self { entries: Vec::new(), idx: 0, offset: 0, height: 0, message: None, confirm: None, prompt: None };
+        view.reload(state);
+        view
+    }
+
+    fn reload(&mut self, state: &AppState) {
+        let Some(repo) = state.repo.as_ref() else {
+            self.message = Some("Not a git repository".into());
+            return;
+        };
+        let previous = self.selected().map(|e| e.info.full_name.clone());
+        match ref_entries(repo, &state.refs) {
+            // Types hidden by reference-format are left out.
+            Ok(entries) => self.entries = entries.into_iter().filter(|e| state.ref_format.format(&e.info).is_some()).collect(),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        if let Some(name) = previous {
+            if let Some(i) = self.entries.iter().position(|e| e.info.full_name == name) { self.idx = i + 1; }
+        }
+        self.idx = self.idx.min(self.entries.len());
+    }
+
+    /// The text and style of `column` on line `idx`, unpadded.
+    fn cell(&self, state: &AppState, column: &ViewColumn, idx: usize) -> Span<'static> {
+        let view = self.keymap();
+        if column.kind == ColumnType::LineNumber {
+            return Span::styled((idx + 1).to_string(), state.theme.style(view, LineType::LineNumber));
+        }
+        let Some(entry) = idx.checked_sub(1).and_then(|i| self.entries.get(i)) else {
+            return match column.kind {
+                ColumnType::Ref => Span::styled("All references", ref_style(state, view, RefKind::Branch)),
+                _ => Span::raw(""),
+            };
+        };
+        let (text, style) = match column.kind {
+            ColumnType::Author => (ident_text(column, &entry.author), state.theme.style(view, LineType::Author)),
+            ColumnType::Committer => (ident_text(column, &entry.committer), state.theme.style(view, LineType::Committer)),
+            ColumnType::Date => (date_text(column, entry.time), state.theme.style(view, LineType::Date)),
+            ColumnType::Id => (short_id(&entry.info.id), state.theme.style(view, LineType::Id)),
+            ColumnType::Ref => (state.ref_format.format(&entry.info).unwrap_or_default(), ref_style(state, view, entry.info.kind)),
+            _ => (entry.title.to_string(), state.theme.style(view, LineType::Default)),
+        };
+        Span::styled(text, style)
+    }
+
+    /// The selected reference; `None` on the "All references" line.
+    fn selected(&self) -> Option<&RefEntry> { self.idx.checked_sub(1).and_then(|i| self.entries.get(i)) }
+
+    /// Run a git action, then pick up the references it changed.
+    fn run(&mut self, state: &mut AppState, action: impl FnOnce(&git2::Repository) -> anyhow::Result<()>, done: String) {
+        let Some(repo) = state.repo.as_ref() else { return };
+        match action(repo) {
+            Ok(()) => self.message = Some(done),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        state.reload_refs();
+        self.reload(state);
+    }
+
+    fn act(&mut self, state: &mut AppState, action: RefAction) {
+        match action {
+            RefAction::Checkout(info) => self.run(state, |repo| checkout_ref(repo, &info), format!("Checked out {}", info.name)),
+            RefAction::Delete(info) => self.run(state, |repo| delete_branch(repo, &info), format!("Deleted branch {}", info.name)),
+        }
+    }
+
+    fn open_main(&mut self, state: &mut AppState) -> Transition<AppState> {
+        let Some(repo) = state.repo.as_ref() else { return Transition::None };
+        let rev = match self.selected() {
+            Some(entry) => entry.info.id.to_string(),
+            None => "--all".to_string(),
+        };
+        match RevSpec::parse(repo, &[rev]) {
+            Ok(spec) => {
+                state.reload_commits(spec);
+                Transition::Push(Box::new(ListView::new()))
+            }
+            Err(e) => { self.message = Some(e.to_string()); Transition::None }
+        }
+    }
+
+    /// The text `/` searches on line `i`, the first being "All references".
+    fn search_line(&self, i: usize, state: &AppState) -> Option<String> {
+        let Some(i) = i.checked_sub(1) else { return Some("All references".into()) };
+        let e = self.entries.get(i)?;
+        Some(format!("{} {} {}", e.author.name, state.ref_format.format(&e.info).unwrap_or_default(), e.title))
+    }
+}
+
+impl View<AppState> for RefsView {
+    fn keymap(&self) -> &'static str { "refs" }
+    fn title(&self) -> String { "tig-rs — refs".into() }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let fs = if let Some(prompt) = &self.prompt {
+            vec![Span::raw(prompt.prompt())]
+        } else if let Some(confirm) = &self.confirm {
+            vec![Span::styled(confirm.prompt(), state.theme.style(self.keymap(), LineType::Prompt))]
+        } else {
+            let mut fs = key_hints(state, self.keymap(), &[
+                (&["enter"], "main view"), (&["checkout"], "checkout"), (&["branch-delete"], "delete"), (&["branch-create"], "new branch"),
+                (&["view-reflog"], "reflog"), (&["view-close"], "close"),
+            ]);
+            if let Some(msg) = &self.message {
+                fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+            }
+            fs
+        };
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let view_width = chunks[0].width as usize;
+        let columns = state.settings.view_columns("refs");
+        let shown: Vec<(&ViewColumn, usize)> = columns.shown().map(|column| {
+            let width = match column.kind {
+                ColumnType::LineNumber => column_width(column, view_width, std::iter::once((self.entries.len() + 1).to_string().len())),
+                _ => column_width(column, view_width, (0..=self.entries.len()).map(|i| self.cell(state, column, i).content.chars().count())),
+            };
+            (column, width)
+        }).collect();
+        let view = self.keymap();
+        let items: Vec<ListItem> = (self.offset..=self.entries.len()).take(self.height).map(|i| {
+            let spans: Vec<Span> = shown.iter().map(|&(column, width)| {
+                let cell = self.cell(state, column, i);
+                let text = match column.kind {
+                    ColumnType::CommitTitle => cell.content.to_string(),
+                    ColumnType::LineNumber => format!("{}│ ", line_number_text(column, i + 1, width)),
+                    _ => format!("{} ", fit(&cell.content, width)),
+                };
+                Span::styled(text, cell.style)
+            }).collect();
+            ListItem::new(state.highlight(view, Line::from(spans)))
+        }).collect();
+        let list = List::new(items)
+            .block(view_block(state, view, self.title()))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        f.render_stateful_widget(list, chunks[0], &mut list_state(Some(self.idx - self.offset)));
+    }
+    fn wants_input(&self) -> bool { self.prompt.is_some() || self.confirm.is_some() }
+    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
+        if let Some(prompt) = self.prompt.as_mut() {
+            match prompt.handle(ev) {
+                PromptResult::Pending => {}
+                PromptResult::Cancel => self.prompt = None,
+                PromptResult::Submit(name) => {
+                    self.prompt = None;
+                    let name = name.trim().to_string();
+                    let target = match self.selected() {
+                        Some(entry) => entry.info.id,
+                        None => match state.refs.head() {
+                            Some(head) => head.id,
+                            None => return Transition::None,
+                        },
+                    };
+                    if !name.is_empty() {
+                        self.run(state, |repo| create_branch(repo, &name, target), format!("Created branch {name}"));
+                    }
+                }
+            }
+            return Transition::None;
+        }
+        if let Some(confirm) = self.confirm.take() {
+            match confirm.answer(ev) {
+                Answer::Pending(confirm) => self.confirm = Some(confirm),
+                Answer::No => {}
+                Answer::Yes(action) => self.act(state, action),
+            }
+        }
+        Transition::None
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        // The first row is "All references".
+        if move_selection(req, &mut self.idx, self.height, self.entries.len()) { return Transition::None; }
+        match req {
+            Request::Enter => return self.open_main(state),
+            Request::Checkout => match self.selected() {
+                Some(entry) => {
+                    let question = format!("Checkout {}?", entry.info.name);
+                    self.confirm = Some(Confirm::new(question, RefAction::Checkout(entry.info.clone())));
+                }
+                None => self.message = Some("Select a reference to check out".into()),
+            },
+            Request::BranchDelete => match self.selected() {
+                Some(entry) if entry.info.full_name.starts_with("refs/heads/") => {
+                    let question = format!("Delete branch {}?", entry.info.name);
+                    self.confirm = Some(Confirm::new(question, RefAction::Delete(entry.info.clone())));
+                }
+                _ => self.message = Some("Only local branches can be deleted".into()),
+            },
+            Request::ViewReflog => {
+                let name = self.selected().map(|e| e.info.name.clone()).unwrap_or_else(|| "HEAD".into());
+                return Transition::Push(Box::new(ReflogView::new(state, &name)));
+            }
+            Request::BranchCreate => {
+                let from = self.selected().map(|e| e.info.name.clone()).unwrap_or_else(|| "HEAD".into());
+                self.prompt = Some(Prompt::new(format!("New branch at {from}: ")));
+            }
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.entries.len() + 1).filter_map(|i| self.search_line(i, state)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.entries.len() + 1) }
+    fn cursor_text(&self, state: &AppState) -> Option<String> { self.search_line(self.idx, state) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        let Some(info) = self.selected().map(|e| &e.info) else { return };
+        commit_vars(state, info.id, vars);
+        vars.set("refname", info.name.clone());
+        match info.kind {
+            RefKind::Branch => vars.set("branch", info.name.clone()),
+            RefKind::Remote | RefKind::TrackedRemote => {
+                let (remote, branch) = info.name.split_once('/').unwrap_or(("", &info.name));
+                vars.set("remote", remote);
+                vars.set("branch", branch);
+            }
+            RefKind::Tag | RefKind::LocalTag => vars.set("tag", info.name.clone()),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use tigrs_core::{CommandVars, LineType, Request};
+use tigrs_git::{revert_entry, update_entries, StagePatch, StageSelection, StatusEntry, StatusSection};
+use tigrs_tui::{Answer, Confirm, Transition, TuiFrame, View};
+
+use crate::{colorize_diff, diff_view_request, key_hints, list_state, move_selection, same_diff_position, view_block, AppState};
+
+/// What a line of the stage view shows, by file, hunk and hunk line.
+#[derive(Clone, Copy)]
+enum StageRow {
+    Header,
+    Hunk(usize, usize),
+    Line(usize, usize, usize),
+    NoNewline(usize, usize),
+}
+
+impl StageRow {
+    fn hunk(self) -> Option<(usize, usize)> {
+        match self {
+            StageRow::Header => None,
+            StageRow::Hunk(f, h) | StageRow::Line(f, h, _) | StageRow::NoNewline(f, h) => Some((f, h)),
+        }
+    }
+}
+
+/// Diff of one status entry whose hunks and lines can be staged, unstaged
+/// or reverted one at a time.
+pub(crate) struct StageView {
+    patch: StagePatch,
+    rows: Vec<StageRow>,
+    /// The diff colored, and whether with syntax highlighting.
+    lines: Option<(bool, Vec<Line<'static>>)>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+    /// Hunk to revert, or the whole file when `None`.
+    confirm: Option<Confirm<Option<(usize, usize)>>>,
+}
+
+impl StageView {
+    pub(crate) fn new(patch: StagePatch) -> Self {
+        let mut view = Self {
+            patch, rows: Vec::new(), lines: None,
+            idx: 0, offset: 0, height: 0, message: None, confirm: None,
+        };
+        view.rebuild();
+        view.idx = view.rows.iter().position(|r| matches!(r, StageRow::Hunk(..))).unwrap_or(0);
+        view
+    }
+
+    fn entry(&self) -> &StatusEntry { &self.patch.entry }
+
+    fn rebuild(&mut self) {
+        self.rows.clear();
+        for (f, file) in self.patch.files.iter().enumerate() {
+            self.rows.extend(file.header.iter().map(|_| StageRow::Header));
+            for (h, hunk) in file.hunks.iter().enumerate() {
+                self.rows.push(StageRow::Hunk(f, h));
+                for (i, line) in hunk.lines.iter().enumerate() {
+                    self.rows.push(StageRow::Line(f, h, i));
+                    if line.no_newline { self.rows.push(StageRow::NoNewline(f, h)); }
+                }
+            }
+        }
+        self.lines = None;
+        self.idx = self.idx.min(self.rows.len().saturating_sub(1));
+    }
+
+    /// Re-read the diff after the index or work tree changed. Returns
+    /// `false` once the file has nothing left to show.
+    fn reload(&mut self, state: &AppState) -> bool {
+        let Some(repo) = state.repo.as_ref() else { return false };
+        match state.diff_settings().and_then(|settings| StagePatch::load(repo, &self.patch.entry, &settings)) {
+            Ok(patch) if patch.is_empty() => return false,
+            Ok(patch) => self.patch = patch,
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        self.rebuild();
+        true
+    }
+
+    /// Re-read the diff after the diff settings changed, staying near the
+    /// same line. Unlike [`Self::reload`] this keeps the view open when
+    /// nothing is left to show.
+    fn reload_settings(&mut self, state: &AppState) {
+        let Some(repo) = state.repo.as_ref() else { return };
+        match state.diff_settings().and_then(|settings| StagePatch::load(repo, &self.patch.entry, &settings)) {
+            Ok(patch) => {
+                let (old, idx) = (self.patch.to_text(), self.idx);
+                self.patch = patch;
+                self.rebuild();
+                if let Some(idx) = same_diff_position(&old, &self.patch.to_text(), idx) { self.idx = idx; }
+            }
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    fn reload_or_close(&mut self, state: &AppState) -> Transition<AppState> {
+        if self.reload(state) { Transition::None } else { Transition::Back }
+    }
+
+    fn update(&mut self, state: &AppState, selection: StageSelection) -> Transition<AppState> {
+        let Some(repo) = state.repo.as_ref() else { return Transition::None };
+        let row = self.rows.get(self.idx).copied();
+        let hunk = row.and_then(StageRow::hunk).filter(|_| self.entry().section != StatusSection::Untracked);
+
+        if selection != StageSelection::Hunk {
+            let what = if matches!(selection, StageSelection::Line(_)) { "single lines" } else { "partial chunks" };
+            if self.entry().section == StatusSection::Untracked || self.entry().status == 'A' {
+                self.message = Some(format!("Staging {what} is not supported for new files"));
+                return Transition::None;
+            }
+            if self.entry().status == 'D' {
+                self.message = Some(format!("Staging {what} is not supported for deleted files"));
+                return Transition::None;
+            }
+        }
+
+        let res = match (hunk, selection) {
+            (None, StageSelection::Hunk) => update_entries(repo, std::slice::from_ref(self.entry()))
+                .map_err(|e| anyhow::anyhow!("Failed to update file: {e}")),
+            (Some((f, h)), StageSelection::Hunk) => self.patch.stage(repo, f, h, selection)
+                .map_err(|e| anyhow::anyhow!("Failed to apply chunk: {e}")),
+            (_, _) => {
+                let Some(StageRow::Line(f, h, i)) = row else {
+                    self.message = Some("Please select a change to stage".into());
+                    return Transition::None;
+                };
+                if !self.patch.files[f].hunks[h].lines[i].is_change() {
+                    self.message = Some("Please select a change to stage".into());
+                    return Transition::None;
+                }
+                let selection = match selection {
+                    StageSelection::Line(_) => StageSelection::Line(i),
+                    _ => StageSelection::Part(i),
+                };
+                self.patch.stage(repo, f, h, selection).map_err(|e| anyhow::anyhow!("Failed to apply chunk: {e}"))
+            }
+        };
+        if let Err(e) = res {
+            self.message = Some(e.to_string());
+            return Transition::None;
+        }
+        // The refresh reloads this view too, closing it once nothing is left.
+        Transition::Refresh
+    }
+
+    fn split(&mut self) {
+        let hunk = self.rows.get(self.idx).and_then(|r| r.hunk());
+        match hunk {
+            Some((f, h)) if self.entry().section != StatusSection::Untracked => {
+                let parts = self.patch.split_hunk(f, h);
+                if parts > 1 {
+                    self.rebuild();
+                    self.message = Some(format!("Split the chunk in {parts}"));
+                } else {
+                    self.message = Some("The chunk cannot be split".into());
+                }
+            }
+            _ => self.message = Some("No chunks to split in sight".into()),
+        }
+    }
+
+    fn revert(&mut self) {
+        match self.entry().section {
+            StatusSection::Unstaged => {
+                let hunk = self.rows.get(self.idx).and_then(|r| r.hunk());
+                self.confirm = Some(Confirm::new("Are you sure you want to revert changes?", hunk));
+            }
+            StatusSection::Staged => self.message = Some("Cannot revert changes to staged files".into()),
+            StatusSection::Untracked => self.message = Some("Cannot revert changes to untracked files".into()),
+        }
+    }
+
+    fn move_to_next_hunk(&mut self) {
+        let next = self.rows.iter().skip(self.idx + 1).position(|r| matches!(r, StageRow::Hunk(..)));
+        match next {
+            Some(n) => self.idx += n + 1,
+            None => self.message = Some("No further chunks".into()),
+        }
+    }
+}
+
+impl View<AppState> for StageView {
+    fn keymap(&self) -> &'static str { "stage" }
+    fn title(&self) -> String {
+        let what = match self.entry().section {
+            StatusSection::Staged => "staged changes",
+            StatusSection::Unstaged => "unstaged changes",
+            StatusSection::Untracked => "untracked file",
+        };
+        format!("tig-rs — stage: {what} to {}", self.entry().path)
+    }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let footer = if let Some(confirm) = &self.confirm {
+            Line::from(Span::styled(confirm.prompt(), state.theme.style(self.keymap(), LineType::Prompt)))
+        } else {
+            let mut fs = key_hints(state, self.keymap(), &[
+                (&["status-update"], "chunk"), (&["stage-update-line"], "line"), (&["stage-update-part"], "part"), (&["stage-split-chunk"], "split"),
+                (&["status-revert"], "revert"), (&["stage-next"], "next chunk"),
+                (&[":toggle ignore-space", ":toggle diff-context -1", ":toggle diff-context +1"], "space/context"), (&["view-close"], "back"),
+            ]);
+            if let Some(msg) = &self.message {
+                fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+            }
+            Line::from(fs)
+        };
+        f.render_widget(Paragraph::new(footer), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let syntax = state.settings.syntax_highlight;
+        if self.lines.as_ref().is_none_or(|(s, _)| *s != syntax) {
+            self.lines = Some((syntax, colorize_diff(&self.patch.to_text(), &state.theme, self.keymap(), syntax)));
+        }
+        let lines = self.lines.as_ref().map_or(&[][..], |(_, lines)| lines.as_slice());
+        let items: Vec<ListItem> = lines.iter()
+            .skip(self.offset)
+            .take(self.height)
+            .map(|l| ListItem::new(state.highlight(self.keymap(), l.clone())))
+            .collect();
+        let list = List::new(items)
+            .block(view_block(state, self.keymap(), self.title()))
+            .highlight_style(state.theme.style(self.keymap(), LineType::Cursor));
+        let selected = if lines.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn wants_input(&self) -> bool { self.confirm.is_some() }
+    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
+        if let Some(confirm) = self.confirm.take() {
+            let target = match confirm.answer(ev) {
+                Answer::Pending(confirm) => { self.confirm = Some(confirm); return Transition::None }
+                Answer::No => return Transition::None,
+                Answer::Yes(target) => target,
+            };
+            let Some(repo) = state.repo.as_ref() else { return Transition::None };
+            let res = match target {
+                Some((f, h)) => self.patch.revert(repo, f, h).map_err(|e| format!("Failed to revert chunk: {e}")),
+                None => revert_entry(repo, self.entry()).map_err(|e| format!("Failed to revert: {e}")),
+            };
+            if let Err(e) = res {
+                self.message = Some(e);
+                return Transition::None;
+            }
+            return Transition::Refresh;
+        }
+        Transition::None
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.rows.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::StatusUpdate => return self.update(state, StageSelection::Hunk),
+            Request::StageUpdateLine => return self.update(state, StageSelection::Line(0)),
+            Request::StageUpdatePart => return self.update(state, StageSelection::Part(0)),
+            Request::StageSplitChunk => self.split(),
+            Request::StatusRevert => self.revert(),
+            Request::StageNext => self.move_to_next_hunk(),
+            Request::Refresh => return self.reload_or_close(state),
+            req => match diff_view_request(req, state, |state| self.reload_settings(state)) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some((self.patch.to_text().lines().map(str::to_string).collect(), self.idx))
+    }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn selected_vars(&self, vars: &mut CommandVars, _state: &AppState) {
+        let entry = &self.patch.entry;
+        vars.set("file", entry.path.clone());
+        vars.set("file_old", entry.old_path.clone().unwrap_or_else(|| entry.path.clone()));
+        vars.set("status", entry.status.to_string());
+    }
+}
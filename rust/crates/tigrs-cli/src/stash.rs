@@ -0,0 +1,185 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use tigrs_core::{CommandVars, LineType, Request};
+use tigrs_git::{date_str, stash_apply, stash_diff_text, stash_drop, stash_entries, stash_pop, StashEntry};
+use tigrs_tui::{Answer, Confirm, Transition, TuiFrame, View};
+
+use crate::{commit_vars, generic_request, key_hints, list_state, move_selection, view_block, AppState, DiffView, ViewData};
+
+#[derive(Clone, Copy)]
+enum StashAction { Apply, Pop, Drop }
+
+impl StashAction {
+    fn verb(self) -> &'static str {
+        match self {
+            StashAction::Apply => "Apply",
+            StashAction::Pop => "Pop",
+            StashAction::Drop => "Drop",
+        }
+    }
+
+    fn done(self) -> &'static str {
+        match self {
+            StashAction::Apply => "Applied",
+            StashAction::Pop => "Popped",
+            StashAction::Drop => "Dropped",
+        }
+    }
+}
+
+/// Stashes with the columns of tig's default `stash-view`: date, author
+/// and message.
+pub(crate) struct StashView {
+    entries: Vec<StashEntry>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+    confirm: Option<Confirm<(StashAction, StashEntry)>>,
+}
+
+impl StashView {
+    pub(crate) fn new(state: &mut AppState) -> Self {
+        let mut view = Self { entries: Vec::new(), idx: 0, offset: 0, height: 0, message: None, confirm: None };
+        view.reload(state);
+        view
+    }
+
+    fn reload(&mut self, state: &mut AppState) {
+        let Some(repo) = state.repo.as_mut() else {
+            self.message = Some("Not a git repository".into());
+            return;
+        };
+        if repo.is_bare() {
+            self.message = Some("The stash view requires a working tree".into());
+            return;
+        }
+        match stash_entries(repo) {
+            Ok(entries) => self.entries = entries,
+            Err(e) => self.message = Some(e.to_string()),
+        }
+        if self.entries.is_empty() && self.message.is_none() { self.message = Some("No stashes".into()); }
+        self.idx = self.idx.min(self.entries.len().saturating_sub(1));
+    }
+
+    fn act(&mut self, state: &mut AppState, action: StashAction, stash: StashEntry) {
+        let Some(repo) = state.repo.as_mut() else { return };
+        let res = match action {
+            StashAction::Apply => stash_apply(repo, &stash),
+            StashAction::Pop => stash_pop(repo, &stash),
+            StashAction::Drop => stash_drop(repo, &stash),
+        };
+        state.reload_refs();
+        self.reload(state);
+        self.message = Some(match res {
+            Ok(()) => format!("{} {}", action.done(), stash.name()),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    /// The text `/` searches on line `i`.
+    fn search_line(&self, i: usize) -> Option<String> {
+        let e = self.entries.get(i)?;
+        Some(format!("{} {}: {}", e.author, e.name(), e.message))
+    }
+}
+
+impl View<AppState> for StashView {
+    fn keymap(&self) -> &'static str { "stash" }
+    fn title(&self) -> String { "tig-rs — stash".into() }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let fs = if let Some(confirm) = &self.confirm {
+            vec![Span::styled(confirm.prompt(), state.theme.style(self.keymap(), LineType::Prompt))]
+        } else {
+            let mut fs = key_hints(state, self.keymap(), &[(&["enter"], "diff"), (&["stash-apply"], "apply"), (&["stash-pop"], "pop"), (&["stash-drop"], "drop"), (&["view-close"], "close")]);
+            if let Some(msg) = &self.message {
+                fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+            }
+            fs
+        };
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        let author_width = self.entries.iter().map(|e| e.author.chars().count()).max().unwrap_or(0);
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let view = self.keymap();
+        let items: Vec<ListItem> = self.entries.iter().skip(self.offset).take(self.height).map(|entry| {
+            ListItem::new(state.highlight(view, Line::from(vec![
+                Span::styled(format!("{} ", date_str(entry.time)), state.theme.style(view, LineType::Date)),
+                Span::styled(format!("{:author_width$} ", entry.author), state.theme.style(view, LineType::Author)),
+                Span::styled(format!("{}: ", entry.name()), state.theme.style(view, LineType::Delimiter)),
+                Span::raw(entry.message.clone()),
+            ])))
+        }).collect();
+        let list = List::new(items)
+            .block(view_block(state, view, self.title()))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        let selected = if self.entries.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn wants_input(&self) -> bool { self.confirm.is_some() }
+    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
+        if let Some(confirm) = self.confirm.take() {
+            match confirm.answer(ev) {
+                Answer::Pending(confirm) => self.confirm = Some(confirm),
+                Answer::No => {}
+                Answer::Yes((action, stash)) => self.act(state, action, stash),
+            }
+        }
+        Transition::None
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.entries.len().saturating_sub(1)) { return Transition::None; }
+        let action = match req {
+            Request::StashApply => StashAction::Apply,
+            Request::StashPop => StashAction::Pop,
+            Request::StashDrop => StashAction::Drop,
+            Request::Enter => {
+                let (Some(repo), Some(stash)) = (state.repo.as_ref(), self.entries.get(self.idx)) else { return Transition::None };
+                match stash_diff_text(repo, stash) {
+                    Ok(text) => {
+                        let title = format!("{}: {}", stash.name(), stash.message);
+                        return Transition::Split(Box::new(DiffView { data: ViewData::new(title, text) }));
+                    }
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+                return Transition::None;
+            }
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => { self.message = Some(e.to_string()); return Transition::None; }
+            },
+        };
+        match self.entries.get(self.idx) {
+            Some(stash) => {
+                let question = format!("{} {}?", action.verb(), stash.name());
+                self.confirm = Some(Confirm::new(question, (action, stash.clone())));
+            }
+            None => self.message = Some("No stash selected".into()),
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.entries.len()).filter_map(|i| self.search_line(i)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.entries.len()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.search_line(self.idx) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { true }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        if let Some(stash) = self.entries.get(self.idx) {
+            vars.set("stash", stash.name());
+            commit_vars(state, stash.id, vars);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use tigrs_core::{CommandVars, LineType, Request};
+use tigrs_git::{load_status, revert_entry, update_entries, StagePatch, StatusEntry, StatusOptions, StatusSection};
+use tigrs_tui::{Answer, Confirm, Transition, TuiFrame, View};
+
+use crate::{generic_request, key_hints, list_state, move_selection, stage::StageView, view_block, AppState, BlameView};
+
+enum StatusLine {
+    Header(String),
+    Section(StatusSection),
+    Entry(StatusEntry),
+    /// Placeholder for an empty section; `true` when untracked files are hidden.
+    Empty(bool),
+}
+
+pub(crate) struct StatusView {
+    lines: Vec<StatusLine>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+    confirm: Option<Confirm<StatusEntry>>,
+}
+
+impl StatusView {
+    pub(crate) fn new(state: &AppState) -> Self {
+        let mut view = Self { lines: Vec::new(), idx: 0, offset: 0, height: 0, message: None, confirm: None };
+        view.reload(state);
+        view.idx = view.lines.iter().position(|l| matches!(l, StatusLine::Entry(_))).unwrap_or(0);
+        view
+    }
+
+    fn selected(&self) -> Option<&StatusLine> { self.lines.get(self.idx) }
+
+    /// Re-read the status, keeping the cursor on the same file if it is still listed.
+    fn reload(&mut self, state: &AppState) {
+        let Some(repo) = state.repo.as_ref() else {
+            self.message = Some("Not a git repository".into());
+            return;
+        };
+        let opts = StatusOptions {
+            show_untracked_files: state.settings.status_show_untracked_files,
+            show_untracked_dirs: state.settings.status_show_untracked_dirs,
+        };
+        let status = match load_status(repo, opts) {
+            Ok(s) => s,
+            Err(e) => { self.message = Some(e.to_string()); return; }
+        };
+        let previous = match self.selected() {
+            Some(StatusLine::Entry(e)) => Some((e.section, e.path.clone())),
+            _ => None,
+        };
+
+        self.lines.clear();
+        self.lines.push(StatusLine::Header(status.branch.clone()));
+        for section in [StatusSection::Staged, StatusSection::Unstaged, StatusSection::Untracked] {
+            self.lines.push(StatusLine::Section(section));
+            let entries = status.section(section);
+            if entries.is_empty() {
+                let hidden = section == StatusSection::Untracked && !opts.show_untracked_files;
+                self.lines.push(StatusLine::Empty(hidden));
+            }
+            self.lines.extend(entries.iter().cloned().map(StatusLine::Entry));
+        }
+
+        if let Some((section, path)) = previous {
+            let found = self.lines.iter().position(|l| matches!(l, StatusLine::Entry(e) if e.section == section && e.path == path));
+            if let Some(i) = found { self.idx = i; }
+        }
+        self.idx = self.idx.min(self.lines.len().saturating_sub(1));
+    }
+
+    fn section_start(&self, section: StatusSection) -> Option<usize> {
+        self.lines.iter().position(|l| matches!(l, StatusLine::Section(s) if *s == section))
+    }
+
+    fn section_entries(&self, section: StatusSection) -> Vec<StatusEntry> {
+        self.lines.iter()
+            .filter_map(|l| match l { StatusLine::Entry(e) if e.section == section => Some(e.clone()), _ => None })
+            .collect()
+    }
+
+    /// Stage or unstage the selected file, or every file of the selected section.
+    fn update(&mut self, state: &AppState) {
+        let Some(repo) = state.repo.as_ref() else { return };
+        let (targets, anchor) = match self.selected() {
+            Some(StatusLine::Entry(e)) => {
+                let start = self.section_start(e.section).unwrap_or(0);
+                (vec![e.clone()], Some((e.section, Some(self.idx - start - 1))))
+            }
+            Some(StatusLine::Section(section)) => (self.section_entries(*section), Some((*section, None))),
+            _ => (Vec::new(), None),
+        };
+        if targets.is_empty() {
+            self.message = Some("Nothing to update".into());
+            return;
+        }
+        match update_entries(repo, &targets) {
+            Ok(()) => self.message = None,
+            Err(e) => self.message = Some(format!("Failed to update file status: {e}")),
+        }
+        self.reload(state);
+
+        // Stay in the same section, on the file that took the updated one's place.
+        if let Some((section, ordinal)) = anchor {
+            if let Some(start) = self.section_start(section) {
+                let count = self.section_entries(section).len();
+                self.idx = match ordinal {
+                    Some(n) => start + 1 + n.min(count.saturating_sub(1)),
+                    None => start,
+                };
+            }
+        }
+    }
+
+    fn revert(&mut self) {
+        match self.selected() {
+            Some(StatusLine::Entry(e)) if e.section == StatusSection::Unstaged => {
+                self.confirm = Some(Confirm::new("Are you sure you want to revert changes?", e.clone()));
+            }
+            Some(StatusLine::Entry(e)) if e.section == StatusSection::Staged => {
+                self.message = Some("Cannot revert changes to staged files".into());
+            }
+            Some(StatusLine::Entry(_)) => self.message = Some("Cannot revert changes to untracked files".into()),
+            Some(StatusLine::Section(_)) => self.message = Some("Cannot revert changes to multiple files".into()),
+            _ => self.message = Some("Nothing to revert".into()),
+        }
+    }
+
+    fn line_spans(state: &AppState, view: &str, line: &StatusLine) -> Line<'static> {
+        match line {
+            StatusLine::Header(text) => Line::from(Span::styled(text.clone(), state.theme.style(view, LineType::Header))),
+            StatusLine::Section(section) => Line::from(Span::styled(section.title(), state.theme.style(view, LineType::Section))),
+            StatusLine::Empty(hidden) => Line::from(Span::raw(if *hidden { "  (not shown)" } else { "  (no files)" })),
+            StatusLine::Entry(entry) => {
+                let ty = match entry.section {
+                    StatusSection::Staged => LineType::StatStaged,
+                    StatusSection::Unstaged => LineType::StatUnstaged,
+                    StatusSection::Untracked => LineType::StatUntracked,
+                };
+                let name = match &entry.old_path {
+                    Some(old) => format!("{old} → {}", entry.path),
+                    None => entry.path.clone(),
+                };
+                Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled(entry.status.to_string(), state.theme.style(view, ty)),
+                    Span::raw(" "),
+                    Span::styled(name, state.theme.style(view, LineType::File)),
+                ])
+            }
+        }
+    }
+
+    /// The text `/` searches on line `i`: what is shown there.
+    fn search_line(&self, i: usize, state: &AppState) -> Option<String> {
+        let line = self.lines.get(i)?;
+        Some(Self::line_spans(state, self.keymap(), line).spans.iter().map(|s| s.content.as_ref()).collect())
+    }
+}
+
+impl View<AppState> for StatusView {
+    fn keymap(&self) -> &'static str { "status" }
+    fn title(&self) -> String { "tig-rs — status".into() }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let footer = if let Some(confirm) = &self.confirm {
+            Line::from(Span::styled(confirm.prompt(), state.theme.style(self.keymap(), LineType::Prompt)))
+        } else {
+            let mut fs = key_hints(state, self.keymap(), &[
+                (&["enter"], "stage"), (&["status-update"], "stage/unstage"), (&["status-revert"], "revert"), (&["view-close"], "back"),
+                (&["move-down", "move-up"], "move"), (&["refresh"], "refresh"),
+            ]);
+            if let Some(msg) = &self.message {
+                fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+            }
+            Line::from(fs)
+        };
+        f.render_widget(Paragraph::new(footer), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let items: Vec<ListItem> = self.lines.iter()
+            .skip(self.offset)
+            .take(self.height)
+            .map(|l| ListItem::new(state.highlight(self.keymap(), Self::line_spans(state, self.keymap(), l))))
+            .collect();
+        let list = List::new(items)
+            .block(view_block(state, self.keymap(), self.title()))
+            .highlight_symbol("> ")
+            .highlight_style(state.theme.style(self.keymap(), LineType::Cursor));
+        let selected = if self.lines.is_empty() { None } else { Some(self.idx - self.offset) };
+        f.render_stateful_widget(list, chunks[0], &mut list_state(selected));
+    }
+    fn wants_input(&self) -> bool { self.confirm.is_some() }
+    fn on_event(&mut self, ev: &Event, state: &mut AppState) -> Transition<AppState> {
+        if let Some(confirm) = self.confirm.take() {
+            match confirm.answer(ev) {
+                Answer::Pending(confirm) => self.confirm = Some(confirm),
+                Answer::No => {}
+                Answer::Yes(entry) => if let Some(repo) = state.repo.as_ref() {
+                    if let Err(e) = revert_entry(repo, &entry) {
+                        self.message = Some(format!("Failed to revert: {e}"));
+                    }
+                    self.reload(state);
+                },
+            }
+        }
+        Transition::None
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        if move_selection(req, &mut self.idx, self.height, self.lines.len().saturating_sub(1)) { return Transition::None; }
+        match req {
+            Request::StatusUpdate => self.update(state),
+            Request::StatusRevert => self.revert(),
+            Request::Refresh => self.reload(state),
+            Request::ViewBlame => match self.selected() {
+                Some(StatusLine::Entry(entry)) if entry.section != StatusSection::Untracked && entry.status != 'D' => {
+                    match BlameView::open(state, &entry.path, None, 1) {
+                        Ok(view) => return Transition::Push(Box::new(view)),
+                        Err(e) => self.message = Some(e.to_string()),
+                    }
+                }
+                _ => self.message = Some("Nothing to blame here".into()),
+            },
+            Request::Enter | Request::ViewStage => {
+                if let (Some(repo), Some(StatusLine::Entry(entry))) = (state.repo.as_ref(), self.selected()) {
+                    match state.diff_settings().and_then(|settings| StagePatch::load(repo, entry, &settings)) {
+                        Ok(patch) => return Transition::Split(Box::new(StageView::new(patch))),
+                        Err(e) => self.message = Some(e.to_string()),
+                    }
+                }
+            }
+            req => {
+                let result = generic_request(req, state);
+                // Commands may change which files are listed, even when
+                // saving the changed option fails.
+                if matches!(req, Request::Command(_)) { self.reload(state); }
+                match result {
+                    Ok(transition) => return transition,
+                    Err(e) => self.message = Some(e.to_string()),
+                }
+            }
+        }
+        Transition::None
+    }
+    fn search_lines(&self, state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.lines.len()).filter_map(|i| self.search_line(i, state)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.lines.len()) }
+    fn cursor_text(&self, state: &AppState) -> Option<String> { self.search_line(self.idx, state) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { matches!(self.selected(), Some(StatusLine::Entry(_))) }
+    fn selected_vars(&self, vars: &mut CommandVars, _state: &AppState) {
+        let Some(StatusLine::Entry(entry)) = self.selected() else { return };
+        vars.set("file", entry.path.clone());
+        vars.set("file_old", entry.old_path.clone().unwrap_or_else(|| entry.path.clone()));
+        vars.set("status", entry.status.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tigrs_core::{Keymaps, Settings, Theme};
+    use tigrs_git::test_repo::{checkout, commit, test_repo};
+    use tigrs_git::RevSpec;
+
+    #[test]
+    fn update_a_whole_section() {
+        let repo = test_repo("status-view-section");
+        let head = commit(&repo, "first", &[], &[("one", "1\n"), ("two", "2\n")]);
+        checkout(&repo, head);
+        let dir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(dir.join("one"), "one\n").unwrap();
+        std::fs::write(dir.join("two"), "two\n").unwrap();
+        std::fs::write(dir.join("new"), "new\n").unwrap();
+        let mut state = AppState::new(Settings::default(), Theme::default(), Keymaps::default(), Some(git2::Repository::open(repo.path()).unwrap()), None, RevSpec::default());
+        let mut view = StatusView::new(&state);
+        let paths = |view: &StatusView, section| view.section_entries(section).into_iter().map(|e| e.path).collect::<Vec<_>>();
+
+        view.idx = view.section_start(StatusSection::Unstaged).unwrap();
+        view.on_request(&Request::StatusUpdate, &mut state);
+        assert_eq!(paths(&view, StatusSection::Staged), ["one", "two"]);
+        assert!(paths(&view, StatusSection::Unstaged).is_empty());
+        assert_eq!(paths(&view, StatusSection::Untracked), ["new"]);
+        // The cursor stays on the header of the section just updated.
+        assert_eq!(view.idx, view.section_start(StatusSection::Unstaged).unwrap());
+
+        view.idx = view.section_start(StatusSection::Staged).unwrap();
+        view.on_request(&Request::StatusUpdate, &mut state);
+        assert!(paths(&view, StatusSection::Staged).is_empty());
+        assert_eq!(paths(&view, StatusSection::Unstaged), ["one", "two"]);
+        assert_eq!(view.idx, view.section_start(StatusSection::Staged).unwrap());
+
+        view.on_request(&Request::StatusUpdate, &mut state);
+        assert_eq!(view.message.as_deref(), Some("Nothing to update"));
+    }
+}
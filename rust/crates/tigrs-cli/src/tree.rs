@@ -0,0 +1,259 @@
+use anyhow::Result;
+use git2::Oid;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{List, ListItem, Paragraph},
+};
+use std::collections::HashMap;
+use tigrs_core::{ColumnType, CommandVars, LineType, Request, ViewColumn};
+use tigrs_git::{short_id, tree_entries, TreeCommit, TreeEntry, TreeHistoryLoader};
+use tigrs_tui::{Transition, TuiFrame, View};
+
+use crate::{
+    columns::{column_width, date_text, file_size_text, fit, ident_text, line_number_text},
+    commit_vars, generic_request, key_hints, list_state, move_selection, view_block, AppState, BlameView, BlobView,
+};
+
+enum TreeRow<'a> {
+    Header,
+    Parent,
+    Entry(&'a TreeEntry),
+}
+
+/// Files of one commit, a directory at a time, with the columns of the
+/// `tree-view` setting.
+pub(crate) struct TreeView {
+    commit: Oid,
+    /// Current directory, empty or ending in `/`.
+    dir: String,
+    entries: Vec<TreeEntry>,
+    history: HashMap<String, TreeCommit>,
+    loader: Option<TreeHistoryLoader>,
+    /// Cursor positions in the directories above.
+    positions: Vec<usize>,
+    idx: usize,
+    offset: usize,
+    height: usize,
+    message: Option<String>,
+}
+
+impl TreeView {
+    pub(crate) fn open(state: &AppState, commit: Oid) -> Result<Self> {
+        let mut view = Self {
+            commit, dir: String::new(), entries: Vec::new(), history: HashMap::new(), loader: None,
+            positions: Vec::new(), idx: 0, offset: 0, height: 0, message: None,
+        };
+        view.load(state)?;
+        Ok(view)
+    }
+
+    fn load(&mut self, state: &AppState) -> Result<()> {
+        let Some(repo) = state.repo.as_ref() else { anyhow::bail!("Not a git repository") };
+        self.entries = tree_entries(repo, self.commit, &self.dir)?;
+        self.history.clear();
+        let names = self.entries.iter().map(|e| e.name.clone()).collect();
+        self.loader = Some(TreeHistoryLoader::spawn(repo.path().to_path_buf(), self.commit, self.dir.clone(), names));
+        self.idx = self.first_entry_row();
+        if self.entries.is_empty() { self.message = Some("Tree is empty".into()); }
+        Ok(())
+    }
+
+    fn first_entry_row(&self) -> usize { if self.dir.is_empty() { 1 } else { 2 } }
+
+    fn row(&self, idx: usize) -> Option<TreeRow<'_>> {
+        match idx {
+            0 => Some(TreeRow::Header),
+            1 if !self.dir.is_empty() => Some(TreeRow::Parent),
+            _ => self.entries.get(idx - self.first_entry_row()).map(TreeRow::Entry),
+        }
+    }
+
+    fn row_count(&self) -> usize { self.first_entry_row() + self.entries.len() }
+
+    fn enter_dir(&mut self, state: &AppState, name: &str) {
+        let previous = (self.dir.clone(), self.idx);
+        self.dir = format!("{}{name}/", self.dir);
+        match self.load(state) {
+            Ok(()) => self.positions.push(previous.1),
+            Err(e) => {
+                self.dir = previous.0;
+                self.message = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Go up one directory; `false` when already at the top.
+    fn leave_dir(&mut self, state: &AppState) -> bool {
+        if self.dir.is_empty() { return false; }
+        let trimmed = self.dir.trim_end_matches('/');
+        self.dir = match trimmed.rfind('/') {
+            Some(i) => trimmed[..=i].to_string(),
+            None => String::new(),
+        };
+        if let Err(e) = self.load(state) { self.message = Some(e.to_string()); }
+        if let Some(idx) = self.positions.pop() { self.idx = idx.min(self.row_count().saturating_sub(1)); }
+        true
+    }
+
+    fn open_blob(&self, state: &AppState, entry: &TreeEntry) -> Result<Transition<AppState>> {
+        let path = format!("{}{}", self.dir, entry.name);
+        Ok(Transition::Split(Box::new(BlobView::open(state, self.commit, path, entry.id)?)))
+    }
+
+    /// The text and style of `column` on row `idx`, unpadded.
+    fn cell(&self, state: &AppState, column: &ViewColumn, idx: usize, row: &TreeRow<'_>) -> Span<'static> {
+        let view = self.keymap();
+        let entry = match row {
+            TreeRow::Entry(entry) => Some(*entry),
+            _ => None,
+        };
+        let commit = entry.and_then(|e| self.history.get(&e.name));
+        let (text, ty) = match column.kind {
+            ColumnType::Mode => (entry.map_or("drwxr-xr-x", |e| e.mode_str()).to_string(), LineType::Mode),
+            ColumnType::Author => (commit.map(|c| ident_text(column, &c.author)).unwrap_or_default(), LineType::Author),
+            ColumnType::Committer => (commit.map(|c| ident_text(column, &c.committer)).unwrap_or_default(), LineType::Committer),
+            ColumnType::Date => (commit.map(|c| date_text(column, c.time)).unwrap_or_default(), LineType::Date),
+            ColumnType::Id => (commit.map(|c| short_id(&c.id)).unwrap_or_default(), LineType::Id),
+            ColumnType::FileSize => (entry.and_then(|e| e.size).map(|s| file_size_text(column, s)).unwrap_or_default(), LineType::FileSize),
+            ColumnType::LineNumber => ((idx + 1).to_string(), LineType::LineNumber),
+            _ => match entry {
+                Some(e) if e.is_dir() => (format!("{}/", e.name), LineType::Directory),
+                Some(e) => (e.name.clone(), LineType::File),
+                None => ("..".to_string(), LineType::Directory),
+            },
+        };
+        Span::styled(text, state.theme.style(view, ty))
+    }
+
+    /// The text `/` searches on line `i`.
+    fn search_line(&self, i: usize) -> Option<String> {
+        Some(match self.row(i)? {
+            TreeRow::Header => format!("Directory path /{}", self.dir),
+            TreeRow::Parent => "..".to_string(),
+            TreeRow::Entry(entry) => {
+                let author = self.history.get(&entry.name).map(|c| c.author.name.to_string()).unwrap_or_default();
+                format!("{author} {}", entry.name)
+            }
+        })
+    }
+}
+
+impl View<AppState> for TreeView {
+    fn keymap(&self) -> &'static str { "tree" }
+    fn title(&self) -> String { format!("tig-rs — tree: /{} @ {}", self.dir, short_id(&self.commit)) }
+    fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &AppState) {
+        if let Some(loader) = self.loader.as_mut() {
+            self.history.extend(loader.poll());
+            if !loader.is_loading() { self.loader = None; }
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+        let mut fs = key_hints(state, self.keymap(), &[(&["enter"], "open"), (&["view-blame"], "blame"), (&["parent"], "parent dir"), (&["view-close"], "close")]);
+        if let Some(msg) = &self.message {
+            fs.push(Span::styled(msg.clone(), state.theme.style(self.keymap(), LineType::Status)));
+        }
+        f.render_widget(Paragraph::new(Line::from(fs)), chunks[1]);
+
+        self.height = chunks[0].height.saturating_sub(2).max(1) as usize;
+        if self.idx < self.offset { self.offset = self.idx; }
+        if self.idx >= self.offset + self.height { self.offset = self.idx + 1 - self.height; }
+        let view_width = chunks[0].width as usize;
+        let columns = state.settings.view_columns("tree");
+        let rows: Vec<(usize, TreeRow)> = (0..self.row_count()).filter_map(|i| Some((i, self.row(i)?))).collect();
+        let shown: Vec<(&ViewColumn, usize)> = columns.shown().map(|column| {
+            let cells = rows.iter().filter(|(_, row)| !matches!(row, TreeRow::Header));
+            let width = match column.kind {
+                ColumnType::LineNumber => column_width(column, view_width, std::iter::once(self.row_count().to_string().len())),
+                _ => column_width(column, view_width, cells.map(|(i, row)| self.cell(state, column, *i, row).content.chars().count())),
+            };
+            (column, width)
+        }).collect();
+        let view = self.keymap();
+        let items: Vec<ListItem> = rows.iter()
+            .skip(self.offset)
+            .take(self.height)
+            .map(|(i, row)| match row {
+                TreeRow::Header => Line::from(Span::styled(
+                    format!("Directory path /{}", self.dir),
+                    state.theme.style(view, LineType::Header),
+                )),
+                _ => Line::from(shown.iter().map(|&(column, width)| {
+                    let cell = self.cell(state, column, *i, row);
+                    let text = match column.kind {
+                        ColumnType::FileName => cell.content.to_string(),
+                        ColumnType::FileSize => format!("{:>width$} ", cell.content),
+                        ColumnType::LineNumber => format!("{}│ ", line_number_text(column, i + 1, width)),
+                        _ => format!("{} ", fit(&cell.content, width)),
+                    };
+                    Span::styled(text, cell.style)
+                }).collect::<Vec<_>>()),
+            })
+            .map(|line| ListItem::new(state.highlight(view, line)))
+            .collect();
+        let title = if self.loader.is_some() { format!("{} (loading…)", self.title()) } else { self.title() };
+        let list = List::new(items)
+            .block(view_block(state, view, title))
+            .highlight_style(state.theme.style(view, LineType::Cursor));
+        f.render_stateful_widget(list, chunks[0], &mut list_state(Some(self.idx - self.offset)));
+    }
+    fn on_request(&mut self, req: &Request, state: &mut AppState) -> Transition<AppState> {
+        self.message = None;
+        let last = self.row_count().saturating_sub(1);
+        if move_selection(req, &mut self.idx, self.height, last) { return Transition::None; }
+        match req {
+            Request::Parent | Request::Back => {
+                return if self.leave_dir(state) { Transition::None } else { Transition::Back };
+            }
+            Request::Enter => match self.row(self.idx) {
+                Some(TreeRow::Parent) => { self.leave_dir(state); }
+                Some(TreeRow::Entry(entry)) if entry.is_dir() => {
+                    let name = entry.name.clone();
+                    self.enter_dir(state, &name);
+                }
+                Some(TreeRow::Entry(entry)) if entry.size.is_some() => match self.open_blob(state, entry) {
+                    Ok(t) => return t,
+                    Err(e) => self.message = Some(e.to_string()),
+                },
+                _ => {}
+            },
+            Request::ViewBlob => match self.row(self.idx) {
+                Some(TreeRow::Entry(entry)) if entry.size.is_some() => return self.on_request(&Request::Enter, state),
+                _ => self.message = Some("No file chosen".into()),
+            },
+            Request::ViewBlame => match self.row(self.idx) {
+                Some(TreeRow::Entry(entry)) if entry.size.is_some() => {
+                    let path = format!("{}{}", self.dir, entry.name);
+                    match BlameView::open(state, &path, Some(self.commit), 1) {
+                        Ok(view) => return Transition::Push(Box::new(view)),
+                        Err(e) => self.message = Some(e.to_string()),
+                    }
+                }
+                _ => self.message = Some("Blame only supported for files".into()),
+            },
+            req => match generic_request(req, state) {
+                Ok(transition) => return transition,
+                Err(e) => self.message = Some(e.to_string()),
+            },
+        }
+        Transition::None
+    }
+    fn search_lines(&self, _state: &AppState) -> Option<(Vec<String>, usize)> {
+        Some(((0..self.row_count()).filter_map(|i| self.search_line(i)).collect(), self.idx))
+    }
+    fn line_count(&self, _state: &AppState) -> Option<usize> { Some(self.row_count()) }
+    fn cursor_text(&self, _state: &AppState) -> Option<String> { self.search_line(self.idx) }
+    fn select_line(&mut self, line: usize, _state: &mut AppState) { self.idx = line; }
+    fn sends_child_enter(&self) -> bool { matches!(self.row(self.idx), Some(TreeRow::Entry(entry)) if entry.size.is_some()) }
+    fn selected_vars(&self, vars: &mut CommandVars, state: &AppState) {
+        commit_vars(state, self.commit, vars);
+        vars.set("directory", self.dir.trim_end_matches('/'));
+        if let Some(TreeRow::Entry(entry)) = self.row(self.idx) {
+            vars.set("file", format!("{}{}", self.dir, entry.name));
+            if !entry.is_dir() { vars.set("blob", entry.id.to_string()); }
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use anyhow::{anyhow, bail, Result};
+use ratatui::style::{Color, Modifier, Style};
+use regex::Regex;
+
+use crate::{tigrc::split_words, KEYMAPS};
+
+/// Kinds of lines and columns that are colored, named as in tigrc `color`
+/// commands. Variants are in the order of `LINE_TYPES`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineType {
+    DiffHeader,
+    DiffDelFile,
+    DiffAddFile,
+    DiffStart,
+    DiffChunk,
+    DiffAdd,
+    DiffAdd2,
+    DiffDel,
+    DiffDel2,
+    DiffIndex,
+    DiffOldmode,
+    DiffNewmode,
+    DiffNewfmode,
+    DiffDelfmode,
+    DiffSimilarity,
+    DiffNoNewline,
+    DiffAddHighlight,
+    DiffDelHighlight,
+    PpMerge,
+    PpRefs,
+    PpReflog,
+    PpReflogmsg,
+    Commit,
+    Parent,
+    Tree,
+    Author,
+    Committer,
+    Default,
+    Cursor,
+    CursorBlur,
+    Status,
+    Delimiter,
+    Date,
+    Mode,
+    Id,
+    Overflow,
+    Directory,
+    File,
+    FileSize,
+    LineNumber,
+    TitleBlur,
+    TitleFocus,
+    Header,
+    Section,
+    MainCommit,
+    MainAnnotated,
+    MainTag,
+    MainLocalTag,
+    MainRemote,
+    MainStash,
+    MainNote,
+    MainPrefetch,
+    MainOther,
+    MainReplace,
+    MainTracked,
+    MainRef,
+    MainHead,
+    StatNone,
+    StatStaged,
+    StatUnstaged,
+    StatUntracked,
+    HelpGroup,
+    HelpAction,
+    HelpToggle,
+    DiffStat,
+    Palette0,
+    Palette1,
+    Palette2,
+    Palette3,
+    Palette4,
+    Palette5,
+    Palette6,
+    Palette7,
+    Palette8,
+    Palette9,
+    Palette10,
+    Palette11,
+    Palette12,
+    Palette13,
+    GraphCommit,
+    SearchResult,
+    /// Questions asked on the status line.
+    Prompt,
+    /// Syntax highlighting of code in diffs and blobs.
+    CodeKeyword,
+    CodeString,
+    CodeNumber,
+    CodeComment,
+}
+
+/// Name and line prefix of each line type; lines starting with the prefix
+/// get the type's colors.
+pub(crate) const LINE_TYPES: &[(&str, &str)] = &[
+    ("diff-header", "diff --"), ("diff-del-file", "--- "), ("diff-add-file", "+++ "), ("diff-start", "---"),
+    ("diff-chunk", "@@"), ("diff-add", "+"), ("diff-add2", " +"), ("diff-del", "-"), ("diff-del2", " -"),
+    ("diff-index", "index "), ("diff-oldmode", "old mode "), ("diff-newmode", "new mode "),
+    ("diff-newfmode", "new file mode "), ("diff-delfmode", "deleted file mode "), ("diff-similarity", "similarity "),
+    ("diff-no-newline", "\\ No newline at end of file"), ("diff-add-highlight", ""), ("diff-del-highlight", ""),
+    ("pp-merge", "Merge: "), ("pp-refs", "Refs: "), ("pp-reflog", "Reflog: "), ("pp-reflogmsg", "Reflog message: "),
+    ("commit", "commit "), ("parent", "parent "), ("tree", "tree "), ("author", "author "), ("committer", "committer "),
+    ("default", ""), ("cursor", ""), ("cursor-blur", ""), ("status", ""), ("delimiter", ""), ("date", ""),
+    ("mode", ""), ("id", ""), ("overflow", ""), ("directory", ""), ("file", ""), ("file-size", ""),
+    ("line-number", ""), ("title-blur", ""), ("title-focus", ""), ("header", ""), ("section", ""),
+    ("main-commit", ""), ("main-annotated", ""), ("main-tag", ""), ("main-local-tag", ""), ("main-remote", ""),
+    ("main-stash", ""), ("main-note", ""), ("main-prefetch", ""), ("main-other", ""), ("main-replace", ""),
+    ("main-tracked", ""), ("main-ref", ""), ("main-head", ""), ("stat-none", ""), ("stat-staged", ""),
+    ("stat-unstaged", ""), ("stat-untracked", ""), ("help-group", ""), ("help-action", ""), ("help-toggle", ""),
+    ("diff-stat", ""), ("palette-0", ""), ("palette-1", ""), ("palette-2", ""), ("palette-3", ""),
+    ("palette-4", ""), ("palette-5", ""), ("palette-6", ""), ("palette-7", ""), ("palette-8", ""),
+    ("palette-9", ""), ("palette-10", ""), ("palette-11", ""), ("palette-12", ""), ("palette-13", ""),
+    ("graph-commit", ""), ("search-result", ""), ("prompt", ""),
+    ("code-keyword", ""), ("code-string", ""), ("code-number", ""), ("code-comment", ""),
+];
+
+const PALETTE: [LineType; 14] = [
+    LineType::Palette0, LineType::Palette1, LineType::Palette2, LineType::Palette3, LineType::Palette4,
+    LineType::Palette5, LineType::Palette6, LineType::Palette7, LineType::Palette8, LineType::Palette9,
+    LineType::Palette10, LineType::Palette11, LineType::Palette12, LineType::Palette13,
+];
+
+impl LineType {
+    /// The `palette-*` color for graph lane or commit `n`.
+    pub fn palette(n: usize) -> Self { PALETTE[n % PALETTE.len()] }
+}
+
+/// Colors tig-rs starts with, in tigrc syntax. They follow tig's names but
+/// keep tig-rs's dark-terminal palette.
+const DEFAULT_COLORS: &str = r#"
+color diff-header default default bold
+color diff-del-file default default bold
+color diff-add-file default default bold
+color diff-chunk yellow default
+color diff-add green default
+color diff-add2 green default
+color diff-del red default
+color diff-del2 red default
+color pp-merge color8 default
+color commit yellow default
+color "Author: " green default
+color "Date: " blue default
+color author green default
+color main."/^(?i)feat/" green default
+color main."/^(?i)fix/" red default
+color main."/^(?i)docs/" blue default
+color main."/^(?i)refactor/" magenta default
+color cursor default default reverse
+color title-blur default default dim
+color status red default
+color delimiter magenta default
+color date blue default
+color mode cyan default
+color id cyan default bold
+color directory blue default bold
+color grep.file blue default
+color line-number color8 default
+color header blue default
+color section magenta default
+color main-tag magenta default bold
+color main-local-tag magenta default
+color main-remote yellow default
+color main-stash red default
+color main-note cyan default
+color main-prefetch cyan default
+color main-other cyan default
+color main-replace cyan default
+color main-tracked yellow default bold
+color main-ref cyan default
+color main-head cyan default bold
+color stat-staged green default bold
+color stat-unstaged red default bold
+color stat-untracked yellow default bold
+color help-action yellow default bold
+color palette-0 magenta default
+color palette-1 yellow default
+color palette-2 cyan default
+color palette-3 green default
+color palette-4 blue default
+color palette-5 red default
+color palette-6 color13 default
+color palette-7 magenta default bold
+color palette-8 yellow default bold
+color palette-9 cyan default bold
+color palette-10 green default bold
+color palette-11 blue default bold
+color palette-12 red default bold
+color palette-13 color13 default bold
+color graph-commit default default bold
+color search-result black yellow
+color prompt yellow default
+color code-keyword magenta default
+color code-string yellow default
+color code-number cyan default
+color code-comment blue default
+"#;
+
+/// How tig maps git's `color.*` settings to its own colors, the default of
+/// the `git-colors` option.
+pub const DEFAULT_GIT_COLORS: &str = "branch.current=main-head branch.local=main-ref branch.plain=main-ref \
+    branch.remote=main-remote diff.meta=diff-header diff.meta=diff-index diff.meta=diff-oldmode \
+    diff.meta=diff-newmode diff.frag=diff-chunk diff.old=diff-del diff.new=diff-add \
+    diff-highlight.oldHighlight=diff-del-highlight diff-highlight.newHighlight=diff-add-highlight \
+    grep.filename=grep.file grep.linenumber=grep.line-number grep.separator=grep.delimiter \
+    status.branch=status.header status.added=stat-staged status.updated=stat-staged \
+    status.changed=stat-unstaged status.untracked=stat-untracked";
+
+/// A line type, or lines starting with a prefix or matching a regex.
+#[derive(Debug, Clone)]
+struct LineRule {
+    /// Line type name; empty for rules added by `color "prefix"`.
+    name: &'static str,
+    line: String,
+    regex: Option<Regex>,
+    /// Style in all views (`None`) and in views by keymap name.
+    styles: Vec<(Option<&'static str>, Style)>,
+}
+
+impl LineRule {
+    fn style_in(&self, view: &str) -> Option<Style> {
+        let find = |v: Option<&str>| self.styles.iter().find(|(s, _)| *s == v).map(|(_, style)| *style);
+        find(Some(view)).or_else(|| find(None))
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(line),
+            // Prefixes ignore case, which matches Signed-off-by lines better.
+            None => !self.line.is_empty() && line.get(..self.line.len()).is_some_and(|p| p.eq_ignore_ascii_case(&self.line)),
+        }
+    }
+}
+
+/// The styles of all line types and line rules, set with `color` commands.
+/// A `default` color leaves the color of what is below, ultimately the
+/// `default` line type's, unchanged.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    rules: Vec<LineRule>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let rules = LINE_TYPES.iter().map(|(name, line)| LineRule {
+            name, line: line.to_string(), regex: None, styles: vec![(None, Style::default())],
+        }).collect();
+        let mut theme = Theme { rules };
+        for line in DEFAULT_COLORS.lines().filter(|l| !l.is_empty()) {
+            let words = split_words(line).expect("default color");
+            theme.color(&words[1].1, &words[2].0, &words[3].0, &words[4..].iter().map(|(w, _)| w.clone()).collect::<Vec<_>>())
+                .expect("default color");
+        }
+        theme
+    }
+}
+
+impl Theme {
+    /// `color <area> <fgcolor> <bgcolor> [attributes]`. `area` is as written
+    /// in the tigrc file, so quoted prefixes and `"/regex/"` can be told
+    /// from line type names; any of them may start with `<keymap>.` to
+    /// color only that view.
+    pub fn color(&mut self, area: &str, fg: &str, bg: &str, attrs: &[String]) -> Result<()> {
+        let (i, view) = self.find_rule(area)?;
+        let mut style = Style { fg: parse_color(fg)?, bg: parse_color(bg)?, ..Style::default() };
+        for attr in attrs {
+            style = style.add_modifier(parse_attr(attr).ok_or_else(|| anyhow!("Unknown color attribute: {attr}"))?);
+        }
+        self.set(i, view, style);
+        Ok(())
+    }
+
+    /// Apply git's `color.<name>` setting with `value` such as `bold red`
+    /// to the areas `mappings` (the `git-colors` option) maps `name` to.
+    pub fn git_color(&mut self, name: &str, value: &str, mappings: &str) {
+        let mut style = None;
+        for (git, area) in mappings.split_whitespace().filter_map(|m| m.split_once('=')) {
+            if !git.eq_ignore_ascii_case(name) { continue; }
+            let style = *style.get_or_insert_with(|| parse_git_color(value));
+            if let Ok((i, view)) = self.find_rule(area) { self.set(i, view, style); }
+        }
+    }
+
+    /// Style of `ty` in the view with keymap `view`.
+    pub fn style(&self, view: &str, ty: LineType) -> Style { self.rules[ty as usize].style_in(view).unwrap_or_default() }
+
+    /// Style of the first line type or rule whose prefix or regex matches
+    /// `line`, as tig colors the lines of diffs and logs.
+    pub fn line_style(&self, view: &str, line: &str) -> Option<Style> {
+        self.rules.iter().filter(|rule| rule.matches(line)).find_map(|rule| rule.style_in(view))
+    }
+
+    /// Like `line_style`, but only with rules colored for `view` itself, as
+    /// `color main."/^fix/" red default` colors commit titles.
+    pub fn view_line_style(&self, view: &str, line: &str) -> Option<Style> {
+        self.rules.iter()
+            .filter(|rule| rule.matches(line))
+            .find_map(|rule| rule.styles.iter().find(|(v, _)| *v == Some(view)).map(|(_, style)| *style))
+    }
+
+    fn set(&mut self, i: usize, view: Option<&'static str>, style: Style) {
+        let styles = &mut self.rules[i].styles;
+        match styles.iter_mut().find(|(v, _)| *v == view) {
+            Some(entry) => entry.1 = style,
+            None => styles.push((view, style)),
+        }
+    }
+
+    /// Index of the rule `area` names, adding a rule for a new prefix or
+    /// regex, and the view it is limited to.
+    fn find_rule(&mut self, area: &str) -> Result<(usize, Option<&'static str>)> {
+        let quoted = |s: &str| s.len() >= 2 && s.starts_with(['"', '\'']) && s.ends_with(&s[..1]);
+        let (view, name) = match area.split_once('.') {
+            Some((keymap, rest)) if !quoted(area) => {
+                let keymap = if keymap == "branch" { "refs" } else { keymap };
+                let view = KEYMAPS.iter().find(|k| **k == keymap).ok_or_else(|| anyhow!("Unknown key map: {keymap}"))?;
+                (Some(*view), rest)
+            }
+            _ => (None, area),
+        };
+        if !quoted(name) {
+            // As in tig, `_` and `-` are the same and case does not matter.
+            let name = name.to_ascii_lowercase().replace('_', "-");
+            let i = self.rules.iter().position(|rule| rule.name == name).ok_or_else(|| anyhow!("Unknown color name: {area}"))?;
+            return Ok((i, view));
+        }
+        let line = &name[1..name.len() - 1];
+        let pattern = line.strip_prefix('/').and_then(|l| l.strip_suffix('/')).filter(|p| !p.is_empty());
+        let line = pattern.unwrap_or(line);
+        if let Some(i) = self.rules.iter().position(|rule| !rule.line.is_empty() && rule.line.eq_ignore_ascii_case(line)) {
+            return Ok((i, view));
+        }
+        let regex = match pattern {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| {
+                // The last line of regex's multi-line report says what is wrong.
+                let e = e.to_string();
+                anyhow!("Invalid color mapping: {}", e.lines().last().unwrap_or_default().trim_start_matches("error: "))
+            })?),
+            None => None,
+        };
+        self.rules.push(LineRule { name: "", line: line.to_string(), regex, styles: Vec::new() });
+        Ok((self.rules.len() - 1, view))
+    }
+}
+
+/// A color name, `color<N>` or `<N>` for one of 256 colors, or `#rrggbb`.
+/// `default` is `None`.
+fn parse_color(name: &str) -> Result<Option<Color>> {
+    let color = match name.to_ascii_lowercase().as_str() {
+        "default" => return Ok(None),
+        "black" => Color::Black,
+        "blue" => Color::Blue,
+        "cyan" => Color::Cyan,
+        "green" => Color::Green,
+        "magenta" => Color::Magenta,
+        "red" => Color::Red,
+        // Color 7; ratatui's `White` is the bright one.
+        "white" => Color::Gray,
+        "yellow" => Color::Yellow,
+        other => {
+            let rgb = other.strip_prefix('#').filter(|hex| hex.len() == 6).and_then(|hex| u32::from_str_radix(hex, 16).ok());
+            let number = other.strip_prefix("color").unwrap_or(other);
+            match (rgb, number.parse::<u8>()) {
+                (Some(rgb), _) => Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8),
+                (None, Ok(n)) if number.bytes().all(|b| b.is_ascii_digit()) => Color::Indexed(n),
+                _ => bail!("Unknown color: {name}"),
+            }
+        }
+    };
+    Ok(Some(color))
+}
+
+fn parse_attr(name: &str) -> Option<Modifier> {
+    Some(match name.to_ascii_lowercase().as_str() {
+        "normal" => Modifier::empty(),
+        "blink" => Modifier::SLOW_BLINK,
+        "bold" => Modifier::BOLD,
+        "dim" => Modifier::DIM,
+        "reverse" | "standout" => Modifier::REVERSED,
+        "underline" => Modifier::UNDERLINED,
+        _ => return None,
+    })
+}
+
+/// A git color value: attributes, then the foreground and background
+/// colors. Words tig-rs does not know are ignored, as in tig.
+fn parse_git_color(value: &str) -> Style {
+    let mut style = Style::default();
+    let mut colors = 0;
+    for word in value.split_whitespace() {
+        let word = if word.starts_with("ul") { "underline" } else { word };
+        if let Some(attr) = parse_attr(word) {
+            style = style.add_modifier(attr);
+        } else if let Ok(color) = parse_color(word) {
+            if colors == 0 { style.fg = color; } else { style.bg = color; }
+            colors += 1;
+        }
+    }
+    style
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(theme: &mut Theme, line: &str) -> Result<()> {
+        let words = split_words(line)?;
+        let attrs: Vec<String> = words[3..].iter().map(|(word, _)| word.clone()).collect();
+        theme.color(&words[0].1, &words[1].0, &words[2].0, &attrs)
+    }
+
+    #[test]
+    fn parse_color_commands() {
+        let mut theme = Theme::default();
+        color(&mut theme, "diff-add color42 #0a0B0c bold Underline").unwrap();
+        let expected = Style::default().fg(Color::Indexed(42)).bg(Color::Rgb(10, 11, 12)).add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        assert_eq!(theme.style("diff", LineType::DiffAdd), expected);
+        // `_` and `-` are the same and case does not matter.
+        color(&mut theme, "Diff_Del 9 default standout").unwrap();
+        assert_eq!(theme.style("diff", LineType::DiffDel), Style::default().fg(Color::Indexed(9)).add_modifier(Modifier::REVERSED));
+        color(&mut theme, "status white black").unwrap();
+        assert_eq!(theme.style("main", LineType::Status), Style::default().fg(Color::Gray).bg(Color::Black));
+
+        let error = |line: &str| color(&mut Theme::default(), line).unwrap_err().to_string();
+        assert_eq!(error("diff-add purple default"), "Unknown color: purple");
+        assert_eq!(error("diff-add color256 default"), "Unknown color: color256");
+        assert_eq!(error("diff-add '#abc' default"), "Unknown color: #abc");
+        assert_eq!(error("diff-add red default shiny"), "Unknown color attribute: shiny");
+        assert_eq!(error("diff-plus red default"), "Unknown color name: diff-plus");
+        assert_eq!(error("nosuch.id red default"), "Unknown key map: nosuch");
+        assert!(error(r#""/(/" red default"#).starts_with("Invalid color mapping: "));
+    }
+
+    #[test]
+    fn color_areas_by_view() {
+        let mut theme = Theme::default();
+        let id = theme.style("", LineType::Id);
+        color(&mut theme, "main.id red default").unwrap();
+        color(&mut theme, "branch.id green default").unwrap();
+        assert_eq!(theme.style("main", LineType::Id), Style::default().fg(Color::Red));
+        assert_eq!(theme.style("refs", LineType::Id), Style::default().fg(Color::Green));
+        assert_eq!(theme.style("diff", LineType::Id), id);
+
+        // Prefixes and regexes, for all views or one; a quoted area with a
+        // dot is a prefix, not a view.
+        color(&mut theme, r#""Signed-off-by:" yellow default"#).unwrap();
+        color(&mut theme, r#"log."v1.0" blue default"#).unwrap();
+        color(&mut theme, r#"main."/^wip\b/" magenta default"#).unwrap();
+        assert_eq!(theme.line_style("diff", "signed-off-by: A"), Some(Style::default().fg(Color::Yellow)));
+        assert_eq!(theme.line_style("log", "v1.0 released"), Some(Style::default().fg(Color::Blue)));
+        assert_eq!(theme.line_style("diff", "v1.0 released"), None);
+        assert_eq!(theme.line_style("diff", "+added"), Some(theme.style("diff", LineType::DiffAdd)));
+        assert_eq!(theme.view_line_style("main", "wip tidy up"), Some(Style::default().fg(Color::Magenta)));
+        assert_eq!(theme.view_line_style("main", "wipe"), None);
+        // Only rules colored for the view itself.
+        assert_eq!(theme.view_line_style("main", "Signed-off-by: A"), None);
+        // Coloring a prefix again changes its rule rather than adding one.
+        color(&mut theme, r#""signed-off-by:" red default"#).unwrap();
+        assert_eq!(theme.line_style("diff", "Signed-off-by: A"), Some(Style::default().fg(Color::Red)));
+    }
+
+    #[test]
+    fn git_colors_mapping() {
+        let mut theme = Theme::default();
+        theme.git_color("diff.new", "bold green", DEFAULT_GIT_COLORS);
+        assert_eq!(theme.style("diff", LineType::DiffAdd), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD));
+        // One git color may map to several areas.
+        theme.git_color("diff.meta", "ul blue #102030", DEFAULT_GIT_COLORS);
+        let meta = Style::default().fg(Color::Blue).bg(Color::Rgb(16, 32, 48)).add_modifier(Modifier::UNDERLINED);
+        assert_eq!(theme.style("diff", LineType::DiffHeader), meta);
+        assert_eq!(theme.style("diff", LineType::DiffIndex), meta);
+        // Names match ignoring case, and view areas only color that view.
+        let file = theme.style("", LineType::File);
+        theme.git_color("grep.fileName", "reverse nosuchword red", DEFAULT_GIT_COLORS);
+        assert_eq!(theme.style("grep", LineType::File), Style::default().fg(Color::Red).add_modifier(Modifier::REVERSED));
+        assert_eq!(theme.style("tree", LineType::File), file);
+
+        // Colors not mapped, or mapped to unknown areas, change nothing.
+        let mut custom = Theme::default();
+        custom.git_color("diff.new", "red", "diff.old=diff-del diff.new=no-such-area");
+        custom.git_color("color.ui", "red", DEFAULT_GIT_COLORS);
+        assert_eq!(custom.style("diff", LineType::DiffAdd), Theme::default().style("diff", LineType::DiffAdd));
+    }
+}
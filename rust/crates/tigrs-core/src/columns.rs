@@ -0,0 +1,306 @@
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+
+use crate::{tigrc::split_words, Settings};
+
+/// Column types of the view settings, such as `author` in `blame-view`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Author,
+    Committer,
+    CommitTitle,
+    Date,
+    FileName,
+    FileSize,
+    Id,
+    LineNumber,
+    Mode,
+    Ref,
+    Text,
+}
+
+/// Each column type's name, the `display` values it takes besides `yes`
+/// and `no` (the first is what `yes` means), and its other options.
+const COLUMN_TYPES: &[(&str, ColumnType, &[&str], &[&str])] = &[
+    ("author", ColumnType::Author, &["full", "abbreviated", "email", "email-user"], &["width", "maxwidth"]),
+    ("committer", ColumnType::Committer, &["full", "abbreviated", "email", "email-user"], &["width", "maxwidth"]),
+    ("commit-title", ColumnType::CommitTitle, &[], &["graph", "refs", "overflow"]),
+    ("date", ColumnType::Date, &["default", "relative", "relative-compact", "custom"], &["format", "local", "use-author", "width"]),
+    ("file-name", ColumnType::FileName, &["auto", "always"], &["width", "maxwidth"]),
+    ("file-size", ColumnType::FileSize, &["default", "units"], &["width"]),
+    ("id", ColumnType::Id, &[], &["color", "width"]),
+    ("line-number", ColumnType::LineNumber, &[], &["interval", "width"]),
+    ("mode", ColumnType::Mode, &[], &["width"]),
+    ("ref", ColumnType::Ref, &[], &["width", "maxwidth"]),
+    ("text", ColumnType::Text, &[], &["commit-title-overflow"]),
+];
+
+/// Column types each view setting takes.
+const VIEW_COLUMNS: &[(&str, &[ColumnType])] = &[
+    ("blob", &[ColumnType::LineNumber, ColumnType::Text]),
+    ("blame", &[
+        ColumnType::Author, ColumnType::Committer, ColumnType::Date, ColumnType::FileName, ColumnType::Id,
+        ColumnType::LineNumber, ColumnType::Text,
+    ]),
+    ("tree", &[
+        ColumnType::Author, ColumnType::Committer, ColumnType::Date, ColumnType::Id, ColumnType::FileName,
+        ColumnType::FileSize, ColumnType::LineNumber, ColumnType::Mode,
+    ]),
+    ("grep", &[ColumnType::FileName, ColumnType::LineNumber, ColumnType::Text]),
+    ("refs", &[
+        ColumnType::Author, ColumnType::Committer, ColumnType::Date, ColumnType::CommitTitle, ColumnType::Id,
+        ColumnType::LineNumber, ColumnType::Ref,
+    ]),
+];
+
+impl ColumnType {
+    fn info(self) -> (&'static str, &'static [&'static str], &'static [&'static str]) {
+        let (name, _, displays, options) = COLUMN_TYPES.iter().find(|(_, t, ..)| *t == self).expect("all column types are listed");
+        (name, displays, options)
+    }
+
+    pub fn name(self) -> &'static str { self.info().0 }
+}
+
+/// One column of a view setting, as `author:email,width=20`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewColumn {
+    pub kind: ColumnType,
+    /// `no`, `yes` for columns shown or not, or one of the column's
+    /// display values.
+    pub display: String,
+    options: Vec<(String, String)>,
+}
+
+impl ViewColumn {
+    fn parse(view: &str, spec: &str) -> Result<Self> {
+        let (name, rest) = spec.split_once(':').unwrap_or((spec, ""));
+        let (_, kind, ..) = COLUMN_TYPES.iter().find(|(n, ..)| *n == name).ok_or_else(|| anyhow!("Unknown column type: {name}"))?;
+        if !view_columns(view).contains(kind) { bail!("The {view} view has no {name} column"); }
+        let mut column = Self { kind: *kind, display: String::new(), options: Vec::new() };
+        let mut parts = rest.split(',').filter(|part| !part.is_empty()).peekable();
+        // The first option is the display one unless it is named.
+        let display = parts.next_if(|part| column.takes_display(part));
+        column.set("display", display.unwrap_or("yes"))?;
+        for part in parts {
+            let (option, value) = part.split_once('=').unwrap_or((part, "yes"));
+            column.set(option, value)?;
+        }
+        Ok(column)
+    }
+
+    /// Set `option`, checking that the column takes it and the value fits.
+    fn set(&mut self, option: &str, value: &str) -> Result<()> {
+        let (name, displays, options) = self.kind.info();
+        if option == "display" {
+            self.display = match value {
+                "yes" | "true" | "1" => displays.first().unwrap_or(&"yes").to_string(),
+                "no" | "false" | "0" => "no".into(),
+                value if displays.contains(&value) => value.into(),
+                value => bail!("Invalid {name} display: {value}"),
+            };
+            return Ok(());
+        }
+        if !options.contains(&option) { bail!("Unknown {name} option: {option}"); }
+        let number = value.strip_suffix('%').filter(|_| option == "maxwidth").unwrap_or(value);
+        if matches!(option, "width" | "maxwidth" | "interval") && number.parse::<usize>().is_err() {
+            bail!("Invalid {name} {option}: {value}");
+        }
+        self.options.retain(|(o, _)| o != option);
+        self.options.push((option.into(), value.into()));
+        Ok(())
+    }
+
+    fn takes_display(&self, value: &str) -> bool {
+        matches!(value, "yes" | "true" | "1" | "no" | "false" | "0") || self.kind.info().1.contains(&value)
+    }
+
+    pub fn shown(&self) -> bool { self.display != "no" }
+
+    /// The value of `option`, such as `format` for dates.
+    pub fn option(&self, option: &str) -> Option<&str> {
+        self.options.iter().find(|(o, _)| o == option).map(|(_, value)| value.as_str())
+    }
+
+    /// Whether a boolean option such as `color` is on.
+    pub fn flag(&self, option: &str) -> bool { matches!(self.option(option), Some("yes" | "true" | "1")) }
+
+    /// A number option; `maxwidth` as a percentage is taken of `total`.
+    pub fn number(&self, option: &str, total: usize) -> Option<usize> {
+        let value = self.option(option)?;
+        match value.strip_suffix('%') {
+            Some(percent) => percent.parse::<usize>().ok().map(|p| total * p.min(100) / 100),
+            None => value.parse().ok(),
+        }
+    }
+}
+
+impl fmt::Display for ViewColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind.name(), self.display)?;
+        for (option, value) in &self.options {
+            // Formats such as "%Y-%m-%d %H:%M" are quoted as in tigrc files.
+            if value.contains(char::is_whitespace) || value.contains(['"', '\\']) {
+                write!(f, ",{option}=\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))?;
+            } else {
+                write!(f, ",{option}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The columns of a view setting such as `blame-view`, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewColumns(Vec<ViewColumn>);
+
+impl ViewColumns {
+    /// Parse the setting of `view`, as `blame`, from its space separated
+    /// column specifications.
+    pub fn parse(view: &str, spec: &str) -> Result<Self> {
+        let words: Vec<String> = split_words(spec)?.into_iter().map(|(word, _)| word).collect();
+        Self::from_words(view, &words)
+    }
+
+    /// Likewise from the words of a `set` command, with quotes removed.
+    pub(crate) fn from_words(view: &str, words: &[String]) -> Result<Self> {
+        words.iter().map(|column| ViewColumn::parse(view, column)).collect::<Result<_>>().map(Self)
+    }
+
+    /// The columns shown, in order.
+    pub fn shown(&self) -> impl Iterator<Item = &ViewColumn> { self.0.iter().filter(|c| c.shown()) }
+
+    pub fn get(&self, kind: ColumnType) -> Option<&ViewColumn> { self.0.iter().find(|c| c.kind == kind) }
+
+    /// Set `option` of the `kind` column, or its display with `None`, for
+    /// shorthands such as `blame-view-line-number = no`.
+    fn set(&mut self, kind: ColumnType, option: Option<&str>, value: &str) -> Result<()> {
+        let column = self.0.iter_mut().find(|c| c.kind == kind).ok_or_else(|| anyhow!("No {} column to change", kind.name()))?;
+        column.set(option.unwrap_or("display"), value)
+    }
+}
+
+impl fmt::Display for ViewColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, column) in self.0.iter().enumerate() {
+            if i > 0 { f.write_str(" ")?; }
+            write!(f, "{column}")?;
+        }
+        Ok(())
+    }
+}
+
+fn view_columns(view: &str) -> &'static [ColumnType] {
+    VIEW_COLUMNS.iter().find(|(v, _)| *v == view).map_or(&[], |(_, kinds)| kinds)
+}
+
+impl Settings {
+    /// The columns of `view`, as `blame` or `refs`.
+    pub fn view_columns(&self, view: &str) -> ViewColumns {
+        let spec = |settings: &Settings| match view {
+            "blame" => settings.blame_view.clone(),
+            "blob" => settings.blob_view.clone(),
+            "grep" => settings.grep_view.clone(),
+            "refs" => settings.refs_view.clone(),
+            "tree" => settings.tree_view.clone(),
+            _ => String::new(),
+        };
+        // Settings are checked when set, but config.toml may be edited by hand.
+        ViewColumns::parse(view, &spec(self)).unwrap_or_else(|_| ViewColumns::parse(view, &spec(&Settings::default())).unwrap_or(ViewColumns(Vec::new())))
+    }
+
+    /// The view and column of a shorthand option such as
+    /// `blame-view-date-format`, with the column option it sets if any.
+    pub(crate) fn view_column_option(name: &str) -> Option<(&str, ColumnType, Option<&str>)> {
+        let (view, rest) = name.split_once("-view-")?;
+        let (_, kind, ..) = COLUMN_TYPES.iter().find(|(column, ..)| rest == *column || rest.starts_with(&format!("{column}-")))?;
+        if !view_columns(view).contains(kind) { return None; }
+        let option = rest[kind.name().len()..].strip_prefix('-');
+        Some((view, *kind, option))
+    }
+
+    /// Change one column of a view setting, as `set blame-view-line-number = no`.
+    pub(crate) fn set_view_column(&mut self, view: &str, kind: ColumnType, option: Option<&str>, value: &str) -> Result<()> {
+        let mut columns = self.view_columns(view);
+        columns.set(kind, option, value)?;
+        match view {
+            "blame" => self.blame_view = columns.to_string(),
+            "blob" => self.blob_view = columns.to_string(),
+            "grep" => self.grep_view = columns.to_string(),
+            "refs" => self.refs_view = columns.to_string(),
+            "tree" => self.tree_view = columns.to_string(),
+            _ => bail!("Unknown view: {view}"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_display_and_options() {
+        let columns = ViewColumns::parse("blame", "id:yes,color author:email,width=20 file-name date:no line-number:yes,interval=5 text").unwrap();
+        let shown: Vec<String> = columns.shown().map(|c| c.to_string()).collect();
+        assert_eq!(shown, ["id:yes,color=yes", "author:email,width=20", "file-name:auto", "line-number:yes,interval=5", "text:yes"]);
+        assert!(columns.get(ColumnType::Id).unwrap().flag("color"));
+        assert_eq!(columns.get(ColumnType::LineNumber).unwrap().number("interval", 80), Some(5));
+        assert!(!columns.get(ColumnType::Date).unwrap().shown());
+    }
+
+    #[test]
+    fn yes_means_the_first_display_value() {
+        let columns = ViewColumns::parse("blame", "author:yes date:true,format=%H:%M").unwrap();
+        assert_eq!(columns.to_string(), "author:full date:default,format=%H:%M");
+    }
+
+    #[test]
+    fn maxwidth_takes_a_percentage() {
+        let columns = ViewColumns::parse("blame", "author:full,maxwidth=20%").unwrap();
+        assert_eq!(columns.get(ColumnType::Author).unwrap().number("maxwidth", 120), Some(24));
+    }
+
+    #[test]
+    fn reject_bad_columns() {
+        for spec in ["bogus", "author:bogus", "author:full,bogus=1", "line-number:yes,interval=x", "status", "mode"] {
+            assert!(ViewColumns::parse("blame", spec).is_err(), "{spec}");
+        }
+        assert!(ViewColumns::parse("tree", "text").is_err());
+        assert!(ViewColumns::parse("refs", "file-name").is_err());
+        assert!(ViewColumns::parse("grep", "author").is_err());
+    }
+
+    #[test]
+    fn quoted_formats_round_trip() {
+        let columns = ViewColumns::parse("blame", r#"date:custom,format="%Y-%m-%d %H:%M" text"#).unwrap();
+        assert_eq!(columns.get(ColumnType::Date).unwrap().option("format"), Some("%Y-%m-%d %H:%M"));
+        assert_eq!(ViewColumns::parse("blame", &columns.to_string()).unwrap(), columns);
+    }
+
+    #[test]
+    fn set_views_and_single_columns() {
+        let mut settings = Settings::default();
+        settings.set_option("blame-view-line-number", &["no".into()]).unwrap();
+        settings.set_option("blame-view-date-format", &["%H:%M %z".into()]).unwrap();
+        let columns = settings.view_columns("blame");
+        assert!(!columns.get(ColumnType::LineNumber).unwrap().shown());
+        assert_eq!(columns.get(ColumnType::Date).unwrap().option("format"), Some("%H:%M %z"));
+        assert!(settings.set_option("blame-view-date", &["sometimes".into()]).is_err());
+
+        settings.set_option("blame-view", &["author:abbreviated".into(), "text".into()]).unwrap();
+        assert_eq!(settings.blame_view, "author:abbreviated text:yes");
+        assert!(settings.set_option("blame-view", &["mode".into()]).is_err());
+        assert!(settings.set_option("blame-view-date", &["default".into()]).is_err());
+    }
+
+    #[test]
+    fn default_settings_parse() {
+        let settings = Settings::default();
+        let views = [("blame", &settings.blame_view), ("blob", &settings.blob_view), ("grep", &settings.grep_view), ("refs", &settings.refs_view), ("tree", &settings.tree_view)];
+        for (view, spec) in views {
+            assert_eq!(ViewColumns::parse(view, spec).unwrap(), settings.view_columns(view));
+            assert!(settings.view_columns(view).shown().count() > 0);
+        }
+    }
+}
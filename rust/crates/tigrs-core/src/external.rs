@@ -0,0 +1,171 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+
+/// Flags an external command's first word can start with.
+const FLAGS: &str = "!?@<+>";
+
+/// The `%(name)` variables external commands can use, and their value when
+/// the current view does not set them, as in tig.
+const VARIABLES: &[(&str, &str)] = &[
+    ("head", "HEAD"), ("commit", "HEAD"), ("blob", ""), ("branch", ""), ("remote", "origin"), ("tag", ""),
+    ("refname", ""), ("stash", ""), ("directory", "."), ("file", ""), ("file_old", ""), ("lineno", "0"),
+    ("lineno_old", "0"), ("ref", "HEAD"), ("status", ""), ("text", ""),
+    ("repo:head", ""), ("repo:head-id", ""), ("repo:remote", ""), ("repo:upstream", ""), ("repo:cdup", ""),
+    ("repo:prefix", ""), ("repo:git-dir", ""), ("repo:worktree", ""), ("repo:is-inside-work-tree", "false"),
+];
+
+/// A command bound with `bind main C ?git cherry-pick %(commit)`, or
+/// entered at the prompt after `:!`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExternalCommand {
+    /// The command and its arguments, with the variables not yet expanded.
+    pub argv: Vec<String>,
+    /// `@`: run without showing the output.
+    pub silent: bool,
+    /// `?`: ask before running.
+    pub confirm: bool,
+    /// `<`: quit once the command ran.
+    pub exit: bool,
+    /// `+`: show the first line of the output in the status line.
+    pub echo: bool,
+    /// `>`: go back to the views without waiting for Enter.
+    pub quick: bool,
+    /// Show the output in the pager, as `:!` does.
+    pub pager: bool,
+}
+
+impl ExternalCommand {
+    /// The command of a binding: `words` with the flags of the first one
+    /// taken off.
+    pub fn parse(words: &[String]) -> Result<Self> {
+        let Some((first, rest)) = words.split_first() else { bail!("No arguments") };
+        let mut command = Self::default();
+        let mut name = first.as_str();
+        if !name.starts_with(|c| FLAGS.contains(c)) {
+            bail!("Unknown command flag '{}'; expected one of {FLAGS}", name.chars().next().unwrap_or_default());
+        }
+        while let Some(flag) = name.chars().next().filter(|c| FLAGS.contains(*c)) {
+            match flag {
+                '@' => command.silent = true,
+                '?' => command.confirm = true,
+                '<' => command.exit = true,
+                '+' => command.echo = true,
+                '>' => command.quick = true,
+                _ => {}
+            }
+            name = &name[1..];
+        }
+        command.argv = Some(name).filter(|n| !n.is_empty()).into_iter().chain(rest.iter().map(String::as_str)).map(String::from).collect();
+        if command.argv.is_empty() { bail!("No command given") }
+        Ok(command)
+    }
+
+    /// The messages the command's `%(prompt)` variables ask with, in order.
+    pub fn prompts(&self) -> Vec<String> {
+        let mut prompts = Vec::new();
+        let _ = self.format(|name| {
+            prompts.extend(prompt_message(name));
+            Ok(String::new())
+        });
+        prompts
+    }
+
+    /// The command with its variables replaced by their values in `vars`,
+    /// and its `%(prompt)` variables by `answers`.
+    pub fn expand(&self, vars: &CommandVars, answers: &[String]) -> Result<Vec<String>> {
+        let mut answers = answers.iter();
+        self.format(|name| match prompt_message(name) {
+            Some(_) => Ok(answers.next().cloned().unwrap_or_default()),
+            None => vars.get(name).map(String::from).ok_or_else(|| anyhow!("Unknown variable: %({name})")),
+        })
+    }
+
+    /// `argv` with each `%(name)` replaced by `value(name)` and `%%` by `%`.
+    fn format(&self, mut value: impl FnMut(&str) -> Result<String>) -> Result<Vec<String>> {
+        self.argv.iter().map(|arg| {
+            let mut formatted = String::new();
+            let mut rest = arg.as_str();
+            while let Some(i) = rest.find('%') {
+                formatted.push_str(&rest[..i]);
+                rest = &rest[i..];
+                if let Some(after) = rest.strip_prefix("%%") {
+                    formatted.push('%');
+                    rest = after;
+                } else if let Some(after) = rest.strip_prefix("%(") {
+                    let Some(end) = after.find(')') else { bail!("Unterminated variable in {arg}") };
+                    formatted.push_str(&value(&after[..end])?);
+                    rest = &after[end + 1..];
+                } else {
+                    formatted.push('%');
+                    rest = &rest[1..];
+                }
+            }
+            formatted.push_str(rest);
+            Ok(formatted)
+        }).collect()
+    }
+}
+
+/// What `%(prompt)` or `%(prompt <message>)` asks with.
+fn prompt_message(name: &str) -> Option<String> {
+    let message = name.strip_prefix("prompt").filter(|m| m.is_empty() || m.starts_with(char::is_whitespace))?.trim_start();
+    Some(if message.is_empty() { "Command argument: ".into() } else { message.to_string() })
+}
+
+/// Values of the `%(name)` variables, set from the repository and from the
+/// selected line of the current view.
+#[derive(Debug, Clone, Default)]
+pub struct CommandVars(HashMap<&'static str, String>);
+
+impl CommandVars {
+    pub fn set(&mut self, name: &'static str, value: impl Into<String>) { self.0.insert(name, value.into()); }
+
+    /// The value of `name`, or its default when unset or empty; `None` for
+    /// names that are not variables.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let (name, default) = VARIABLES.iter().find(|(n, _)| *n == name)?;
+        Some(self.0.get(name).filter(|v| !v.is_empty()).map_or(*default, String::as_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(values: &[&str]) -> Vec<String> { values.iter().map(|v| v.to_string()).collect() }
+
+    #[test]
+    fn parse_flags() {
+        let command = ExternalCommand::parse(&words(&["?@git", "cherry-pick", "%(commit)"])).unwrap();
+        assert_eq!(command.argv, ["git", "cherry-pick", "%(commit)"]);
+        assert!(command.confirm && command.silent && !command.exit && !command.echo && !command.quick);
+        let command = ExternalCommand::parse(&words(&["<+>", "true"])).unwrap();
+        assert_eq!(command.argv, ["true"]);
+        assert!(command.exit && command.echo && command.quick && !command.confirm);
+        assert!(ExternalCommand::parse(&words(&["git", "log"])).is_err());
+        assert!(ExternalCommand::parse(&words(&["!"])).is_err());
+        assert!(ExternalCommand::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn expand_variables() {
+        let command = ExternalCommand::parse(&words(&["!git", "show", "%(commit):%(file)", "100%%", "50%", "%(lineno)"])).unwrap();
+        let mut vars = CommandVars::default();
+        vars.set("commit", "abc123");
+        vars.set("file", "src/main.rs");
+        assert_eq!(command.expand(&vars, &[]).unwrap(), ["git", "show", "abc123:src/main.rs", "100%", "50%", "0"]);
+        let command = ExternalCommand::parse(&words(&["!echo", "%(nope)"])).unwrap();
+        assert!(command.expand(&vars, &[]).unwrap_err().to_string().contains("%(nope)"));
+        let command = ExternalCommand::parse(&words(&["!echo", "%(file"])).unwrap();
+        assert!(command.expand(&vars, &[]).is_err());
+    }
+
+    #[test]
+    fn prompt_for_arguments() {
+        let command = ExternalCommand::parse(&words(&["!git", "commit", "-m", "%(prompt Message: )", "%(prompt)"])).unwrap();
+        assert_eq!(command.prompts(), ["Message: ", "Command argument: "]);
+        let answers = words(&["fix", "--amend"]);
+        assert_eq!(command.expand(&CommandVars::default(), &answers).unwrap(), ["git", "commit", "-m", "fix", "--amend"]);
+        assert_eq!(command.expand(&CommandVars::default(), &[]).unwrap(), ["git", "commit", "-m", "", ""]);
+    }
+}
@@ -0,0 +1,482 @@
+use anyhow::{anyhow, bail, Result};
+use std::{collections::HashMap, fmt};
+
+use crate::{tigrc::split_words, ExternalCommand};
+
+/// One key press, named as in tigrc `bind` commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    /// `<C-x>`, with the letter in lower case.
+    Ctrl(char),
+    Enter,
+    Esc,
+    Tab,
+    BackTab,
+    Backspace,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// `<ScrollBack>`, Shift-Up in most terminals.
+    ScrollBack,
+    /// `<ScrollFwd>`, Shift-Down in most terminals.
+    ScrollFwd,
+    ShiftLeft,
+    ShiftRight,
+    ShiftDelete,
+    ShiftHome,
+    ShiftEnd,
+    F(u8),
+}
+
+/// Names of `<...>` keys, matched ignoring case.
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("Enter", Key::Enter), ("Space", Key::Char(' ')), ("Backspace", Key::Backspace), ("Tab", Key::Tab),
+    ("Escape", Key::Esc), ("Esc", Key::Esc), ("Left", Key::Left), ("Right", Key::Right), ("Up", Key::Up), ("Down", Key::Down),
+    ("Insert", Key::Insert), ("Ins", Key::Insert), ("Delete", Key::Delete), ("Del", Key::Delete), ("Hash", Key::Char('#')),
+    ("Home", Key::Home), ("End", Key::End), ("PageUp", Key::PageUp), ("PgUp", Key::PageUp),
+    ("PageDown", Key::PageDown), ("PgDown", Key::PageDown), ("LessThan", Key::Char('<')), ("LT", Key::Char('<')),
+    ("ScrollBack", Key::ScrollBack), ("SBack", Key::ScrollBack), ("ScrollFwd", Key::ScrollFwd), ("SFwd", Key::ScrollFwd),
+    ("BackTab", Key::BackTab), ("ShiftTab", Key::BackTab), ("ShiftLeft", Key::ShiftLeft), ("ShiftRight", Key::ShiftRight),
+    ("ShiftDelete", Key::ShiftDelete), ("ShiftDel", Key::ShiftDelete), ("ShiftHome", Key::ShiftHome), ("ShiftEnd", Key::ShiftEnd),
+    ("SingleQuote", Key::Char('\'')), ("DoubleQuote", Key::Char('"')),
+];
+
+impl Key {
+    /// Parse a key sequence such as `gg`, `<C-f>`, `<Esc>o` or `<F5>`.
+    pub fn parse_seq(text: &str) -> Result<Vec<Key>> {
+        let mut keys = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let Some(name) = rest.strip_prefix('<').filter(|_| rest.len() > 1) else {
+                if c == '^' && rest.len() > 1 {
+                    bail!("Control key mapping must now use '<Ctrl-{}>' instead of '{rest}'", &rest[1..]);
+                }
+                keys.push(Key::Char(c));
+                rest = &rest[c.len_utf8()..];
+                continue;
+            };
+            let Some((name, after)) = name.split_once('>') else { bail!("Missing '>' from key mapping: {rest}") };
+            let ctrl = name.strip_prefix("Ctrl-").or_else(|| name.strip_prefix("C-"));
+            let key = match ctrl {
+                Some(letter) => {
+                    let mut chars = letter.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Key::Ctrl(c.to_ascii_lowercase()),
+                        _ => bail!("Invalid control key: <{name}>"),
+                    }
+                }
+                None => KEY_NAMES.iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, key)| *key)
+                    .or_else(|| name.strip_prefix(['F', 'f']).and_then(|n| n.parse().ok()).filter(|n| (1..=19).contains(n)).map(Key::F))
+                    .ok_or_else(|| anyhow!("Unknown key mapping: {name}"))?,
+            };
+            keys.push(key);
+            rest = after;
+        }
+        if keys.is_empty() { bail!("Empty key mapping"); }
+        Ok(keys)
+    }
+}
+
+impl fmt::Display for Key {
+    /// The key as a `bind` command spells it, with plain characters as is.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Char(' ') => write!(f, "<Space>"),
+            Key::Char(c) => write!(f, "{c}"),
+            Key::Ctrl(c) => write!(f, "<C-{c}>"),
+            Key::F(n) => write!(f, "<F{n}>"),
+            key => {
+                let name = KEY_NAMES.iter().find(|(_, k)| k == key).map_or("?", |(name, _)| name);
+                write!(f, "<{name}>")
+            }
+        }
+    }
+}
+
+/// What a key asks a view to do, named as in tigrc `bind` commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    ViewMain,
+    ViewDiff,
+    ViewLog,
+    ViewReflog,
+    ViewTree,
+    ViewBlob,
+    ViewBlame,
+    ViewRefs,
+    ViewPager,
+    ViewStatus,
+    ViewStage,
+    ViewStash,
+    ViewGrep,
+    Enter,
+    Back,
+    Next,
+    Previous,
+    Parent,
+    ViewNext,
+    Refresh,
+    ViewClose,
+    Quit,
+    StatusUpdate,
+    StatusRevert,
+    StageUpdateLine,
+    StageUpdatePart,
+    StageSplitChunk,
+    StageNext,
+    MoveUp,
+    MoveDown,
+    MovePageUp,
+    MovePageDown,
+    MoveHalfPageUp,
+    MoveHalfPageDown,
+    MoveFirstLine,
+    MoveLastLine,
+    Edit,
+    StopLoading,
+    Search,
+    SearchBack,
+    FindNext,
+    FindPrev,
+    /// Show a merge's combined diff or the diff against its next parent.
+    NextMergeParent,
+    Checkout,
+    ResetHard,
+    BranchCreate,
+    BranchDelete,
+    StashApply,
+    StashPop,
+    StashDrop,
+    /// Open the `:` prompt.
+    Prompt,
+    /// Show only the focused view of a split.
+    Maximize,
+    /// Move the cursor to a line, counting from 1, as `:<number>` does.
+    GotoLine(usize),
+    None,
+    /// A `:` prompt command such as `:toggle wrap-lines`, without the colon.
+    Command(Vec<String>),
+    /// An external command, bound with one of tig's `!?@<+>` flags or
+    /// entered after `:!`.
+    Run(ExternalCommand),
+}
+
+pub(crate) const REQUEST_NAMES: &[(&str, Request)] = &[
+    ("view-main", Request::ViewMain), ("view-diff", Request::ViewDiff), ("view-log", Request::ViewLog),
+    ("view-reflog", Request::ViewReflog), ("view-tree", Request::ViewTree), ("view-blob", Request::ViewBlob),
+    ("view-blame", Request::ViewBlame), ("view-refs", Request::ViewRefs), ("view-branch", Request::ViewRefs),
+    ("view-pager", Request::ViewPager), ("view-status", Request::ViewStatus), ("view-stage", Request::ViewStage),
+    ("view-stash", Request::ViewStash), ("view-grep", Request::ViewGrep),
+    ("enter", Request::Enter), ("back", Request::Back), ("next", Request::Next), ("previous", Request::Previous),
+    ("parent", Request::Parent), ("view-next", Request::ViewNext), ("refresh", Request::Refresh),
+    ("view-close", Request::ViewClose), ("quit", Request::Quit),
+    ("status-update", Request::StatusUpdate), ("status-revert", Request::StatusRevert),
+    ("stage-update-line", Request::StageUpdateLine), ("stage-update-part", Request::StageUpdatePart),
+    ("stage-split-chunk", Request::StageSplitChunk), ("stage-next", Request::StageNext),
+    ("move-up", Request::MoveUp), ("move-down", Request::MoveDown),
+    ("move-page-up", Request::MovePageUp), ("move-page-down", Request::MovePageDown),
+    ("move-half-page-up", Request::MoveHalfPageUp), ("move-half-page-down", Request::MoveHalfPageDown),
+    ("move-first-line", Request::MoveFirstLine), ("move-last-line", Request::MoveLastLine),
+    ("edit", Request::Edit), ("stop-loading", Request::StopLoading),
+    ("search", Request::Search), ("search-back", Request::SearchBack),
+    ("find-next", Request::FindNext), ("find-prev", Request::FindPrev), ("next-merge-parent", Request::NextMergeParent),
+    ("checkout", Request::Checkout), ("reset-hard", Request::ResetHard),
+    ("branch-create", Request::BranchCreate), ("branch-delete", Request::BranchDelete),
+    ("stash-apply", Request::StashApply), ("stash-pop", Request::StashPop), ("stash-drop", Request::StashDrop),
+    ("prompt", Request::Prompt), ("maximize", Request::Maximize),
+    ("none", Request::None),
+];
+
+/// tig requests tig-rs does not implement yet.
+const UNSUPPORTED_REQUESTS: &[&str] = &[
+    "view-help", "view-close-no-quit", "status-merge", "move-next-merge", "move-prev-merge",
+    "scroll-line-up", "scroll-line-down", "scroll-page-up", "scroll-page-down", "scroll-half-page-up",
+    "scroll-half-page-down", "scroll-first-col", "scroll-left", "scroll-right",
+    "options", "screen-redraw", "show-version",
+];
+
+/// A binding to a tig request tig-rs does not implement; reported as a
+/// warning so tigrc files written for C tig still load.
+#[derive(Debug)]
+pub struct UnsupportedRequest(pub String);
+
+impl fmt::Display for UnsupportedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Unsupported request name: {}", self.0) }
+}
+
+impl std::error::Error for UnsupportedRequest {}
+
+impl Request {
+    /// The request a `bind` command names with `words`: a request name,
+    /// a `:` command or an external command.
+    pub fn parse(words: &[String]) -> Result<Self> {
+        let Some(first) = words.first() else { bail!("Missing request") };
+        if let Some(cmd) = first.strip_prefix(':') {
+            let mut argv: Vec<String> = words.to_vec();
+            if cmd.is_empty() { argv.remove(0); } else { argv[0] = cmd.to_string(); }
+            if argv.is_empty() { bail!("Missing command after ':'") }
+            return Ok(Request::Command(argv));
+        }
+        if first.starts_with(['!', '?', '@', '<', '+', '>']) {
+            return Ok(Request::Run(ExternalCommand::parse(words)?));
+        }
+        if words.len() > 1 { bail!("Too many arguments for request {first}") }
+        // As in tig, `_` and `-` are the same and case does not matter.
+        let name = first.to_ascii_lowercase().replace('_', "-");
+        if UNSUPPORTED_REQUESTS.contains(&name.as_str()) { return Err(UnsupportedRequest(first.clone()).into()); }
+        REQUEST_NAMES.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, req)| req.clone())
+            .ok_or_else(|| anyhow!("Unknown request name: {first}"))
+    }
+
+    /// The request for a line entered at the `:` prompt: a line number,
+    /// `!` and an external command, a request name, or a command such as
+    /// `set` or `goto`.
+    pub fn from_prompt(line: &str) -> Result<Self> {
+        let raw = split_words(line)?;
+        let mut words: Vec<String> = raw.iter().map(|(word, _)| word.clone()).collect();
+        // Quotes tell color names from line rules, so keep them.
+        if words.len() > 1 && words[0] == "color" { words[1] = raw[1].1.clone(); }
+        let Some(first) = words.first() else { return Ok(Request::None) };
+        if first.chars().all(|c| c.is_ascii_digit()) {
+            let lineno = first.parse().map_err(|_| anyhow!("Unable to parse '{first}' as a line number"))?;
+            return Ok(Request::GotoLine(lineno));
+        }
+        if let Some(cmd) = first.strip_prefix('!') {
+            let argv: Vec<String> = Some(cmd.to_string()).filter(|c| !c.is_empty()).into_iter().chain(words[1..].iter().cloned()).collect();
+            if argv.is_empty() { bail!("Missing command after '!'") }
+            return Ok(Request::Run(ExternalCommand { argv, pager: true, ..ExternalCommand::default() }));
+        }
+        if words.len() == 1 && first.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            match Request::parse(&words) {
+                Ok(req) => return Ok(req),
+                Err(e) if e.is::<UnsupportedRequest>() => return Err(e),
+                Err(_) => {}
+            }
+        }
+        Ok(Request::Command(words))
+    }
+}
+
+/// Keymaps views can have; `generic` applies to all of them and `search`
+/// to the search prompt.
+pub const KEYMAPS: &[&str] = &[
+    "generic", "search", "main", "diff", "log", "reflog", "tree", "blob", "blame", "refs", "pager", "status", "stage", "stash", "grep",
+];
+
+/// Bindings tig-rs starts with, in tigrc syntax.
+const DEFAULT_BINDINGS: &str = r"
+bind generic <Enter> enter
+bind generic <Lt> back
+bind generic , parent
+bind generic <Tab> view-next
+bind generic R refresh
+bind generic <F5> refresh
+bind generic q view-close
+bind generic Q quit
+bind generic j move-down
+bind generic <Down> move-down
+bind generic k move-up
+bind generic <Up> move-up
+bind generic <C-D> move-half-page-down
+bind generic <C-U> move-half-page-up
+bind generic <PgDown> move-page-down
+bind generic <Space> move-page-down
+bind generic <PgUp> move-page-up
+bind generic - move-page-up
+bind generic <Home> move-first-line
+bind generic <End> move-last-line
+bind generic e edit
+bind generic b view-blame
+bind generic z stop-loading
+bind generic / search
+bind generic ? search-back
+bind generic n find-next
+bind generic N find-prev
+bind generic : prompt
+bind generic O maximize
+bind generic w :toggle wrap-lines
+bind generic y :toggle syntax-highlight
+bind generic ~ :toggle line-graphics
+bind generic W :toggle ignore-space
+bind main s view-status
+bind main S view-status
+bind main r view-refs
+bind main g view-grep
+bind main l view-log
+bind main L view-reflog
+bind main Y view-stash
+bind main t view-tree
+bind main G :toggle commit-title-graph
+bind main F :toggle commit-title-refs
+bind pager d view-diff
+bind pager D view-diff
+bind pager p view-pager
+bind pager m next-merge-parent
+bind pager g move-first-line
+bind pager G move-last-line
+bind pager [ :toggle diff-context -1
+bind pager ] :toggle diff-context +1
+bind diff p view-pager
+bind diff P view-pager
+bind diff d view-diff
+bind diff m next-merge-parent
+bind diff g move-first-line
+bind diff G move-last-line
+bind diff [ :toggle diff-context -1
+bind diff ] :toggle diff-context +1
+bind stage u status-update
+bind stage 1 stage-update-line
+bind stage 2 stage-update-part
+bind stage \ stage-split-chunk
+bind stage ! status-revert
+bind stage @ stage-next
+bind stage [ :toggle diff-context -1
+bind stage ] :toggle diff-context +1
+bind status u status-update
+bind status ! status-revert
+bind blob <Hash> :toggle blob-view-line-number
+bind grep g view-grep
+bind log } next
+bind log { previous
+bind reflog C checkout
+bind reflog ! reset-hard
+bind reflog F :toggle commit-title-refs
+bind refs C checkout
+bind refs ! branch-delete
+bind refs L view-reflog
+bind refs n branch-create
+bind stash A stash-apply
+bind stash P stash-pop
+bind stash ! stash-drop
+bind tree <Backspace> parent
+";
+
+/// What a key sequence means in a keymap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyLookup {
+    Request(Request),
+    /// The start of a longer binding; `Some` if it is also bound itself.
+    Prefix(Option<Request>),
+    Unbound,
+}
+
+/// Key sequences bound to requests, per keymap.
+#[derive(Debug, Clone)]
+pub struct Keymaps {
+    maps: HashMap<String, Vec<(Vec<Key>, Request)>>,
+}
+
+impl Default for Keymaps {
+    fn default() -> Self {
+        let mut keymaps = Keymaps { maps: HashMap::new() };
+        for line in DEFAULT_BINDINGS.lines().filter(|l| !l.is_empty()) {
+            let words: Vec<String> = line.split_whitespace().skip(1).map(str::to_string).collect();
+            keymaps.bind(&words[0], &words[1], &words[2..]).expect("default binding");
+        }
+        keymaps
+    }
+}
+
+impl Keymaps {
+    /// Bind `keys` in `keymap` to the request named by `request`,
+    /// replacing an earlier binding of the same keys.
+    pub fn bind(&mut self, keymap: &str, keys: &str, request: &[String]) -> Result<()> {
+        let keymap = if keymap == "branch" { "refs" } else { keymap };
+        if !KEYMAPS.contains(&keymap) { bail!("Unknown key map: {keymap}"); }
+        let keys = Key::parse_seq(keys)?;
+        let request = Request::parse(request)?;
+        let map = self.maps.entry(keymap.to_string()).or_default();
+        match map.iter_mut().find(|(k, _)| *k == keys) {
+            Some(binding) => binding.1 = request,
+            None => map.push((keys, request)),
+        }
+        Ok(())
+    }
+
+    /// The first key sequence that does `request` in `keymap`, for hints.
+    pub fn key_for(&self, keymap: &str, request: &Request) -> Option<String> {
+        [keymap, "generic"].iter()
+            .flat_map(|name| self.maps.get(*name).into_iter().flatten())
+            .filter(|(keys, req)| req == request && self.lookup(keymap, keys) == KeyLookup::Request(request.clone()))
+            .map(|(keys, _)| keys.iter().map(Key::to_string).collect())
+            .next()
+    }
+
+    /// What `keys` mean in `keymap`, falling back to `generic`.
+    pub fn lookup(&self, keymap: &str, keys: &[Key]) -> KeyLookup {
+        let maps = [keymap, "generic"].map(|name| self.maps.get(name).map_or(&[][..], Vec::as_slice));
+        let exact = maps.iter().find_map(|map| map.iter().find(|(k, _)| k == keys)).map(|(_, req)| req.clone());
+        let longer = maps.iter().any(|map| map.iter().any(|(k, _)| k.len() > keys.len() && k.starts_with(keys)));
+        match (exact, longer) {
+            (exact, true) => KeyLookup::Prefix(exact),
+            (Some(req), false) => KeyLookup::Request(req),
+            (None, false) => KeyLookup::Unbound,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_sequences() {
+        assert_eq!(Key::parse_seq("gg").unwrap(), [Key::Char('g'), Key::Char('g')]);
+        assert_eq!(Key::parse_seq("<C-f>").unwrap(), [Key::Ctrl('f')]);
+        assert_eq!(Key::parse_seq("<Ctrl-F>").unwrap(), [Key::Ctrl('f')]);
+        assert_eq!(Key::parse_seq("<Esc>o").unwrap(), [Key::Esc, Key::Char('o')]);
+        assert_eq!(Key::parse_seq("<pgdown>").unwrap(), [Key::PageDown]);
+        assert_eq!(Key::parse_seq("<F5>").unwrap(), [Key::F(5)]);
+        assert_eq!(Key::parse_seq("<Space>").unwrap(), [Key::Char(' ')]);
+        assert_eq!(Key::parse_seq("<").unwrap(), [Key::Char('<')]);
+        assert_eq!(Key::parse_seq("^").unwrap(), [Key::Char('^')]);
+    }
+
+    #[test]
+    fn reject_bad_key_sequences() {
+        assert!(Key::parse_seq("^F").unwrap_err().to_string().contains("<Ctrl-F>"));
+        assert!(Key::parse_seq("<Esc").is_err());
+        assert!(Key::parse_seq("<Bogus>").is_err());
+        assert!(Key::parse_seq("<F20>").is_err());
+        assert!(Key::parse_seq("<C-ab>").is_err());
+        assert!(Key::parse_seq("").is_err());
+    }
+
+    #[test]
+    fn page_keys_are_bound() {
+        let keymaps = Keymaps::default();
+        assert_eq!(keymaps.lookup("pager", &[Key::Char(' ')]), KeyLookup::Request(Request::MovePageDown));
+        assert_eq!(keymaps.lookup("diff", &[Key::PageDown]), KeyLookup::Request(Request::MovePageDown));
+        assert_eq!(keymaps.lookup("diff", &[Key::Ctrl('d')]), KeyLookup::Request(Request::MoveHalfPageDown));
+    }
+
+    #[test]
+    fn keys_for_requests() {
+        let mut keymaps = Keymaps::default();
+        assert_eq!(keymaps.key_for("main", &Request::MoveDown).as_deref(), Some("j"));
+        assert_eq!(keymaps.key_for("main", &Request::Enter).as_deref(), Some("<Enter>"));
+        assert_eq!(keymaps.key_for("tree", &Request::Parent).as_deref(), Some("<Backspace>"));
+        assert_eq!(keymaps.key_for("pager", &Request::MovePageDown).as_deref(), Some("<PageDown>"));
+        let toggle = Request::Command(vec!["toggle".into(), "wrap-lines".into()]);
+        assert_eq!(keymaps.key_for("diff", &toggle).as_deref(), Some("w"));
+        // Keys bound to something else in the view do not count.
+        keymaps.bind("main", "j", &["none".into()]).unwrap();
+        assert_eq!(keymaps.key_for("main", &Request::MoveDown).as_deref(), Some("<Down>"));
+        keymaps.bind("main", "<C-x>gg", &["move-down".into()]).unwrap();
+        keymaps.bind("main", "<Down>", &["none".into()]).unwrap();
+        assert_eq!(keymaps.key_for("main", &Request::MoveDown).as_deref(), Some("<C-x>gg"));
+        assert_eq!(keymaps.key_for("main", &Request::StashPop), None);
+    }
+}
@@ -3,19 +3,249 @@ use dirs::config_dir;
 use serde::{Deserialize, Serialize};
 use std::{fs, path::PathBuf};
 
+mod color;
+mod columns;
+mod external;
+mod keymap;
+mod tigrc;
+
+pub use color::{LineType, Theme, DEFAULT_GIT_COLORS};
+pub use columns::{ColumnType, ViewColumn, ViewColumns};
+pub use external::{CommandVars, ExternalCommand};
+pub use keymap::{Key, KeyLookup, Keymaps, Request, UnsupportedRequest, KEYMAPS};
+pub use tigrc::{prompt_completions, Config, Diagnostic, Severity, UnknownOption};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
     pub wrap_lines: bool,
     pub syntax_highlight: bool,
+    pub line_graphics: LineGraphics,
+    pub commit_title_graph: bool,
+    pub commit_title_refs: bool,
+    pub reference_format: String,
+    pub status_show_untracked_dirs: bool,
+    pub status_show_untracked_files: bool,
+    pub tab_size: usize,
+    pub editor_line_number: bool,
+    /// Columns of the blob view, as tig's `blob-view` option.
+    pub blob_view: String,
+    /// Columns of the blame view, as tig's `blame-view` option.
+    pub blame_view: String,
+    /// Columns of the grep view, as tig's `grep-view` option.
+    pub grep_view: String,
+    /// Columns of the refs view, as tig's `refs-view` option.
+    pub refs_view: String,
+    /// Columns of the tree view, as tig's `tree-view` option.
+    pub tree_view: String,
+    /// What the log view shows for each commit: `--stat`, `--name-status`,
+    /// `--patch` and `--cc`, as for `git log`.
+    pub log_options: String,
+    /// Similarity in percent from which a deleted and an added file show as
+    /// a rename; 0 turns rename detection off.
+    pub diff_rename_threshold: u16,
+    /// Likewise for copies of files changed in the same commit; off by default.
+    pub diff_copy_threshold: u16,
+    pub ignore_space: IgnoreSpace,
+    /// Lines of context around each diff hunk.
+    pub diff_context: u32,
+    /// Extra `git diff` options such as `--patience` or `-w`.
+    pub diff_options: String,
+    /// How git's `color.*` settings map to tig-rs colors, as
+    /// `diff.new=diff-add` pairs; `no` ignores them.
+    pub git_colors: String,
+    /// Whether `/` and `?` searches ignore case; `smart-case` does unless
+    /// the pattern has capitals.
+    pub ignore_case: IgnoreCase,
+    /// Whether searches wrap around at the top or bottom of a view.
+    pub wrap_search: bool,
+    /// Lines of `:` prompt history kept between runs; 0 keeps none.
+    pub history_size: usize,
+    /// How a view opened from another is shown beside it.
+    pub vertical_split: VerticalSplit,
+    /// Height of the lower view of a horizontal split, in lines or as a
+    /// percentage such as `67%`.
+    pub split_view_height: String,
+    /// Width of the right view of a vertical split, likewise.
+    pub split_view_width: String,
+    /// Whether a view opened beside another gets the focus.
+    pub focus_child: bool,
+    /// Whether moving the cursor in a view updates the view beside it.
+    pub send_child_enter: bool,
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        Self { wrap_lines: false, syntax_highlight: true }
+        Self {
+            wrap_lines: false,
+            syntax_highlight: true,
+            line_graphics: LineGraphics::Default,
+            commit_title_graph: true,
+            commit_title_refs: true,
+            reference_format: "[branch] <tag> {remote} ~replace~".into(),
+            status_show_untracked_dirs: true,
+            status_show_untracked_files: true,
+            tab_size: 8,
+            editor_line_number: true,
+            blob_view: "line-number:no,interval=5 text".into(),
+            blame_view: r#"id:yes,color file-name:auto author:full committer:no date:default,format="%Y-%m-%d" line-number:yes,interval=1 text"#.into(),
+            grep_view: "file-name:no line-number:yes,interval=1 text".into(),
+            refs_view: r#"line-number:no id:no date:default,format="%Y-%m-%d" author:full committer:no ref commit-title"#.into(),
+            tree_view: r#"line-number:no,interval=5 mode author:full committer:no file-size date:default,format="%Y-%m-%d" id:no file-name"#.into(),
+            log_options: "--cc --stat".into(),
+            diff_rename_threshold: 50,
+            diff_copy_threshold: 0,
+            ignore_space: IgnoreSpace::No,
+            diff_context: 3,
+            diff_options: String::new(),
+            git_colors: DEFAULT_GIT_COLORS.into(),
+            ignore_case: IgnoreCase::No,
+            wrap_search: true,
+            history_size: 500,
+            vertical_split: VerticalSplit::Auto,
+            split_view_height: "67%".into(),
+            split_view_width: "50%".into(),
+            focus_child: true,
+            send_child_enter: true,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineGraphics {
+    Ascii,
+    Default,
+    #[serde(rename = "utf-8")]
+    Utf8,
+    Auto,
+}
+
+impl LineGraphics {
+    /// Resolve `auto` to UTF-8 or ASCII based on the locale.
+    pub fn resolve(self) -> Self {
+        if self != LineGraphics::Auto { return self; }
+        let utf8 = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|v| !v.is_empty())
+            .map(|v| v.to_ascii_lowercase().replace('-', "").contains("utf8"))
+            .unwrap_or(false);
+        if utf8 { LineGraphics::Utf8 } else { LineGraphics::Ascii }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            LineGraphics::Ascii => LineGraphics::Default,
+            LineGraphics::Default => LineGraphics::Utf8,
+            LineGraphics::Utf8 => LineGraphics::Auto,
+            LineGraphics::Auto => LineGraphics::Ascii,
+        }
+    }
+}
+
+/// Which whitespace changes diffs leave out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IgnoreSpace {
+    No,
+    /// All whitespace, as `git diff -w`.
+    All,
+    /// Changes in the amount of whitespace, as `git diff -b`.
+    Some,
+    /// Whitespace at the end of lines.
+    AtEol,
+}
+
+impl IgnoreSpace {
+    /// The setting's value as written in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            IgnoreSpace::No => "no",
+            IgnoreSpace::All => "all",
+            IgnoreSpace::Some => "some",
+            IgnoreSpace::AtEol => "at-eol",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            IgnoreSpace::No => IgnoreSpace::All,
+            IgnoreSpace::All => IgnoreSpace::Some,
+            IgnoreSpace::Some => IgnoreSpace::AtEol,
+            IgnoreSpace::AtEol => IgnoreSpace::No,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IgnoreCase {
+    No,
+    Yes,
+    SmartCase,
+}
+
+impl IgnoreCase {
+    /// Whether a search for `pattern` ignores case.
+    pub fn applies_to(self, pattern: &str) -> bool {
+        match self {
+            IgnoreCase::No => false,
+            IgnoreCase::Yes => true,
+            IgnoreCase::SmartCase => !pattern.chars().any(char::is_uppercase),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            IgnoreCase::No => IgnoreCase::Yes,
+            IgnoreCase::Yes => IgnoreCase::SmartCase,
+            IgnoreCase::SmartCase => IgnoreCase::No,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VerticalSplit {
+    Horizontal,
+    Vertical,
+    /// Vertical on terminals wide enough for it.
+    Auto,
+}
+
+impl VerticalSplit {
+    /// Whether to split side by side on a terminal of `width` by `height`,
+    /// by tig's rule for `auto`.
+    pub fn is_vertical(self, width: u16, height: u16) -> bool {
+        match self {
+            VerticalSplit::Horizontal => false,
+            VerticalSplit::Vertical => true,
+            VerticalSplit::Auto => width > 160 || f32::from(width) * 0.5 > f32::from(height.saturating_sub(1)) * 2.0,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            VerticalSplit::Horizontal => VerticalSplit::Vertical,
+            VerticalSplit::Vertical => VerticalSplit::Auto,
+            VerticalSplit::Auto => VerticalSplit::Horizontal,
+        }
+    }
+}
+
+/// `size` of `total` for a `split-view-*` option: a number of lines or
+/// columns, a percentage such as `67%`, or a fraction below 1.
+pub fn split_size(size: &str, total: u16) -> Option<u16> {
+    let (number, percent) = match size.trim().strip_suffix('%') {
+        Some(number) => (number, true),
+        None => (size.trim(), false),
+    };
+    let value: f64 = number.parse().ok().filter(|v: &f64| *v >= 0.0)?;
+    let fraction = if percent { value / 100.0 } else { value };
+    let lines = if percent || fraction < 1.0 { f64::from(total) * fraction } else { value };
+    Some(lines.min(f64::from(total)) as u16)
+}
+
 impl Settings {
     pub fn load() -> Result<Self> {
         let mut s = Self::default();
@@ -31,6 +261,8 @@ impl Settings {
         Ok(s)
     }
 
+    /// Write every setting to the config, values read from tigrc files
+    /// included. The prompt saves single options with [`Settings::save_option`].
     pub fn save(&self) -> Result<()> {
         if let Some(path) = config_path() {
             if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
@@ -42,9 +274,36 @@ impl Settings {
     }
 }
 
+/// Where the `:` prompt history is kept, beside the config.
+pub fn history_path() -> Option<PathBuf> {
+    let mut dir = config_dir()?;
+    dir.push("tig-rs");
+    dir.push("history");
+    Some(dir)
+}
+
 fn config_path() -> Option<PathBuf> {
     let mut dir = config_dir()?;
     dir.push("tig-rs");
     dir.push("config.toml");
     Some(dir)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_sizes() {
+        assert_eq!(split_size("30", 100), Some(30));
+        assert_eq!(split_size("67%", 90), Some(60));
+        assert_eq!(split_size(" 50% ", 41), Some(20));
+        assert_eq!(split_size("0.25", 80), Some(20));
+        assert_eq!(split_size("1", 80), Some(1));
+        assert_eq!(split_size("200", 80), Some(80));
+        assert_eq!(split_size("150%", 80), Some(80));
+        assert_eq!(split_size("-10", 80), None);
+        assert_eq!(split_size("half", 80), None);
+        assert_eq!(split_size("%", 80), None);
+    }
+}
@@ -0,0 +1,538 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    ffi::OsString,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{color::LINE_TYPES, config_path, keymap::REQUEST_NAMES, split_size, Keymaps, Settings, Theme, UnsupportedRequest, ViewColumns, KEYMAPS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem with one line of a tigrc file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+/// `set` of an option tig-rs does not know; reported as a warning since
+/// tigrc files written for C tig use many of them.
+#[derive(Debug)]
+pub struct UnknownOption(pub String);
+
+impl fmt::Display for UnknownOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Unknown option name: {}", self.0) }
+}
+
+impl std::error::Error for UnknownOption {}
+
+/// Settings with the `set`, `bind` and `color` commands of the tigrc files
+/// applied on top, and what went wrong reading them.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub settings: Settings,
+    pub keymaps: Keymaps,
+    pub theme: Theme,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Files being read, innermost last, to catch recursive `source`.
+    sourcing: Vec<PathBuf>,
+}
+
+/// tig option names that differ from the setting's name.
+const OPTION_ALIASES: &[(&str, &str)] = &[
+    ("main-view-commit-title-graph", "commit-title-graph"),
+    ("main-view-commit-title-refs", "commit-title-refs"),
+    ("status-untracked-dirs", "status-show-untracked-dirs"),
+];
+
+impl Config {
+    pub fn new(settings: Settings) -> Self { Self { settings, ..Self::default() } }
+
+    /// Read the system and user tigrc files in C tig's order: `$TIGRC_SYSTEM`
+    /// or `/etc/tigrc`, then `$TIGRC_USER`, or else
+    /// `$XDG_CONFIG_HOME/tig/config` falling back to `~/.tigrc`.
+    pub fn load_tigrc(&mut self) {
+        let var = std::env::var_os;
+        self.load_tigrc_from(var("TIGRC_SYSTEM"), var("TIGRC_USER"), var("XDG_CONFIG_HOME"), dirs::home_dir());
+    }
+
+    /// `load_tigrc` with the environment variables and home directory given.
+    fn load_tigrc_from(&mut self, system: Option<OsString>, user: Option<OsString>, xdg: Option<OsString>, home: Option<PathBuf>) {
+        let system = system.map_or_else(|| PathBuf::from("/etc/tigrc"), PathBuf::from);
+        // An empty `$TIGRC_SYSTEM` skips the system file.
+        if !system.as_os_str().is_empty() { self.load_file(&system); }
+
+        if let Some(user) = user {
+            if !user.is_empty() { self.load_file(Path::new(&user)); }
+            return;
+        }
+        let xdg = xdg
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")));
+        let found = xdg.is_some_and(|dir| self.load_file(&dir.join("tig").join("config")));
+        if !found {
+            if let Some(home) = home { self.load_file(&home.join(".tigrc")); }
+        }
+    }
+
+    /// Read a tigrc file if it exists, returning whether it did. Failures
+    /// to read it are recorded like errors in it.
+    fn load_file(&mut self, path: &Path) -> bool {
+        match self.source(path) {
+            Ok(()) => true,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => {
+                self.diagnostics.push(Diagnostic {
+                    path: path.to_path_buf(), line: 0, severity: Severity::Error,
+                    message: format!("Error loading file: {e}"),
+                });
+                true
+            }
+        }
+    }
+
+    /// Apply the commands of the tigrc file at `path`, with `~/` expanded.
+    pub fn source(&mut self, path: &Path) -> io::Result<()> {
+        let path = expand_home(path);
+        let text = fs::read_to_string(&path)?;
+        let id = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if self.sourcing.contains(&id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Recursive source of {}", path.display())));
+        }
+        self.sourcing.push(id);
+        self.parse(&path, &text);
+        self.sourcing.pop();
+        Ok(())
+    }
+
+    /// Apply tigrc commands from `text`, recording problems as coming from
+    /// `path`. Bad lines are skipped and the rest still applied, as in tig.
+    pub fn parse(&mut self, path: &Path, text: &str) {
+        let mut lines = text.lines().enumerate();
+        while let Some((i, line)) = lines.next() {
+            // A backslash at the end of a line continues it on the next.
+            let mut line = line.to_string();
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) => { line.push(' '); line.push_str(next); }
+                    None => break,
+                }
+            }
+            if let Err(e) = self.command(&line) {
+                let severity = if e.is::<UnknownOption>() || e.is::<UnsupportedRequest>() { Severity::Warning } else { Severity::Error };
+                self.diagnostics.push(Diagnostic { path: path.to_path_buf(), line: i + 1, severity, message: e.to_string() });
+            }
+        }
+    }
+
+    /// Apply one tigrc line.
+    pub fn command(&mut self, line: &str) -> Result<()> {
+        let raw = split_words(line)?;
+        let words: Vec<String> = raw.iter().map(|(word, _)| word.clone()).collect();
+        let Some((cmd, args)) = words.split_first() else { return Ok(()) };
+        match cmd.as_str() {
+            "set" => self.set_command(args),
+            "bind" => {
+                match args {
+                    [keymap, key, request @ ..] if !request.is_empty() => self.keymaps.bind(keymap, key, request),
+                    _ => bail!("Invalid key binding: bind keymap key action"),
+                }
+            }
+            "color" => {
+                let [_, fg, bg, attrs @ ..] = args else { bail!("Invalid color mapping: color area fgcolor bgcolor [attrs]") };
+                self.theme.color(&raw[1].1, fg, bg, attrs)
+            }
+            "source" => {
+                let (quiet, path) = match args {
+                    [path] => (false, path),
+                    [opt, path] if opt == "-q" => (true, path),
+                    [opt, _] => bail!("Invalid source option: {opt}"),
+                    _ => bail!("Invalid source command: source [-q] <path>"),
+                };
+                match self.source(Path::new(path)) {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        if quiet { Ok(()) } else { bail!("File does not exist: {path}") }
+                    }
+                    res => Ok(res?),
+                }
+            }
+            _ => bail!("Unknown option command: {cmd}"),
+        }
+    }
+
+    /// `set <name> = <value>`, given the words after `set`.
+    fn set_command(&mut self, args: &[String]) -> Result<()> {
+        let [name, rest @ ..] = args else { bail!("Invalid set command: set option = value") };
+        match rest {
+            [eq, values @ ..] if eq == "=" && !values.is_empty() => self.settings.set_option(name, values),
+            [eq] if eq == "=" => bail!("Invalid set command: set option = value"),
+            _ => bail!("No value assigned to {name}"),
+        }
+    }
+}
+
+/// Commands the `:` prompt takes besides request names.
+const PROMPT_COMMANDS: &[&str] = &["set", "toggle", "goto", "source", "color", "bind"];
+
+/// Words completing the last word of `line` at the `:` prompt: command and
+/// request names, option names after `set` and `toggle`, and keymaps and
+/// color names after `bind` and `color`.
+pub fn prompt_completions(line: &str) -> Vec<String> {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    let partial = if line.ends_with(char::is_whitespace) { "" } else { words.pop().unwrap_or("") };
+    let mut names: Vec<String> = match words.as_slice() {
+        [] => PROMPT_COMMANDS.iter().chain(REQUEST_NAMES.iter().map(|(name, _)| name)).map(|name| name.to_string()).collect(),
+        ["set" | "toggle"] => Settings::option_names(),
+        ["bind"] => KEYMAPS.iter().map(|name| name.to_string()).collect(),
+        ["color"] => LINE_TYPES.iter().map(|(name, _)| name.to_string()).collect(),
+        _ => Vec::new(),
+    };
+    names.retain(|name| name.starts_with(partial) && name != partial);
+    names.sort();
+    names.dedup();
+    names
+}
+
+impl Settings {
+    /// Names `set` and `toggle` take, as tig spells them.
+    pub fn option_names() -> Vec<String> {
+        let table = toml::Table::try_from(Settings::default()).unwrap_or_default();
+        table.keys().map(|key| key.replace('_', "-")).chain(OPTION_ALIASES.iter().map(|(alias, _)| alias.to_string())).collect()
+    }
+
+    /// Set the option tig calls `name` to `values`. Strings and view
+    /// settings take all the words, other options exactly one.
+    pub fn set_option(&mut self, name: &str, values: &[String]) -> Result<()> {
+        if let Some((view, kind, option)) = Self::view_column_option(name) {
+            let [value] = values else { bail!("Option {name} only takes one value") };
+            return self.set_view_column(view, kind, option, value).map_err(|e| anyhow!("Invalid value for {name}: {e}"));
+        }
+        let (key, mut table) = self.option_table(name)?;
+        let value = match (&table[&key], values) {
+            (toml::Value::String(_), _) if key.ends_with("_view") => match ViewColumns::from_words(key.trim_end_matches("_view"), values) {
+                Ok(columns) => toml::Value::String(columns.to_string()),
+                Err(e) => bail!("Invalid value for {name}: {e}"),
+            },
+            (toml::Value::String(_), [value]) if key.starts_with("split_view_") && split_size(value, 100).is_none() => {
+                bail!("Invalid value for {name}: {value}")
+            }
+            (toml::Value::String(_), _) => toml::Value::String(values.join(" ")),
+            (_, [_, _, ..]) => bail!("Option {name} only takes one value"),
+            (toml::Value::Boolean(_), [value]) => match value.as_str() {
+                "yes" | "true" | "1" => toml::Value::Boolean(true),
+                "no" | "false" | "0" => toml::Value::Boolean(false),
+                _ => bail!("Invalid value for {name}: {value}"),
+            },
+            (toml::Value::Integer(_), [value]) => match value.parse() {
+                Ok(n) => toml::Value::Integer(n),
+                Err(_) => bail!("Invalid number for {name}: {value}"),
+            },
+            _ => bail!("Option {name} cannot be set"),
+        };
+        table.insert(key, value);
+        self.apply_table(name, table, &values.join(" "))
+    }
+
+    /// `:toggle <name> [arg]`: flip a boolean or a column such as
+    /// `blob-view-line-number`, switch an enum to its next value, or add
+    /// `arg` such as `+1` or `-1` to a number.
+    pub fn toggle_option(&mut self, name: &str, arg: Option<&str>) -> Result<()> {
+        if let Some((view, kind, None)) = Self::view_column_option(name) {
+            let shown = self.view_columns(view).get(kind).is_some_and(|column| column.shown());
+            return self.set_view_column(view, kind, None, if shown { "no" } else { "yes" });
+        }
+        let (key, mut table) = self.option_table(name)?;
+        let value = match (key.as_str(), &table[&key]) {
+            ("line_graphics", _) => { self.line_graphics = self.line_graphics.next(); return Ok(()); }
+            ("ignore_space", _) => { self.ignore_space = self.ignore_space.next(); return Ok(()); }
+            ("ignore_case", _) => { self.ignore_case = self.ignore_case.next(); return Ok(()); }
+            ("vertical_split", _) => { self.vertical_split = self.vertical_split.next(); return Ok(()); }
+            (_, toml::Value::Boolean(b)) => toml::Value::Boolean(!b),
+            (_, toml::Value::Integer(n)) => {
+                let Some(arg) = arg else { bail!("Toggling {name} needs an amount such as +1 or -1") };
+                let delta: i64 = arg.trim_start_matches('+').parse().map_err(|_| anyhow!("Invalid number: {arg}"))?;
+                toml::Value::Integer(n.saturating_add(delta).max(0))
+            }
+            _ => bail!("Option {name} cannot be toggled"),
+        };
+        table.insert(key, value);
+        self.apply_table(name, table, arg.unwrap_or(""))
+    }
+
+    /// Save the value of the option tig calls `name` to the config, leaving
+    /// the other options saved there as they were.
+    pub fn save_option(&self, name: &str) -> Result<()> {
+        match config_path() {
+            Some(path) => self.save_option_to(name, &path),
+            None => Ok(()),
+        }
+    }
+
+    fn save_option_to(&self, name: &str, path: &Path) -> Result<()> {
+        let key = match Self::view_column_option(name) {
+            Some((view, ..)) => format!("{view}_view"),
+            None => self.option_table(name)?.0,
+        };
+        let mut saved: toml::Table = match fs::read_to_string(path) {
+            Ok(data) => toml::from_str(&data).with_context(|| format!("Parsing TOML config: {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => return Err(e).with_context(|| format!("Reading config: {}", path.display())),
+        };
+        if let Some(value) = toml::Table::try_from(self)?.remove(&key) { saved.insert(key, value); }
+        if let Some(parent) = path.parent() { fs::create_dir_all(parent)?; }
+        fs::write(path, toml::to_string_pretty(&saved)?).with_context(|| format!("Writing config: {}", path.display()))
+    }
+
+    /// The settings as a table, and the key in it for the option tig
+    /// calls `name`.
+    fn option_table(&self, name: &str) -> Result<(String, toml::Table)> {
+        let option = OPTION_ALIASES.iter().find(|(alias, _)| *alias == name).map_or(name, |(_, option)| option);
+        let key = option.replace('-', "_");
+        let table = toml::Table::try_from(self)?;
+        if option.contains('_') || !table.contains_key(&key) { return Err(UnknownOption(name.to_string()).into()); }
+        Ok((key, table))
+    }
+
+    fn apply_table(&mut self, name: &str, table: toml::Table, value: &str) -> Result<()> {
+        match table.try_into() {
+            Ok(settings) => { *self = settings; Ok(()) }
+            Err(_) => bail!("Invalid value for {name}: {value}"),
+        }
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Split a tigrc line into words, each also as written. Quotes group
+/// words and are removed, a backslash in quotes escapes the next
+/// character, and a `#` starting a word begins a comment unless it is a
+/// `#rrggbb` color.
+pub(crate) fn split_words(line: &str) -> Result<Vec<(String, String)>> {
+    let mut words = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, first)) = chars.peek() else { break };
+        if first == '#' {
+            let rest: String = chars.clone().skip(1).map(|(_, c)| c).take_while(|c| !c.is_whitespace()).collect();
+            if rest.len() != 6 || !rest.chars().all(|c| c.is_ascii_hexdigit()) { break; }
+        }
+        let mut word = String::new();
+        let mut quote = None;
+        let mut end = line.len();
+        while let Some((i, c)) = chars.next() {
+            match (quote, c) {
+                (None, c) if c.is_whitespace() => { end = i; break; }
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), '\\') => word.extend(chars.next().map(|(_, c)| c)),
+                (_, c) => word.push(c),
+            }
+        }
+        if quote.is_some() { bail!("Unterminated quote in: {line}"); }
+        words.push((word, line[start..end].to_string()));
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnType, IgnoreSpace, Key, KeyLookup, LineGraphics, Request};
+
+    fn words(values: &[&str]) -> Vec<String> { values.iter().map(|v| v.to_string()).collect() }
+
+    /// An empty directory for tigrc files, removed when dropped. The path
+    /// is canonical, as sourced paths are.
+    struct TempDir { path: PathBuf, _dir: tempfile::TempDir }
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            Self { path: dir.path().canonicalize().unwrap(), _dir: dir }
+        }
+
+        fn write(&self, name: &str, text: &str) -> PathBuf {
+            let path = self.path.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
+            path
+        }
+    }
+
+    fn split(line: &str) -> Vec<String> { split_words(line).unwrap().into_iter().map(|(word, _)| word).collect() }
+
+    #[test]
+    fn split_quotes_and_comments() {
+        assert_eq!(split(r#"bind main x !git commit -m "a b" # done"#), ["bind", "main", "x", "!git", "commit", "-m", "a b"]);
+        assert_eq!(split(r#"set a = 'it''s' "q\"x""#), ["set", "a", "=", "its", "q\"x"]);
+        assert_eq!(split("color cursor #ffffff #00aa00 bold"), ["color", "cursor", "#ffffff", "#00aa00", "bold"]);
+        // Not six hex digits: a comment.
+        assert_eq!(split("color cursor white #fff"), ["color", "cursor", "white"]);
+        assert_eq!(split("  # set a = 1"), Vec::<String>::new());
+        assert_eq!(split_words("set x = \"y").unwrap_err().to_string(), "Unterminated quote in: set x = \"y");
+        // Each word also as written.
+        assert_eq!(split_words("color 'diff-add' green default").unwrap()[1], ("diff-add".into(), "'diff-add'".into()));
+    }
+
+    #[test]
+    fn diagnostics_name_file_and_line() {
+        let dir = TempDir::new();
+        let path = dir.write("tigrc", "set tab-size = 4\n\nset no-such-option = 1\nset wrap-lines = \\\n  maybe\nbogus\nset diff-context = \\\n 6\n");
+        let mut config = Config::default();
+        config.source(&path).unwrap();
+        let found: Vec<_> = config.diagnostics.iter().map(|d| (d.line, d.severity, d.to_string())).collect();
+        assert_eq!(found, [
+            (3, Severity::Warning, format!("{}:3: Unknown option name: no-such-option", path.display())),
+            (4, Severity::Error, format!("{}:4: Invalid value for wrap-lines: maybe", path.display())),
+            (6, Severity::Error, format!("{}:6: Unknown option command: bogus", path.display())),
+        ]);
+        // Bad lines are skipped, the rest still applied.
+        assert_eq!((config.settings.tab_size, config.settings.diff_context), (4, 6));
+    }
+
+    #[test]
+    fn source_nested_and_recursive_files() {
+        let dir = TempDir::new();
+        let inner = dir.write("inner", "set tab-size = 3\nsource -q missing\nsource missing\n");
+        let outer = dir.write("outer", &format!("source {}\nset diff-context = 7\n", inner.display()));
+        let mut config = Config::default();
+        config.source(&outer).unwrap();
+        assert_eq!((config.settings.tab_size, config.settings.diff_context), (3, 7));
+        let found: Vec<_> = config.diagnostics.iter().map(|d| (d.path.clone(), d.line, d.message.clone())).collect();
+        assert_eq!(found, [(inner.clone(), 3, "File does not exist: missing".to_string())]);
+
+        let a = dir.write("a", &format!("source {}\nset tab-size = 5\n", dir.path.join("b").display()));
+        dir.write("b", &format!("set diff-context = 2\nsource {}\n", a.display()));
+        let mut config = Config::default();
+        config.source(&a).unwrap();
+        assert_eq!((config.settings.tab_size, config.settings.diff_context), (5, 2));
+        let [diagnostic] = config.diagnostics.as_slice() else { panic!("{:?}", config.diagnostics) };
+        assert_eq!((&diagnostic.path, diagnostic.line, diagnostic.severity), (&dir.path.join("b"), 2, Severity::Error));
+        assert_eq!(diagnostic.message, format!("Recursive source of {}", a.display()));
+    }
+
+    #[test]
+    fn bind_to_none_masks_generic_binding() {
+        let mut config = Config::default();
+        config.parse(Path::new("tigrc"), "bind main j none\nbind main\n");
+        assert_eq!(config.keymaps.lookup("main", &[Key::Char('j')]), KeyLookup::Request(Request::None));
+        assert_eq!(config.keymaps.lookup("diff", &[Key::Char('j')]), KeyLookup::Request(Request::MoveDown));
+        let [diagnostic] = config.diagnostics.as_slice() else { panic!("{:?}", config.diagnostics) };
+        assert_eq!(diagnostic.line, 2);
+    }
+
+    #[test]
+    fn load_order() {
+        let dir = TempDir::new();
+        // Each file sets an unknown option named after it, so the warnings
+        // tell which files were read and in what order.
+        let rc = |name: &str| dir.write(name, &format!("set {} = 1\n", name.replace(['/', '.'], "-")));
+        let system = rc("system");
+        let user = rc("user");
+        let home = dir.path.join("home");
+        rc("home/.tigrc");
+        let loaded = |system: &Path, user: Option<&Path>, xdg: Option<&Path>| {
+            let mut config = Config::default();
+            let os = |path: &Path| path.as_os_str().to_os_string();
+            config.load_tigrc_from(Some(os(system)), user.map(os), xdg.map(os), Some(home.clone()));
+            config.diagnostics.into_iter().map(|d| d.path.strip_prefix(&dir.path).unwrap().display().to_string()).collect::<Vec<_>>()
+        };
+        let none = Path::new("");
+
+        assert_eq!(loaded(&system, Some(&user), None), ["system", "user"]);
+        // An empty `$TIGRC_USER` reads no user file at all.
+        assert_eq!(loaded(&system, Some(none), None), ["system"]);
+        assert_eq!(loaded(none, None, None), ["home/.tigrc"]);
+        rc("home/.config/tig/config");
+        assert_eq!(loaded(none, None, None), ["home/.config/tig/config"]);
+        rc("xdg/tig/config");
+        assert_eq!(loaded(none, None, Some(&dir.path.join("xdg"))), ["xdg/tig/config"]);
+        // Falls back to `~/.tigrc` when `$XDG_CONFIG_HOME` has no config.
+        assert_eq!(loaded(none, None, Some(&dir.path.join("empty"))), ["home/.tigrc"]);
+    }
+
+    #[test]
+    fn set_options_by_type() {
+        let mut settings = Settings::default();
+        settings.set_option("wrap-lines", &words(&["yes"])).unwrap();
+        settings.set_option("tab-size", &words(&["4"])).unwrap();
+        settings.set_option("diff-options", &words(&["--patience", "-w"])).unwrap();
+        settings.set_option("line-graphics", &words(&["utf-8"])).unwrap();
+        settings.set_option("main-view-commit-title-graph", &words(&["no"])).unwrap();
+        assert!(settings.wrap_lines);
+        assert_eq!(settings.tab_size, 4);
+        assert_eq!(settings.diff_options, "--patience -w");
+        assert_eq!(settings.line_graphics, LineGraphics::Utf8);
+        assert!(!settings.commit_title_graph);
+    }
+
+    #[test]
+    fn reject_bad_values() {
+        let mut settings = Settings::default();
+        assert!(settings.set_option("wrap-lines", &words(&["maybe"])).is_err());
+        assert!(settings.set_option("tab-size", &words(&["4", "5"])).is_err());
+        assert!(settings.set_option("tab-size", &words(&["four"])).is_err());
+        assert!(settings.set_option("line-graphics", &words(&["fancy"])).is_err());
+        assert!(settings.set_option("split-view-height", &words(&["tall"])).is_err());
+        assert!(settings.set_option("no-such-option", &words(&["1"])).unwrap_err().is::<UnknownOption>());
+        // Setting names are spelled with dashes.
+        assert!(settings.set_option("tab_size", &words(&["4"])).unwrap_err().is::<UnknownOption>());
+        assert_eq!(settings.tab_size, Settings::default().tab_size);
+    }
+
+    #[test]
+    fn toggle_options_by_type() {
+        let mut settings = Settings::default();
+        settings.toggle_option("wrap-lines", None).unwrap();
+        settings.toggle_option("ignore-space", None).unwrap();
+        settings.toggle_option("diff-context", Some("+2")).unwrap();
+        settings.toggle_option("tab-size", Some("-100")).unwrap();
+        assert!(settings.wrap_lines);
+        assert_eq!(settings.ignore_space, IgnoreSpace::All);
+        assert_eq!(settings.diff_context, 5);
+        assert_eq!(settings.tab_size, 0);
+        assert!(settings.toggle_option("diff-context", None).is_err());
+        assert!(settings.toggle_option("diff-options", None).is_err());
+
+        settings.toggle_option("blob-view-line-number", None).unwrap();
+        assert_eq!(settings.blob_view, "line-number:yes,interval=5 text:yes");
+        settings.toggle_option("blob-view-line-number", None).unwrap();
+        assert!(!settings.view_columns("blob").get(ColumnType::LineNumber).unwrap().shown());
+    }
+
+    #[test]
+    fn save_only_the_option_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "tab_size = 4\n").unwrap();
+        // As if set by a tigrc file.
+        let mut settings = Settings { status_show_untracked_files: false, ..Settings::default() };
+        settings.toggle_option("wrap-lines", None).unwrap();
+        settings.save_option_to("wrap-lines", &path).unwrap();
+        let saved: toml::Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved["tab_size"].as_integer(), Some(4));
+        assert_eq!(saved["wrap_lines"].as_bool(), Some(true));
+    }
+}
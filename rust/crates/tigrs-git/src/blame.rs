@@ -0,0 +1,185 @@
+use crate::{diff_to_text, DiffSettings, Ident, Interner};
+use anyhow::{bail, Result};
+use git2::{BlameOptions, DiffOptions, Oid, Repository};
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+/// Commit a group of lines was last changed in. Uncommitted lines carry
+/// the zero id.
+#[derive(Debug, Clone)]
+pub struct BlameCommit {
+    pub id: Oid,
+    pub author: Ident,
+    pub committer: Ident,
+    /// Author time.
+    pub time: i64,
+    pub summary: Box<str>,
+    /// File name in that commit, which differs from the blamed one after renames.
+    pub path: String,
+}
+
+impl BlameCommit {
+    pub fn is_uncommitted(&self) -> bool { self.id.is_zero() }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    pub commit: Arc<BlameCommit>,
+    /// Line number in the blamed file, starting at 1.
+    pub lineno: usize,
+    /// Line number in `commit.path` at `commit.id`.
+    pub orig_lineno: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileBlame {
+    pub path: String,
+    /// Revision blamed; `None` for the work tree.
+    pub rev: Option<Oid>,
+    pub lines: Vec<BlameLine>,
+}
+
+/// Blame `path` as of `rev`, or as found in the work tree when `rev` is `None`.
+pub fn blame_file(repo: &Repository, path: &str, rev: Option<Oid>) -> Result<FileBlame> {
+    let content = match rev {
+        Some(id) => {
+            let tree = repo.find_commit(id)?.tree()?;
+            let entry = tree.get_path(Path::new(path))?;
+            repo.find_blob(entry.id())?.content().to_vec()
+        }
+        None => match repo.workdir() {
+            Some(dir) => std::fs::read(dir.join(path))?,
+            None => bail!("The blame view requires a working tree or a revision"),
+        },
+    };
+    if content.contains(&0) { bail!("Cannot blame binary file {path}"); }
+
+    let mut opts = BlameOptions::new();
+    opts.track_copies_same_file(true);
+    if let Some(id) = rev { opts.newest_commit(id); }
+    let blame = repo.blame_file(Path::new(path), Some(&mut opts))?;
+    // Lines not in HEAD yet are attributed to the zero id.
+    let blame = if rev.is_none() { blame.blame_buffer(&content)? } else { blame };
+
+    let mut idents = Interner::default();
+    let mut commits: HashMap<(Oid, String), Arc<BlameCommit>> = HashMap::new();
+    let text = String::from_utf8_lossy(&content);
+    let mut lines = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let Some(hunk) = blame.get_line(i + 1) else { continue };
+        let orig_path = hunk.path().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_string());
+        let id = hunk.final_commit_id();
+        let commit = commits.entry((id, orig_path.clone())).or_insert_with(|| {
+            let sig = hunk.final_signature();
+            let found = repo.find_commit(id).ok();
+            let summary = found.as_ref().and_then(|c| c.summary().map(Into::into)).unwrap_or_default();
+            let (author, committer) = match &found {
+                Some(c) if !id.is_zero() => (idents.ident(&sig), idents.ident(&c.committer())),
+                _ => {
+                    let nobody = Ident { name: idents.intern("Not Committed Yet"), email: idents.intern("not.committed.yet") };
+                    (nobody.clone(), nobody)
+                }
+            };
+            Arc::new(BlameCommit {
+                id,
+                author,
+                committer,
+                time: if id.is_zero() { 0 } else { sig.when().seconds() },
+                summary,
+                path: orig_path,
+            })
+        });
+        let offset = i + 1 - hunk.final_start_line();
+        lines.push(BlameLine {
+            commit: commit.clone(),
+            lineno: i + 1,
+            orig_lineno: hunk.orig_start_line() + offset,
+            text: line.to_string(),
+        });
+    }
+    Ok(FileBlame { path: path.to_string(), rev, lines })
+}
+
+/// Where to continue when peeling back `line`: the first parent of its
+/// commit, the file name there and the line closest to the blamed one.
+/// A file renamed or copied in that commit is followed to its old name.
+pub fn blame_parent(repo: &Repository, line: &BlameLine, settings: &DiffSettings) -> Result<(Oid, String, usize)> {
+    let commit = &line.commit;
+    if commit.is_uncommitted() {
+        // The work tree's parent is HEAD.
+        let head = repo.head()?.peel_to_commit()?;
+        return Ok((head.id(), commit.path.clone(), line.orig_lineno));
+    }
+    let git_commit = repo.find_commit(commit.id)?;
+    let Ok(parent) = git_commit.parent(0) else { bail!("The selected commit has no parents with this file") };
+    let parent_tree = parent.tree()?;
+    let tree = git_commit.tree()?;
+    let old_path = if parent_tree.get_path(Path::new(&commit.path)).is_ok() {
+        commit.path.clone()
+    } else {
+        match settings.rename_source(repo, Some(&parent_tree), &tree, &commit.path)? {
+            Some(source) => source,
+            None => bail!("The selected commit has no parents with this file"),
+        }
+    };
+
+    let mut opts = DiffOptions::new();
+    opts.pathspec(&old_path).pathspec(&commit.path).disable_pathspec_match(true).context_lines(0);
+    let diff = settings.following().diff_trees(repo, Some(&parent_tree), Some(&tree), Some(&mut opts))?;
+    // Shift by the size of earlier hunks; inside a hunk, go to its start.
+    // Empty ranges start after the line they name.
+    let target = line.orig_lineno as i64;
+    let mut lineno = target;
+    diff.foreach(&mut |_, _| true, None, Some(&mut |_, hunk| {
+        let range = |start: u32, lines: u32| (start as i64 + (lines == 0) as i64, lines as i64);
+        let (old_start, old_lines) = range(hunk.old_start(), hunk.old_lines());
+        let (new_start, new_lines) = range(hunk.new_start(), hunk.new_lines());
+        if target >= new_start + new_lines {
+            lineno = target + (old_start + old_lines) - (new_start + new_lines);
+            true
+        } else {
+            if target >= new_start { lineno = old_start; }
+            false
+        }
+    }), None).or_else(|e| if e.code() == git2::ErrorCode::User { Ok(()) } else { Err(e) })?;
+    Ok((parent.id(), old_path, lineno.max(1) as usize))
+}
+
+/// Diff of uncommitted changes to `path` against HEAD, shown for lines
+/// blamed on the work tree.
+pub fn uncommitted_diff_text(repo: &Repository, path: &str) -> Result<String> {
+    let mut opts = DiffOptions::new();
+    opts.pathspec(path).disable_pathspec_match(true);
+    let head = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let diff = repo.diff_tree_to_workdir_with_index(head.as_ref(), Some(&mut opts))?;
+    diff_to_text(&diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{commit, test_repo};
+
+    #[test]
+    fn peel_back_through_a_rename() {
+        let repo = test_repo("blame-rename");
+        let old: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+        let first = commit(&repo, "first", &[], &[("old", &old)]);
+        // Renamed with two lines added on top and line 6 changed.
+        let new = format!("top 1\ntop 2\n{}", old.replace("line 6\n", "six\n"));
+        let renamed = commit(&repo, "rename", &[first], &[("new", &new)]);
+        let changed = commit(&repo, "change", &[renamed], &[("new", &new.replace("line 9\n", "LINE 9\n"))]);
+
+        let blame = blame_file(&repo, "new", Some(changed)).unwrap();
+        let line = |text: &str| blame.lines.iter().find(|l| l.text == text).unwrap();
+        assert_eq!((line("line 5").commit.id, line("line 5").commit.path.as_str(), line("line 5").orig_lineno), (first, "old", 5));
+
+        let settings = DiffSettings::default();
+        assert_eq!(blame_parent(&repo, line("LINE 9"), &settings).unwrap(), (renamed, "new".to_string(), 11));
+        // Lines of the renaming commit map to the old name, past the lines
+        // added above them or onto the start of their own hunk.
+        assert_eq!(blame_parent(&repo, line("six"), &settings).unwrap(), (first, "old".to_string(), 6));
+        assert_eq!(blame_parent(&repo, line("top 2"), &settings).unwrap(), (first, "old".to_string(), 1));
+        assert!(blame_parent(&repo, line("line 5"), &settings).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{short_id, DiffSettings};
+use anyhow::Result;
+use git2::{Commit, Oid, Patch, Repository};
+use std::path::{Path, PathBuf};
+
+/// A diff line of one parent against the merge result, keyed by where it
+/// shows up in the result.
+#[derive(Default)]
+struct ParentDiff {
+    /// Result lines the parent does not have.
+    added: Vec<bool>,
+    /// Parent lines removed just before each result line; the last slot
+    /// holds lines removed at the end.
+    lost: Vec<Vec<String>>,
+    /// Parent lines before each slot, for hunk headers.
+    before: Vec<usize>,
+}
+
+fn parent_diff(old: &[u8], new: &[u8], result_lines: usize, settings: &DiffSettings) -> Result<ParentDiff> {
+    let mut d = ParentDiff { added: vec![false; result_lines], lost: vec![Vec::new(); result_lines + 1], before: Vec::new() };
+    let mut opts = settings.options();
+    opts.context_lines(0);
+    let patch = Patch::from_buffers(old, None, new, None, Some(&mut opts))?;
+    for h in 0..patch.num_hunks() {
+        let (hunk, _) = patch.hunk(h)?;
+        // Removed lines come before the added ones of the same hunk.
+        let slot = if hunk.new_lines() == 0 { hunk.new_start() as usize } else { hunk.new_start() as usize - 1 };
+        for l in 0..patch.num_lines_in_hunk(h)? {
+            let line = patch.line_in_hunk(h, l)?;
+            match (line.origin(), line.new_lineno()) {
+                ('+', Some(n)) => d.added[n as usize - 1] = true,
+                ('-', _) => d.lost[slot].push(String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string()),
+                _ => {}
+            }
+        }
+    }
+    let mut count = 0;
+    for j in 0..=result_lines {
+        d.before.push(count);
+        count += d.lost[j].len() + usize::from(j < result_lines && !d.added[j]);
+    }
+    d.before.push(count);
+    Ok(d)
+}
+
+/// `-start,count` / `+start,count` of a hunk header.
+fn range(sign: char, before: usize, count: usize) -> String {
+    format!("{sign}{},{count}", if count == 0 { before } else { before + 1 })
+}
+
+fn blob_content(repo: &Repository, commit: &Commit<'_>, path: &Path) -> Result<Option<(Oid, Vec<u8>)>> {
+    let Ok(entry) = commit.tree()?.get_path(path) else { return Ok(None) };
+    let blob = repo.find_blob(entry.id())?;
+    Ok(Some((entry.id(), blob.content().to_vec())))
+}
+
+/// The combined diff of one file, or `None` when every hunk matches one of
+/// the parents and `--cc` would leave it out.
+fn combined_file(repo: &Repository, commit: &Commit<'_>, path: &Path, settings: &DiffSettings) -> Result<Option<String>> {
+    let parents: Vec<Commit<'_>> = commit.parents().collect();
+    let result = blob_content(repo, commit, path)?;
+    let olds = parents.iter().map(|p| blob_content(repo, p, path)).collect::<Result<Vec<_>>>()?;
+    let name = path.display();
+
+    let ids: Vec<String> = olds.iter().map(|o| o.as_ref().map_or("0000000".into(), |(id, _)| short_id(id))).collect();
+    let mut out = format!("diff --cc {name}\nindex {}..{}\n", ids.join(","), result.as_ref().map_or("0000000".into(), |(id, _)| short_id(id)));
+    let binary = |content: &[u8]| content.contains(&0);
+    if result.as_ref().is_some_and(|(_, c)| binary(c)) || olds.iter().flatten().any(|(_, c)| binary(c)) {
+        out.push_str("Binary files differ\n");
+        return Ok(Some(out));
+    }
+    out.push_str(&format!("--- a/{name}\n"));
+    out.push_str(&match result { Some(_) => format!("+++ b/{name}\n"), None => "+++ /dev/null\n".into() });
+
+    let new = result.map(|(_, c)| c).unwrap_or_default();
+    let text = String::from_utf8_lossy(&new);
+    let lines: Vec<&str> = text.lines().collect();
+    let n = lines.len();
+    let diffs = olds.iter()
+        .map(|o| parent_diff(o.as_ref().map_or(&[][..], |(_, c)| c), &new, n, settings))
+        .collect::<Result<Vec<_>>>()?;
+    let context = settings.context as usize;
+    let changed = |j: usize, d: &ParentDiff| !d.lost[j].is_empty() || (j < n && d.added[j]);
+
+    // Changes less than a context apart form one hunk, and only hunks where
+    // the result differs from every parent are kept, as in git.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for j in (0..=n).filter(|&j| diffs.iter().any(|d| changed(j, d))) {
+        match hunks.last_mut() {
+            Some((_, end)) if j <= *end + context => *end = j,
+            _ => hunks.push((j, j)),
+        }
+    }
+    hunks.retain(|&(s, e)| diffs.iter().all(|d| (s..=e).any(|j| changed(j, d))));
+    if hunks.is_empty() { return Ok(None); }
+
+    // Add context, joining hunks whose context overlaps.
+    let mut shown: Vec<(usize, usize)> = Vec::new();
+    for (s, e) in hunks {
+        let (s, e) = (s.saturating_sub(context), (e + context).min(n));
+        match shown.last_mut() {
+            Some((_, end)) if s <= *end + 1 => *end = e,
+            _ => shown.push((s, e)),
+        }
+    }
+
+    let at = "@".repeat(parents.len() + 1);
+    for (s, e) in shown {
+        let mut header = at.clone();
+        for d in &diffs {
+            header.push(' ');
+            header.push_str(&range('-', d.before[s], d.before[e + 1] - d.before[s]));
+        }
+        header.push(' ');
+        header.push_str(&range('+', s, (s..=e).filter(|&j| j < n).count()));
+        out.push_str(&format!("{header} {at}\n"));
+        for j in s..=e {
+            // The same line removed from several parents is shown once.
+            let mut removed: Vec<(String, Vec<bool>)> = Vec::new();
+            for (i, d) in diffs.iter().enumerate() {
+                let mut from = 0;
+                for line in &d.lost[j] {
+                    match removed[from..].iter().position(|(text, marks)| text == line && !marks[i]) {
+                        Some(k) => { removed[from + k].1[i] = true; from += k + 1; }
+                        None => {
+                            let mut marks = vec![false; diffs.len()];
+                            marks[i] = true;
+                            removed.push((line.clone(), marks));
+                            from = removed.len();
+                        }
+                    }
+                }
+            }
+            for (text, marks) in removed {
+                let prefix: String = marks.iter().map(|&m| if m { '-' } else { ' ' }).collect();
+                out.push_str(&format!("{prefix}{text}\n"));
+            }
+            if let Some(line) = lines.get(j) {
+                let prefix: String = diffs.iter().map(|d| if d.added[j] { '+' } else { ' ' }).collect();
+                out.push_str(&format!("{prefix}{line}\n"));
+            }
+        }
+    }
+    Ok(Some(out))
+}
+
+/// Changes of a merge commit in `git diff --cc` format: only files that
+/// differ from every parent, and only hunks where the result matches none
+/// of them. Diffs are limited to `paths` when any are given.
+pub fn combined_diff_text(repo: &Repository, commit: &Commit<'_>, paths: &[String], settings: &DiffSettings) -> Result<String> {
+    let tree = commit.tree()?;
+    let mut changed: Option<Vec<PathBuf>> = None;
+    for parent in commit.parents() {
+        let mut opts = settings.options();
+        for p in paths { opts.pathspec(p); }
+        let diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&tree), Some(&mut opts))?;
+        let files: Vec<PathBuf> = diff.deltas()
+            .filter_map(|d| d.new_file().path().or(d.old_file().path()).map(Path::to_path_buf))
+            .collect();
+        changed = Some(match changed {
+            None => files,
+            Some(prev) => prev.into_iter().filter(|p| files.contains(p)).collect(),
+        });
+    }
+    let mut out = String::new();
+    for path in changed.unwrap_or_default() {
+        if let Some(text) = combined_file(repo, commit, &path, settings)? { out.push_str(&text); }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{commit, test_repo};
+    use crate::{commit_diff_text, DiffParent};
+
+    #[test]
+    fn combined_and_per_parent_diffs() {
+        let repo = test_repo("combined");
+        let lines = |first: &str, last: &str| format!("{first}\n2\n3\n4\n5\n6\n7\n8\n9\n{last}\n");
+        let base = commit(&repo, "base", &[], &[("clean", &lines("1", "10")), ("file", "a\nb\nc\n")]);
+        let ours = commit(&repo, "ours", &[base], &[("clean", &lines("one", "10")), ("file", "a\nX\nc\n")]);
+        let theirs = commit(&repo, "theirs", &[base], &[("clean", &lines("1", "ten")), ("file", "a\nY\nc\n")]);
+        let merge = commit(&repo, "merge", &[ours, theirs], &[("clean", &lines("one", "ten")), ("file", "a\nZ\nc\n")]);
+        let settings = DiffSettings::default();
+
+        // `clean` takes each change from one parent and is left out.
+        let text = combined_diff_text(&repo, &repo.find_commit(merge).unwrap(), &[], &settings).unwrap();
+        let patch: Vec<&str> = text.lines().skip(1).collect();
+        assert!(text.starts_with("diff --cc file\n"));
+        assert_eq!(patch[1..], ["--- a/file", "+++ b/file", "@@@ -1,3 -1,3 +1,3 @@@", "  a", "- X", " -Y", "++Z", "  c"]);
+
+        let text = commit_diff_text(&repo, merge, DiffParent::Parent(1), &settings).unwrap();
+        assert!(text.contains(&format!("Diff against parent 2 of 2: {} theirs\n", short_id(&theirs))));
+        assert!(text.contains("-1\n+one\n") && text.contains("-Y\n+Z\n") && !text.contains("+ten"));
+        assert_eq!(DiffParent::Combined.next(2), DiffParent::Parent(0));
+        assert_eq!(DiffParent::Parent(1).next(2), DiffParent::Combined);
+    }
+}
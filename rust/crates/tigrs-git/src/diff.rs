@@ -0,0 +1,163 @@
+use anyhow::{anyhow, bail, Result};
+use git2::{Delta, Diff, DiffFindOptions, DiffOptions, Repository, Tree};
+use std::path::Path;
+
+/// How diffs are computed, from the diff settings: rename and copy
+/// detection, context size, whitespace handling and `diff-options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSettings {
+    /// Similarity in percent from which a deleted and an added file are
+    /// shown as a rename; `None` turns rename detection off.
+    pub renames: Option<u16>,
+    /// Likewise for files copied from a file changed in the same commit.
+    pub copies: Option<u16>,
+    /// Lines of context around each hunk.
+    pub context: u32,
+    /// `-w`: ignore all whitespace.
+    pub ignore_whitespace: bool,
+    /// `-b`: ignore changes in the amount of whitespace.
+    pub ignore_whitespace_change: bool,
+    /// `--ignore-space-at-eol`.
+    pub ignore_whitespace_eol: bool,
+    pub patience: bool,
+    pub minimal: bool,
+    /// `-a`: diff binary files as text.
+    pub text: bool,
+}
+
+impl Default for DiffSettings {
+    /// What `git diff` does without options.
+    fn default() -> Self {
+        Self {
+            renames: Some(50), copies: None, context: 3,
+            ignore_whitespace: false, ignore_whitespace_change: false, ignore_whitespace_eol: false,
+            patience: false, minimal: false, text: false,
+        }
+    }
+}
+
+impl DiffSettings {
+    /// Apply `git diff` style options, as given by `diff-options`.
+    pub fn parse_options(&mut self, args: &str) -> Result<()> {
+        for arg in args.split_whitespace() {
+            if let Some(n) = arg.strip_prefix("--unified=").or_else(|| arg.strip_prefix("-U").filter(|n| !n.is_empty())) {
+                self.context = n.parse().map_err(|_| anyhow!("Invalid context size: {arg}"))?;
+                continue;
+            }
+            match arg {
+                "-w" | "--ignore-all-space" => self.ignore_whitespace = true,
+                "-b" | "--ignore-space-change" => self.ignore_whitespace_change = true,
+                "--ignore-space-at-eol" => self.ignore_whitespace_eol = true,
+                "--patience" => self.patience = true,
+                "--minimal" => self.minimal = true,
+                "-a" | "--text" => self.text = true,
+                "-M" | "--find-renames" => self.renames = self.renames.or(Self::default().renames),
+                "-C" | "--find-copies" => self.copies = Some(50),
+                "--no-renames" => self.renames = None,
+                _ => bail!("Unsupported diff option: {arg}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Options for diffs shown with these settings.
+    pub fn options(&self) -> DiffOptions {
+        let mut opts = DiffOptions::new();
+        opts.context_lines(self.context)
+            .ignore_whitespace(self.ignore_whitespace)
+            .ignore_whitespace_change(self.ignore_whitespace_change)
+            .ignore_whitespace_eol(self.ignore_whitespace_eol)
+            .patience(self.patience)
+            .minimal(self.minimal)
+            .force_text(self.text);
+        opts
+    }
+
+    /// Turn matching deletes and adds in `diff` into renames and copies.
+    pub fn find_similar(&self, diff: &mut Diff<'_>) -> Result<()> {
+        if self.renames.is_none() && self.copies.is_none() { return Ok(()); }
+        let mut opts = DiffFindOptions::new();
+        if let Some(threshold) = self.renames { opts.renames(true).rename_threshold(threshold); }
+        if let Some(threshold) = self.copies { opts.copies(true).copy_threshold(threshold); }
+        diff.find_similar(Some(&mut opts))?;
+        Ok(())
+    }
+
+    /// Diff two trees, detecting renames and copies.
+    pub fn diff_trees<'r>(&self, repo: &'r Repository, old: Option<&Tree<'_>>, new: Option<&Tree<'_>>, opts: Option<&mut DiffOptions>) -> Result<Diff<'r>> {
+        let mut diff = repo.diff_tree_to_tree(old, new, opts)?;
+        self.find_similar(&mut diff)?;
+        Ok(diff)
+    }
+
+    /// These settings with rename detection on, as following a file
+    /// across renames needs it even when diffs are shown without.
+    pub(crate) fn following(self) -> Self {
+        Self { renames: self.renames.or(Self::default().renames), ..self }
+    }
+
+    /// These settings with whitespace compared exactly, for patches that
+    /// are applied: with whitespace ignored, context lines come from one
+    /// side only and whitespace changes inside a hunk are lost.
+    pub(crate) fn exact_whitespace(self) -> Self {
+        Self { ignore_whitespace: false, ignore_whitespace_change: false, ignore_whitespace_eol: false, ..self }
+    }
+
+    /// The file `path` of `new` was renamed or copied from, if it is not in
+    /// `old` under that name.
+    pub fn rename_source(&self, repo: &Repository, old: Option<&Tree<'_>>, new: &Tree<'_>, path: &str) -> Result<Option<String>> {
+        let diff = self.following().diff_trees(repo, old, Some(new), None)?;
+        let source = diff.deltas()
+            .find(|d| matches!(d.status(), Delta::Renamed | Delta::Copied) && d.new_file().path() == Some(Path::new(path)))
+            .and_then(|d| d.old_file().path().map(|p| p.to_string_lossy().into_owned()));
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{commit, test_repo};
+
+    fn statuses(repo: &Repository, old: &Tree<'_>, new: &Tree<'_>, settings: DiffSettings) -> Vec<(Delta, String)> {
+        let diff = settings.diff_trees(repo, Some(old), Some(new), None).unwrap();
+        diff.deltas().map(|d| (d.status(), d.new_file().path().unwrap().to_string_lossy().into_owned())).collect()
+    }
+
+    #[test]
+    fn rename_and_copy_thresholds() {
+        let repo = test_repo("diff-renames");
+        let text: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+        let changed = text.replace("line 1\n", "one\n").replace("line 2\n", "two\n").replace("line 3\n", "three\n");
+        let src: String = (1..=10).map(|n| format!("item {n}\n")).collect();
+        // `new` is `old` 70% alike; `copy` is `src` before `src` changed.
+        let first = commit(&repo, "first", &[], &[("old", &text), ("src", &src)]);
+        let second = commit(&repo, "second", &[first], &[("new", &changed), ("src", &format!("{src}more\n")), ("copy", &src)]);
+        let (old, new) = (repo.find_commit(first).unwrap().tree().unwrap(), repo.find_commit(second).unwrap().tree().unwrap());
+
+        let settings = DiffSettings::default();
+        assert_eq!(settings.rename_source(&repo, Some(&old), &new, "new").unwrap().as_deref(), Some("old"));
+        let strict = DiffSettings { renames: Some(90), ..settings };
+        assert_eq!(strict.rename_source(&repo, Some(&old), &new, "new").unwrap(), None);
+        // Following a file looks for renames even when diffs do not.
+        let off = DiffSettings { renames: None, ..settings };
+        assert_eq!(off.rename_source(&repo, Some(&old), &new, "new").unwrap().as_deref(), Some("old"));
+        assert!(statuses(&repo, &old, &new, off).contains(&(Delta::Deleted, "old".into())));
+        assert!(statuses(&repo, &old, &new, settings).contains(&(Delta::Renamed, "new".into())));
+        assert!(statuses(&repo, &old, &new, settings).contains(&(Delta::Added, "copy".into())));
+        let copies = DiffSettings { copies: Some(50), ..settings };
+        assert!(statuses(&repo, &old, &new, copies).contains(&(Delta::Copied, "copy".into())));
+    }
+
+    #[test]
+    fn parse_diff_options() {
+        let mut settings = DiffSettings::default();
+        settings.parse_options("-U5 -w --no-renames -C --patience").unwrap();
+        let expected = DiffSettings { context: 5, ignore_whitespace: true, renames: None, copies: Some(50), patience: true, ..DiffSettings::default() };
+        assert_eq!(settings, expected);
+        settings.parse_options("-M --unified=0").unwrap();
+        assert_eq!((settings.renames, settings.context), (Some(50), 0));
+        assert!(settings.parse_options("-U").is_err());
+        assert!(settings.parse_options("--color-words").is_err());
+    }
+}
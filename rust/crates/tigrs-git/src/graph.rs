@@ -0,0 +1,294 @@
+use git2::Oid;
+
+/// Number of distinct lane colors handed out by [`Graph`].
+pub const GRAPH_COLORS: u8 = 7;
+
+const COMMIT: u16 = 1 << 0;
+const MERGE: u16 = 1 << 1;
+const INITIAL: u16 = 1 << 2;
+const UP: u16 = 1 << 3;
+const DOWN: u16 = 1 << 4;
+const LEFT: u16 = 1 << 5;
+const RIGHT: u16 = 1 << 6;
+const JOIN: u16 = 1 << 7;
+
+/// How graph symbols are drawn, mirroring tig's `line-graphics` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphStyle { Ascii, Default, Utf8 }
+
+/// One column of a graph row. Each cell renders to two characters: the
+/// horizontal connector coming in from the left and the lane glyph itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphCell {
+    bits: u16,
+    pub color: u8,
+}
+
+impl GraphCell {
+    fn has(&self, bit: u16) -> bool { self.bits & bit != 0 }
+
+    pub fn is_commit(&self) -> bool { self.has(COMMIT) }
+    pub fn is_empty(&self) -> bool { self.bits == 0 }
+
+    pub fn symbol(&self, style: GraphStyle) -> [char; 2] {
+        let (h, v) = match style {
+            GraphStyle::Ascii => ('-', '|'),
+            GraphStyle::Default | GraphStyle::Utf8 => ('─', '│'),
+        };
+        let pre = if self.has(LEFT) { h } else { ' ' };
+        if self.is_commit() {
+            let glyph = match style {
+                GraphStyle::Ascii => if self.has(INITIAL) { 'I' } else if self.has(MERGE) { 'M' } else { '*' },
+                GraphStyle::Default => if self.has(INITIAL) { 'I' } else if self.has(MERGE) { 'M' } else { 'o' },
+                GraphStyle::Utf8 => if self.has(INITIAL) { '◎' } else if self.has(MERGE) { '●' } else { '∙' },
+            };
+            return [pre, glyph];
+        }
+
+        let (up, down) = (self.has(UP), self.has(DOWN));
+        let (left, right) = (self.has(LEFT), self.has(RIGHT));
+        if !self.has(JOIN) && (left || right) && (up || down) {
+            // A horizontal line crossing over a lane that passes through.
+            return [h, v];
+        }
+        let glyph = match style {
+            GraphStyle::Ascii => match (up, down, left, right) {
+                (true, true, false, false) | (true, false, false, false) | (false, true, false, false) => '|',
+                (true, true, _, _) => '+',
+                (_, _, true, true) if up || down => '+',
+                (true, false, _, _) => '\'',
+                (false, true, _, _) => '.',
+                (false, false, true, _) | (false, false, _, true) => '-',
+                _ => ' ',
+            },
+            GraphStyle::Default | GraphStyle::Utf8 => {
+                let round = style == GraphStyle::Utf8;
+                match (up, down, left, right) {
+                    (true, true, false, false) | (true, false, false, false) | (false, true, false, false) => '│',
+                    (true, true, true, false) => '┤',
+                    (true, true, false, true) => '├',
+                    (true, true, true, true) => '┼',
+                    (true, false, true, false) => if round { '╯' } else { '┘' },
+                    (true, false, false, true) => if round { '╰' } else { '└' },
+                    (true, false, true, true) => '┴',
+                    (false, true, true, false) => if round { '╮' } else { '┐' },
+                    (false, true, false, true) => if round { '╭' } else { '┌' },
+                    (false, true, true, true) => '┬',
+                    (false, false, true, _) | (false, false, _, true) => '─',
+                    _ => ' ',
+                }
+            }
+        };
+        [pre, glyph]
+    }
+}
+
+/// The graph symbols drawn in front of one commit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphRow {
+    pub cells: Vec<GraphCell>,
+}
+
+impl GraphRow {
+    pub fn render(&self, style: GraphStyle) -> String {
+        let mut out = String::with_capacity(self.cells.len() * 2);
+        for cell in &self.cells { out.extend(cell.symbol(style)); }
+        out
+    }
+}
+
+/// Incremental lane layout. Feed commits in display order (children before
+/// parents) and get back one [`GraphRow`] per commit.
+#[derive(Debug, Default)]
+pub struct Graph {
+    lanes: Vec<Option<(Oid, u8)>>,
+    next_color: u8,
+}
+
+impl Graph {
+    pub fn new() -> Self { Self::default() }
+
+    fn new_color(&mut self) -> u8 {
+        let color = self.next_color;
+        self.next_color = (self.next_color + 1) % GRAPH_COLORS;
+        color
+    }
+
+    fn free_lane(&self, taken: &[usize]) -> Option<usize> {
+        self.lanes.iter().enumerate().position(|(i, l)| l.is_none() && !taken.contains(&i))
+    }
+
+    /// Lay out `id` with the `parents` that are shown, drawing it as an
+    /// initial commit when it is a `root` one. A commit whose parents are
+    /// all hidden, such as the oldest one of `a..b`, is not.
+    pub fn add_commit(&mut self, id: Oid, parents: &[Oid], root: bool) -> GraphRow {
+        let existing = self.lanes.iter().position(|l| matches!(l, Some((oid, _)) if *oid == id));
+        let (col, color) = match existing {
+            Some(col) => (col, self.lanes[col].map(|(_, c)| c).unwrap_or(0)),
+            None => {
+                let color = self.new_color();
+                let col = match self.free_lane(&[]) {
+                    Some(i) => i,
+                    None => { self.lanes.push(None); self.lanes.len() - 1 }
+                };
+                (col, color)
+            }
+        };
+
+        let mut cells: Vec<GraphCell> = self.lanes.iter()
+            .map(|l| match l {
+                Some((_, color)) => GraphCell { bits: UP, color: *color },
+                None => GraphCell::default(),
+            })
+            .collect();
+        cells[col].bits |= COMMIT;
+        cells[col].color = color;
+        if parents.len() > 1 { cells[col].bits |= MERGE; }
+        if root { cells[col].bits |= INITIAL; }
+
+        // Other lanes waiting for this commit end here and join its column.
+        let mut segments = Vec::new();
+        let mut freed = Vec::new();
+        for (i, lane) in self.lanes.iter_mut().enumerate() {
+            if i != col && matches!(lane, Some((oid, _)) if *oid == id) {
+                *lane = None;
+                cells[i].bits |= JOIN;
+                segments.push(i);
+                freed.push(i);
+            }
+        }
+
+        self.lanes[col] = parents.first().map(|p| (*p, color));
+        for parent in parents.iter().skip(1) {
+            if *parent == parents[0] { continue; }
+            let target = self.lanes.iter().enumerate()
+                .position(|(i, l)| i != col && matches!(l, Some((oid, _)) if oid == parent));
+            let k = match target {
+                Some(k) => k,
+                None => {
+                    let mut taken = freed.clone();
+                    taken.push(col);
+                    let k = match self.free_lane(&taken) {
+                        Some(k) => k,
+                        None => { self.lanes.push(None); self.lanes.len() - 1 }
+                    };
+                    let color = self.new_color();
+                    self.lanes[k] = Some((*parent, color));
+                    k
+                }
+            };
+            if k >= cells.len() { cells.resize(k + 1, GraphCell::default()); }
+            cells[k].bits |= JOIN;
+            segments.push(k);
+        }
+
+        for (i, lane) in self.lanes.iter().enumerate() {
+            if let Some((_, color)) = lane {
+                cells[i].bits |= DOWN;
+                if cells[i].bits & (UP | COMMIT) == 0 { cells[i].color = *color; }
+            }
+        }
+        for k in segments {
+            let (lo, hi) = (col.min(k), col.max(k));
+            for cell in &mut cells[lo + 1..=hi] { cell.bits |= LEFT; }
+            for cell in &mut cells[lo..hi] { cell.bits |= RIGHT; }
+        }
+
+        while matches!(self.lanes.last(), Some(None)) { self.lanes.pop(); }
+        while cells.last().is_some_and(GraphCell::is_empty) { cells.pop(); }
+        GraphRow { cells }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        revspec::SpecWalk,
+        test_repo::{branch, commit, test_repo},
+        RevSpec,
+    };
+
+    fn id(n: u8) -> Oid { Oid::from_bytes(&[n; 20]).unwrap() }
+
+    /// Lay out `(commit, parents)` in order and render each row in ASCII.
+    fn layout(commits: &[(u8, &[u8])]) -> Vec<String> {
+        let mut graph = Graph::new();
+        commits.iter()
+            .map(|(c, parents)| {
+                let parents: Vec<Oid> = parents.iter().map(|p| id(*p)).collect();
+                graph.add_commit(id(*c), &parents, parents.is_empty()).render(GraphStyle::Ascii)
+            })
+            .collect()
+    }
+
+    /// Rows for the commits `args` show in a repository.
+    fn walk_layout(repo: &git2::Repository, args: &[&str]) -> Vec<String> {
+        let spec = RevSpec::parse(repo, args).unwrap();
+        let mut graph = Graph::new();
+        SpecWalk::new(repo, &spec).unwrap()
+            .filter_map(|entry| match entry.unwrap() {
+                (id, Some(parents)) => {
+                    let root = repo.find_commit(id).unwrap().parent_count() == 0;
+                    Some(graph.add_commit(id, &parents, root).render(GraphStyle::Ascii))
+                }
+                (_, None) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn linear() {
+        assert_eq!(layout(&[(3, &[2]), (2, &[1]), (1, &[])]), [" *", " *", " I"]);
+    }
+
+    #[test]
+    fn fork() {
+        // 4 and 3 both branch off 2.
+        let rows = layout(&[(4, &[2]), (3, &[2]), (2, &[1]), (1, &[])]);
+        assert_eq!(rows, [" *", " | *", " *-'", " I"]);
+    }
+
+    #[test]
+    fn merge() {
+        let rows = layout(&[(4, &[3, 2]), (3, &[1]), (2, &[1]), (1, &[])]);
+        assert_eq!(rows, [" M-.", " * |", " | *", " I-'"]);
+    }
+
+    #[test]
+    fn octopus() {
+        let rows = layout(&[(5, &[4, 3, 2]), (4, &[1]), (3, &[1]), (2, &[1]), (1, &[])]);
+        assert_eq!(rows, [" M-+-.", " * | |", " | * |", " | | *", " I-+-'"]);
+    }
+
+    #[test]
+    fn path_limited_parents_are_rewritten() {
+        // Only `a` and `c` touch `file`; the others would otherwise keep
+        // lanes open for commits that are never shown.
+        let repo = test_repo("graph-paths");
+        let a = commit(&repo, "a", &[], &[("file", "1")]);
+        let b = commit(&repo, "b", &[a], &[("file", "1"), ("other", "1")]);
+        let side = commit(&repo, "side", &[b], &[("file", "1"), ("other", "2")]);
+        let c = commit(&repo, "c", &[b], &[("file", "2"), ("other", "1")]);
+        let merge = commit(&repo, "merge", &[c, side], &[("file", "2"), ("other", "2")]);
+        let d = commit(&repo, "d", &[merge], &[("file", "2"), ("other", "3")]);
+        branch(&repo, "main", d);
+        assert_eq!(walk_layout(&repo, &["main", "--", "file"]), [" *", " I"]);
+        // `b` is the oldest commit touching `other` but not a root commit.
+        assert_eq!(walk_layout(&repo, &["main", "--", "other"]), [" *", " *", " *"]);
+    }
+
+    #[test]
+    fn hidden_parents_close_their_lane() {
+        let repo = test_repo("graph-range");
+        let base = commit(&repo, "base", &[], &[]);
+        let side = commit(&repo, "side", &[base], &[]);
+        let main = commit(&repo, "main", &[base], &[]);
+        let merge = commit(&repo, "merge", &[main, side], &[]);
+        let top = commit(&repo, "top", &[merge], &[]);
+        branch(&repo, "top", top);
+        branch(&repo, "side", side);
+        // `main` is shown without parents, but is no root commit.
+        assert_eq!(walk_layout(&repo, &["side..top"]), [" *", " *", " *"]);
+    }
+}
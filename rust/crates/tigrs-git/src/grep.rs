@@ -0,0 +1,213 @@
+use anyhow::{anyhow, bail, Result};
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use regex::{Regex, RegexBuilder};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+/// What to search for and where, parsed from `git grep` style arguments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GrepQuery {
+    pub pattern: String,
+    pub ignore_case: bool,
+    /// Match `pattern` literally instead of as a regular expression.
+    pub fixed: bool,
+    /// Commit to search; `None` searches tracked files in the work tree.
+    pub rev: Option<Oid>,
+}
+
+impl GrepQuery {
+    /// Parse `[-i] [-F|-E] <pattern> [<rev>]`.
+    pub fn parse<S: AsRef<str>>(repo: &Repository, args: &[S]) -> Result<Self> {
+        let mut query = GrepQuery::default();
+        let mut words = Vec::new();
+        for arg in args.iter().map(AsRef::as_ref) {
+            match arg {
+                "-i" | "--ignore-case" => query.ignore_case = true,
+                "-F" | "--fixed-strings" => query.fixed = true,
+                "-E" | "-G" | "--extended-regexp" | "--basic-regexp" => query.fixed = false,
+                _ => words.push(arg),
+            }
+        }
+        match words.as_slice() {
+            [pattern] => query.pattern = pattern.to_string(),
+            [pattern, rev] => {
+                query.pattern = pattern.to_string();
+                query.rev = Some(repo.revparse_single(rev)?.peel_to_commit()?.id());
+            }
+            _ => bail!("usage: grep [-i] [-F] <pattern> [<rev>]"),
+        }
+        Ok(query)
+    }
+
+    fn regex(&self) -> Result<Regex> {
+        let pattern = if self.fixed { regex::escape(&self.pattern) } else { self.pattern.clone() };
+        RegexBuilder::new(&pattern).case_insensitive(self.ignore_case).build().map_err(|e| {
+            // Syntax errors span several lines; keep the one saying what is wrong.
+            let msg = e.to_string();
+            let reason = msg.lines().last().unwrap_or("").trim_start_matches("error: ").to_string();
+            anyhow!("Invalid pattern {}: {reason}", self.pattern)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrepMatch {
+    pub path: String,
+    /// Line number, starting at 1.
+    pub lineno: usize,
+    pub text: String,
+}
+
+enum GrepEvent {
+    Matches(Vec<GrepMatch>),
+    Error(String),
+}
+
+/// Searches files on a background thread and streams matches file by
+/// file, like tig reading `git grep` output.
+pub struct GrepSearch {
+    rx: Receiver<GrepEvent>,
+    stop: Arc<AtomicBool>,
+    loading: bool,
+    error: Option<String>,
+}
+
+fn grep_content(regex: &Regex, path: &str, content: &[u8], tx: &Sender<GrepEvent>) -> bool {
+    // Binary files are skipped, as with `git grep -I`.
+    if content.contains(&0) { return true; }
+    let text = String::from_utf8_lossy(content);
+    let matches: Vec<GrepMatch> = text.lines().enumerate()
+        .filter(|(_, line)| regex.is_match(line))
+        .map(|(i, line)| GrepMatch { path: path.to_string(), lineno: i + 1, text: line.to_string() })
+        .collect();
+    matches.is_empty() || tx.send(GrepEvent::Matches(matches)).is_ok()
+}
+
+impl GrepSearch {
+    /// Start searching; fails right away if the pattern does not compile.
+    pub fn spawn(git_dir: PathBuf, query: GrepQuery) -> Result<Self> {
+        let regex = query.regex()?;
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        thread::spawn(move || {
+            let res = (|| -> Result<()> {
+                let repo = Repository::open(&git_dir)?;
+                match query.rev {
+                    Some(rev) => {
+                        let tree = repo.find_commit(rev)?.tree()?;
+                        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+                            if flag.load(Ordering::Relaxed) { return TreeWalkResult::Abort; }
+                            if entry.kind() != Some(ObjectType::Blob) { return TreeWalkResult::Ok; }
+                            let path = format!("{dir}{}", entry.name().unwrap_or(""));
+                            let Ok(blob) = repo.find_blob(entry.id()) else { return TreeWalkResult::Ok };
+                            if grep_content(&regex, &path, blob.content(), &tx) { TreeWalkResult::Ok } else { TreeWalkResult::Abort }
+                        })?;
+                    }
+                    None => {
+                        let Some(workdir) = repo.workdir() else { bail!("Searching the work tree requires a work tree") };
+                        let index = repo.index()?;
+                        let mut last: Option<Vec<u8>> = None;
+                        for entry in index.iter() {
+                            if flag.load(Ordering::Relaxed) { break; }
+                            // A conflicted file has an entry for each stage,
+                            // one after the other; it is searched once.
+                            if last.as_ref() == Some(&entry.path) { continue; }
+                            let path = String::from_utf8_lossy(&entry.path).into_owned();
+                            last = Some(entry.path);
+                            // Removed files are not searched, as in `git grep`.
+                            let Ok(content) = std::fs::read(workdir.join(&path)) else { continue };
+                            if !grep_content(&regex, &path, &content, &tx) { break; }
+                        }
+                    }
+                }
+                Ok(())
+            })();
+            if let Err(e) = res { let _ = tx.send(GrepEvent::Error(e.to_string())); }
+        });
+        Ok(Self { rx, stop, loading: true, error: None })
+    }
+
+    /// Matches found since the last call.
+    pub fn poll(&mut self) -> Vec<GrepMatch> {
+        let mut out = Vec::new();
+        while self.loading {
+            match self.rx.try_recv() {
+                Ok(GrepEvent::Matches(mut matches)) => out.append(&mut matches),
+                Ok(GrepEvent::Error(e)) => self.error = Some(e),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.loading = false,
+            }
+        }
+        out
+    }
+
+    pub fn is_loading(&self) -> bool { self.loading }
+
+    pub fn error(&self) -> Option<&str> { self.error.as_deref() }
+}
+
+impl Drop for GrepSearch {
+    fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{branch, checkout, commit, poll_all, test_repo};
+
+    fn search(repo: &Repository, args: &[&str]) -> Vec<(String, usize, String)> {
+        let mut search = GrepSearch::spawn(repo.path().to_path_buf(), GrepQuery::parse(repo, args).unwrap()).unwrap();
+        let mut found: Vec<_> = poll_all(&mut search, GrepSearch::is_loading, GrepSearch::poll)
+            .into_iter().map(|m| (m.path, m.lineno, m.text)).collect();
+        assert_eq!(search.error(), None);
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn grep_a_revision() {
+        let repo = test_repo("grep-rev");
+        let first = commit(&repo, "first", &[], &[("a", "foo bar\nbaz\nfoo\n"), ("b", "Foo.x\n"), ("bin", "foo\0")]);
+        let second = commit(&repo, "second", &[first], &[("a", "gone\n"), ("b", "Foo.x\n")]);
+        branch(&repo, "main", second);
+
+        let m = |path: &str, lineno, text: &str| (path.to_string(), lineno, text.to_string());
+        assert_eq!(search(&repo, &["foo", "main~"]), [m("a", 1, "foo bar"), m("a", 3, "foo")]);
+        assert_eq!(search(&repo, &["-i", "foo", "main~"]), [m("a", 1, "foo bar"), m("a", 3, "foo"), m("b", 1, "Foo.x")]);
+        assert_eq!(search(&repo, &["-i", "foo", "main"]), [m("b", 1, "Foo.x")]);
+        assert_eq!(search(&repo, &["-F", "o.x", "main"]), [m("b", 1, "Foo.x")]);
+        assert_eq!(search(&repo, &["o.b", "main~"]), [m("a", 1, "foo bar")]);
+        assert!(search(&repo, &["-F", "o.b", "main~"]).is_empty());
+
+        assert!(GrepQuery::parse(&repo, &["a", "b", "c"]).is_err());
+        let bad = GrepQuery::parse(&repo, &["(", "main"]).unwrap();
+        assert!(GrepSearch::spawn(repo.path().to_path_buf(), bad).is_err());
+    }
+
+    #[test]
+    fn grep_the_work_tree() {
+        let repo = test_repo("grep-workdir");
+        let base = commit(&repo, "base", &[], &[("file", "a\n"), ("gone", "foo\n"), ("kept", "foo x\n")]);
+        let ours = commit(&repo, "ours", &[base], &[("file", "foo ours\n"), ("gone", "foo\n"), ("kept", "foo x\n")]);
+        let theirs = commit(&repo, "theirs", &[base], &[("file", "foo theirs\n"), ("gone", "foo\n"), ("kept", "foo x\n")]);
+        checkout(&repo, ours);
+        repo.merge(&[&repo.find_annotated_commit(theirs).unwrap()], None, None).unwrap();
+        assert!(repo.index().unwrap().has_conflicts());
+        let workdir = repo.workdir().unwrap();
+        std::fs::remove_file(workdir.join("gone")).unwrap();
+        std::fs::write(workdir.join("untracked"), "foo\n").unwrap();
+
+        // The conflicted file is searched once, with its conflict markers;
+        // deleted and untracked files are not searched.
+        let m = |path: &str, lineno, text: &str| (path.to_string(), lineno, text.to_string());
+        assert_eq!(search(&repo, &["foo"]), [m("file", 2, "foo ours"), m("file", 4, "foo theirs"), m("kept", 1, "foo x")]);
+    }
+}
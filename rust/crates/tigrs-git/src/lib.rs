@@ -1,14 +1,96 @@
 use anyhow::Result;
-use git2::{Oid, Repository, Sort};
+use git2::{Oid, Repository};
+use std::{collections::HashSet, sync::Arc};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
+pub mod blame;
+pub mod combined;
+pub mod diff;
+pub mod graph;
+pub mod grep;
+pub mod loader;
+pub mod log;
+pub mod refdb;
+pub mod reflog;
+pub mod refs;
+pub mod revspec;
+pub mod stage;
+pub mod stash;
+pub mod status;
+pub mod tree;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_repo;
+
+pub use blame::{blame_file, blame_parent, uncommitted_diff_text, BlameCommit, BlameLine, FileBlame};
+pub use combined::combined_diff_text;
+pub use diff::DiffSettings;
+pub use grep::{GrepMatch, GrepQuery, GrepSearch};
+pub use loader::CommitLoader;
+pub use log::{commit_log_text, LogCommit, LogLoader, LogOptions};
+pub use refdb::{RefDb, RefFormat, RefInfo, RefKind};
+pub use reflog::{reflog_entries, ReflogEntry};
+pub use refs::{checkout_commit, checkout_ref, create_branch, delete_branch, ref_entries, reset_hard, RefEntry};
+pub use revspec::RevSpec;
+pub use stage::{DiffLine, FileDiff, Hunk, StagePatch, StageSelection};
+pub use stash::{stash_apply, stash_diff_text, stash_drop, stash_entries, stash_pop, StashEntry};
+pub use status::{
+    load_status, revert_entry, status_diff_text, update_entries, RepoStatus, StatusEntry, StatusOptions,
+    StatusSection,
+};
+pub use tree::{tree_entries, TreeCommit, TreeEntry, TreeHistoryLoader};
+
+/// Compact commit record for the main view. Authors are shared between
+/// commits and ids are kept binary; format with `short_id`/`time_str`.
 #[derive(Debug, Clone)]
 pub struct CommitInfo {
-    pub id: String,        // short id (7 chars)
-    pub full_id: String,   // full 40-char id
-    pub summary: String,
-    pub author: String,
-    pub time: String,
+    pub id: Oid,
+    /// Shown parents, with hidden ones rewritten to their nearest shown
+    /// ancestors.
+    pub parents: Box<[Oid]>,
+    /// Whether the commit has no parents at all, rather than none shown.
+    pub root: bool,
+    pub summary: Box<str>,
+    pub author: Arc<str>,
+    pub time: i64,
+}
+
+impl CommitInfo {
+    pub fn short_id(&self) -> String { short_id(&self.id) }
+    pub fn time_str(&self) -> String { to_rfc3339(self.time) }
+}
+
+/// Name and email of an author or committer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident {
+    pub name: Arc<str>,
+    pub email: Arc<str>,
+}
+
+impl Ident {
+    /// The first letter of each word of the name, for abbreviated author columns.
+    pub fn initials(&self) -> String {
+        self.name.split(|c: char| c.is_whitespace() || c.is_ascii_punctuation()).filter_map(|word| word.chars().next()).collect()
+    }
+
+    /// The email address up to the `@`.
+    pub fn email_user(&self) -> &str { self.email.split('@').next().unwrap_or_default() }
+}
+
+/// Deduplicates author strings across commits.
+#[derive(Debug, Default)]
+pub(crate) struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    pub(crate) fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(existing) = self.0.get(s) { return existing.clone(); }
+        let value: Arc<str> = Arc::from(s);
+        self.0.insert(value.clone());
+        value
+    }
+
+    pub(crate) fn ident(&mut self, sig: &git2::Signature<'_>) -> Ident {
+        Ident { name: self.intern(sig.name().unwrap_or("<unknown>")), email: self.intern(sig.email().unwrap_or("")) }
+    }
 }
 
 pub fn discover_repo(start: Option<&str>) -> Result<Repository> {
@@ -20,80 +102,195 @@ pub fn discover_repo(start: Option<&str>) -> Result<Repository> {
 }
 
 pub fn recent_commits(repo: &Repository, limit: usize) -> Result<Vec<CommitInfo>> {
-    let mut walk = repo.revwalk()?;
-    walk.push_head()?;
-    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revision_commits(repo, &RevSpec::default(), limit)
+}
 
+pub fn revision_commits(repo: &Repository, spec: &RevSpec, limit: usize) -> Result<Vec<CommitInfo>> {
+    let mut authors = Interner::default();
     let mut out = Vec::new();
-    for (i, oid) in walk.enumerate() {
-        if i >= limit { break; }
-        let oid = oid?;
-        if let Some(info) = commit_info(repo, oid)? { out.push(info); }
+    for entry in revspec::SpecWalk::new(repo, spec)? {
+        if out.len() >= limit { break; }
+        let (oid, Some(parents)) = entry? else { continue };
+        if let Some(mut info) = commit_info(repo, oid, &mut authors)? {
+            info.parents = parents.into();
+            out.push(info);
+        }
     }
     Ok(out)
 }
 
-fn commit_info(repo: &Repository, oid: Oid) -> Result<Option<CommitInfo>> {
+pub(crate) fn commit_info(repo: &Repository, oid: Oid, authors: &mut Interner) -> Result<Option<CommitInfo>> {
     let obj = repo.find_object(oid, None)?;
     let commit = match obj.peel_to_commit() {
         Ok(c) => c,
         Err(_) => return Ok(None),
     };
-    let full_id = commit.id().to_string();
-    let id = short_id(&commit.id())?;
-    let summary = commit.summary().unwrap_or("").to_string();
+    let summary = commit.summary().unwrap_or("").into();
     let author_sig = commit.author();
     let author = match (author_sig.name(), author_sig.email()) {
-        (Some(n), Some(e)) => format!("{} <{}>", n, e),
-        (Some(n), None) => n.to_string(),
-        _ => String::from("<unknown>"),
+        (Some(n), Some(e)) => authors.intern(&format!("{} <{}>", n, e)),
+        (Some(n), None) => authors.intern(n),
+        _ => authors.intern("<unknown>"),
     };
-    let time = to_rfc3339(commit.time().seconds());
+    let time = commit.time().seconds();
+    let parents: Box<[Oid]> = commit.parent_ids().collect();
+    let root = parents.is_empty();
 
-    Ok(Some(CommitInfo { id, full_id, summary, author, time }))
+    Ok(Some(CommitInfo { id: commit.id(), parents, root, summary, author, time }))
 }
 
-fn short_id(oid: &Oid) -> Result<String> {
-    let s = oid.to_string();
-    Ok(s.chars().take(7).collect())
+pub fn short_id(oid: &Oid) -> String {
+    oid.to_string().chars().take(7).collect()
 }
 
-fn to_rfc3339(secs: i64) -> String {
-    let dt = OffsetDateTime::from_unix_timestamp(secs).unwrap_or_else(|_| OffsetDateTime::UNIX_EPOCH);
+pub fn to_rfc3339(secs: i64) -> String {
+    let dt = OffsetDateTime::from_unix_timestamp(secs).unwrap_or(OffsetDateTime::UNIX_EPOCH);
     dt.format(&Rfc3339).unwrap_or_else(|_| String::from("1970-01-01T00:00:00Z"))
 }
 
-pub fn commit_diff_text(repo: &Repository, oid: Oid) -> Result<String> {
+/// `YYYY-MM-DD` in UTC, tig's default `date` column format.
+pub fn date_str(secs: i64) -> String {
+    let dt = OffsetDateTime::from_unix_timestamp(secs).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!("{:04}-{:02}-{:02}", dt.year(), u8::from(dt.month()), dt.day())
+}
+
+/// `secs` formatted by the strftime(3) conversions `%Y %y %m %d %e %H %M
+/// %S %j %a %A %b %B %z %Z %s %%`, in UTC; others are kept as written.
+pub fn format_date(secs: i64, format: &str) -> String {
+    const DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+    let dt = OffsetDateTime::from_unix_timestamp(secs).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    let month = dt.month().to_string();
+    let day = DAYS[dt.weekday().number_days_from_monday() as usize];
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' { out.push(c); continue; }
+        match chars.next() {
+            Some('Y') => out.push_str(&dt.year().to_string()),
+            Some('y') => out.push_str(&format!("{:02}", dt.year().rem_euclid(100))),
+            Some('m') => out.push_str(&format!("{:02}", u8::from(dt.month()))),
+            Some('d') => out.push_str(&format!("{:02}", dt.day())),
+            Some('e') => out.push_str(&format!("{:2}", dt.day())),
+            Some('H') => out.push_str(&format!("{:02}", dt.hour())),
+            Some('M') => out.push_str(&format!("{:02}", dt.minute())),
+            Some('S') => out.push_str(&format!("{:02}", dt.second())),
+            Some('j') => out.push_str(&format!("{:03}", dt.ordinal())),
+            Some('a') => out.push_str(&day[..3]),
+            Some('A') => out.push_str(day),
+            Some('b') => out.push_str(&month[..3]),
+            Some('B') => out.push_str(&month),
+            Some('z') => out.push_str("+0000"),
+            Some('Z') => out.push_str("UTC"),
+            Some('s') => out.push_str(&secs.to_string()),
+            Some('%') => out.push('%'),
+            Some(other) => { out.push('%'); out.push(other); }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// How long before `now` `secs` was, as tig's relative dates: "3 days ago",
+/// or "3D" when `compact`.
+pub fn relative_date(secs: i64, now: i64, compact: bool) -> String {
+    const UNITS: [(&str, char, i64, i64); 7] = [
+        ("second", 's', 1, 60 * 2),
+        ("minute", 'm', 60, 60 * 60 * 2),
+        ("hour", 'h', 60 * 60, 60 * 60 * 24 * 2),
+        ("day", 'D', 60 * 60 * 24, 60 * 60 * 24 * 7 * 2),
+        ("week", 'W', 60 * 60 * 24 * 7, 60 * 60 * 24 * 7 * 5),
+        ("month", 'M', 60 * 60 * 24 * 30, 60 * 60 * 24 * 365),
+        ("year", 'Y', 60 * 60 * 24 * 365, i64::MAX),
+    ];
+    let seconds = (now - secs).abs();
+    let (name, symbol, unit, _) = UNITS.iter().find(|(.., interval)| seconds < *interval).unwrap_or(&UNITS[6]);
+    let n = seconds / unit;
+    match (compact, now >= secs) {
+        (true, past) => format!("{}{n}{symbol}", if past { "" } else { "-" }),
+        (false, past) => format!("{n} {name}{} {}", if n > 1 { "s" } else { "" }, if past { "ago" } else { "ahead" }),
+    }
+}
+
+/// Which changes of a commit the diff view shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiffParent {
+    /// The combined diff of a merge; other commits diff against their parent.
+    #[default]
+    Combined,
+    /// Against one parent, counting from 0.
+    Parent(usize),
+}
+
+impl DiffParent {
+    /// The combined diff, then each of `parents` in turn.
+    pub fn next(self, parents: usize) -> Self {
+        match self {
+            DiffParent::Combined => DiffParent::Parent(0),
+            DiffParent::Parent(i) if i + 1 < parents => DiffParent::Parent(i + 1),
+            DiffParent::Parent(_) => DiffParent::Combined,
+        }
+    }
+}
+
+pub fn commit_diff_text(repo: &Repository, oid: Oid, against: DiffParent, settings: &DiffSettings) -> Result<String> {
     let commit = repo.find_commit(oid)?;
-    let tree = commit.tree()?;
-    let parent_tree = if commit.parent_count() > 0 {
-        Some(commit.parent(0)?.tree()?)
+    let parents = commit.parent_count();
+
+    let mut out = String::new();
+    // Header
+    let kind = match parents {
+        0 => " (root commit)",
+        1 => "",
+        _ => " (merge)",
+    };
+    out.push_str(&format!("commit {}{kind}\n", commit.id()));
+    if parents > 1 {
+        let ids: Vec<String> = commit.parent_ids().map(|id| short_id(&id)).collect();
+        out.push_str(&format!("Merge: {}\n", ids.join(" ")));
+    }
+    if let Some(a) = commit.author().name() {
+        out.push_str(&format!("Author: {}\n", a));
+    }
+    out.push_str(&format!("Date:   {}\n\n", to_rfc3339(commit.time().seconds())));
+    if let Some(msg) = commit.message() { out.push_str(msg); out.push('\n'); }
+    out.push('\n');
+
+    let parent = match against {
+        DiffParent::Combined if parents > 1 => {
+            out.push_str(&format!("Combined diff against {parents} parents\n\n"));
+            out.push_str(&combined_diff_text(repo, &commit, &[], settings)?);
+            return Ok(out);
+        }
+        DiffParent::Combined => 0,
+        DiffParent::Parent(i) => i,
+    };
+    let parent_tree = if parents > 0 {
+        let p = commit.parent(parent)?;
+        if parents > 1 {
+            out.push_str(&format!("Diff against parent {} of {parents}: {} {}\n\n", parent + 1, short_id(&p.id()), p.summary().unwrap_or("")));
+        }
+        Some(p.tree()?)
     } else {
         None
     };
+    let diff = settings.diff_trees(repo, parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut settings.options()))?;
+    out.push_str(&diff_to_text(&diff)?);
+    Ok(out)
+}
 
-    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+/// Render a diff as unified patch text.
+pub fn diff_to_text(diff: &git2::Diff<'_>) -> Result<String> {
     let mut patch = String::new();
     diff.print(git2::DiffFormat::Patch, |_, _, line| {
         let origin = line.origin(); // returns a char
         if let Ok(text) = std::str::from_utf8(line.content()) {
-            patch.push(origin);
+            // File and hunk headers ('F', 'H') carry their own text.
+            if matches!(origin, '+' | '-' | ' ') { patch.push(origin); }
             patch.push_str(text);
         }
         true
     })?;
-
-    let mut out = String::new();
-    // Header
-    out.push_str(&format!("commit {}\n", commit.id()));
-    if let Some(a) = commit.author().name() {
-        out.push_str(&format!("Author: {}\n", a));
-    }
-    out.push_str(&format!("Date:   {}\n\n", to_rfc3339(commit.time().seconds())));
-    if let Some(msg) = commit.message() { out.push_str(msg); out.push('\n'); }
-    out.push('\n');
-    out.push_str(&patch);
-    Ok(out)
+    Ok(patch)
 }
 
 pub fn oid_from_str(repo: &Repository, s: &str) -> Result<Oid> {
@@ -101,3 +298,44 @@ pub fn oid_from_str(repo: &Repository, s: &str) -> Result<Oid> {
     let obj = repo.revparse_single(s)?;
     Ok(obj.id())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{commit, test_repo};
+
+    #[test]
+    fn format_dates() {
+        // 2009-02-13 23:31:30 UTC, a Friday.
+        assert_eq!(format_date(1_234_567_890, "%Y-%m-%d %H:%M:%S %z"), "2009-02-13 23:31:30 +0000");
+        assert_eq!(format_date(1_234_567_890, "%a %b %e %y %j %%%q"), "Fri Feb 13 09 044 %%q");
+    }
+
+    #[test]
+    fn relative_dates() {
+        let now = 1_000_000_000;
+        assert_eq!(relative_date(now - 30, now, false), "30 seconds ago");
+        assert_eq!(relative_date(now - 3 * 3600, now, false), "3 hours ago");
+        assert_eq!(relative_date(now - 86_400, now, false), "24 hours ago");
+        assert_eq!(relative_date(now - 3 * 86_400, now, true), "3D");
+        assert_eq!(relative_date(now + 120, now, true), "-2m");
+        assert_eq!(relative_date(now - 2 * 365 * 86_400, now, false), "2 years ago");
+    }
+
+    #[test]
+    fn ident_abbreviations() {
+        let ident = Ident { name: "Jean-Luc Picard".into(), email: "jl@enterprise.example".into() };
+        assert_eq!(ident.initials(), "JLP");
+        assert_eq!(ident.email_user(), "jl");
+    }
+
+    #[test]
+    fn commit_diff_headers_have_no_origin_markers() {
+        let repo = test_repo("commit-diff");
+        let first = commit(&repo, "first", &[], &[("file", "a\n")]);
+        let second = commit(&repo, "second", &[first], &[("file", "b\n")]);
+        let text = commit_diff_text(&repo, second, DiffParent::default(), &DiffSettings::default()).unwrap();
+        let patch: Vec<&str> = text.lines().skip_while(|l| !l.starts_with("diff --git")).collect();
+        assert_eq!(patch, ["diff --git a/file b/file", "index 7898192..6178079 100644", "--- a/file", "+++ b/file", "@@ -1 +1 @@", "-a", "+b"]);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{commit_info, revspec::SpecWalk, CommitInfo, Interner, RevSpec};
+use anyhow::Result;
+use git2::Repository;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const BATCH_SIZE: usize = 1000;
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+enum LoadEvent {
+    Batch(Vec<CommitInfo>),
+    Error(String),
+}
+
+/// Walks commits on a background thread and hands them over in batches,
+/// so the UI can draw while a large history is still being read.
+pub struct CommitLoader {
+    rx: Receiver<LoadEvent>,
+    stop: Arc<AtomicBool>,
+    loading: bool,
+    error: Option<String>,
+}
+
+impl CommitLoader {
+    /// Start walking `spec` in the repository at `git_dir`, stopping after
+    /// `limit` commits if given.
+    pub fn spawn(git_dir: PathBuf, spec: RevSpec, limit: Option<usize>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        thread::spawn(move || {
+            let send = |batch: &mut Vec<CommitInfo>| tx.send(LoadEvent::Batch(std::mem::take(batch))).is_ok();
+            let res = (|| -> Result<()> {
+                let repo = Repository::open(&git_dir)?;
+                let walk = SpecWalk::new(&repo, &spec)?;
+
+                let mut authors = Interner::default();
+                let mut batch = Vec::new();
+                let mut last_sent = Instant::now();
+                let mut count = 0usize;
+                for entry in walk {
+                    if flag.load(Ordering::Relaxed) || limit.is_some_and(|n| count >= n) { break; }
+                    let (oid, Some(parents)) = entry? else { continue };
+                    if let Some(mut info) = commit_info(&repo, oid, &mut authors)? {
+                        info.parents = parents.into();
+                        batch.push(info);
+                        count += 1;
+                    }
+                    if batch.len() >= BATCH_SIZE || last_sent.elapsed() >= BATCH_INTERVAL {
+                        if !send(&mut batch) { return Ok(()); }
+                        last_sent = Instant::now();
+                    }
+                }
+                send(&mut batch);
+                Ok(())
+            })();
+            if let Err(e) = res { let _ = tx.send(LoadEvent::Error(e.to_string())); }
+        });
+        Self { rx, stop, loading: true, error: None }
+    }
+
+    /// Collect commits received since the last call.
+    pub fn poll(&mut self) -> Vec<CommitInfo> {
+        let mut out = Vec::new();
+        while self.loading {
+            match self.rx.try_recv() {
+                Ok(LoadEvent::Batch(mut batch)) => out.append(&mut batch),
+                Ok(LoadEvent::Error(e)) => self.error = Some(e),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.loading = false,
+            }
+        }
+        out
+    }
+
+    /// Ask the loader to stop; already loaded commits are kept.
+    pub fn stop(&self) { self.stop.store(true, Ordering::Relaxed); }
+
+    pub fn is_loading(&self) -> bool { self.loading }
+
+    pub fn error(&self) -> Option<&str> { self.error.as_deref() }
+}
+
+impl Drop for CommitLoader {
+    fn drop(&mut self) { self.stop(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{branch, commit, poll_all, test_repo};
+
+    fn load(loader: &mut CommitLoader) -> Vec<CommitInfo> { poll_all(loader, CommitLoader::is_loading, CommitLoader::poll) }
+
+    #[test]
+    fn streams_newest_first() {
+        let repo = test_repo("loader");
+        let first = commit(&repo, "first", &[], &[]);
+        let second = commit(&repo, "second", &[first], &[]);
+        let third = commit(&repo, "third", &[second], &[]);
+        branch(&repo, "main", third);
+        let spec = RevSpec::parse(&repo, &["main"]).unwrap();
+        let commits = load(&mut CommitLoader::spawn(repo.path().to_path_buf(), spec.clone(), None));
+        let summaries: Vec<&str> = commits.iter().map(|c| &*c.summary).collect();
+        assert_eq!(summaries, ["third", "second", "first"]);
+        assert_eq!(&*commits[0].parents, [second]);
+
+        let limited = load(&mut CommitLoader::spawn(repo.path().to_path_buf(), spec, Some(2)));
+        assert_eq!(limited.len(), 2);
+    }
+}
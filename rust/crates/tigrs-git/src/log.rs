@@ -0,0 +1,294 @@
+use crate::{combined_diff_text, diff_to_text, to_rfc3339, DiffSettings, RevSpec};
+use anyhow::{bail, Result};
+use git2::{Commit, Diff, DiffFormat, DiffStatsFormat, Oid, Repository, Sort};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+/// What the log view shows below each commit message, parsed from the
+/// `log-options` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogOptions {
+    pub stat: bool,
+    pub name_status: bool,
+    pub patch: bool,
+    /// Show changes of merge commits; `git log` leaves them out otherwise.
+    pub cc: bool,
+    /// Keep following a single file across renames.
+    pub follow: bool,
+}
+
+impl LogOptions {
+    /// Parse `git log` style options such as `--cc --stat`.
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut opts = LogOptions::default();
+        let mut no_patch = false;
+        for arg in args.split_whitespace() {
+            match arg {
+                "--stat" => opts.stat = true,
+                "--name-status" => opts.name_status = true,
+                "-p" | "-u" | "--patch" => opts.patch = true,
+                "-s" | "--no-patch" => no_patch = true,
+                "--cc" => opts.cc = true,
+                "--follow" => opts.follow = true,
+                _ => bail!("Unsupported log option: {arg}"),
+            }
+        }
+        // As in git, `--cc` alone implies a patch.
+        if opts.cc && !opts.stat && !opts.name_status { opts.patch = true; }
+        if no_patch { opts.patch = false; }
+        Ok(opts)
+    }
+}
+
+/// One commit in `git log` format with the parts `opts` asks for. Diffs
+/// are limited to `paths` when any are given.
+pub fn commit_log_text(repo: &Repository, commit: &Commit<'_>, opts: LogOptions, paths: &[String], settings: &DiffSettings) -> Result<String> {
+    let mut out = format!("commit {}\n", commit.id());
+    if commit.parent_count() > 1 {
+        let parents: Vec<String> = commit.parent_ids().map(|id| crate::short_id(&id)).collect();
+        out.push_str(&format!("Merge: {}\n", parents.join(" ")));
+    }
+    let author = commit.author();
+    out.push_str(&format!("Author: {} <{}>\n", author.name().unwrap_or("<unknown>"), author.email().unwrap_or("")));
+    out.push_str(&format!("Date:   {}\n\n", to_rfc3339(author.when().seconds())));
+    for line in commit.message().unwrap_or("").trim_end().lines() {
+        out.push_str(&format!("    {line}\n"));
+    }
+
+    if commit.parent_count() > 1 && !opts.cc { return Ok(out); }
+    // A merge's stat and file list are against its first parent, its
+    // patch is the combined diff.
+    let Some(diff) = commit_diff(repo, commit, paths, settings)? else { return Ok(out) };
+    if opts.stat {
+        let stats = diff.stats()?.to_buf(DiffStatsFormat::FULL, 80)?;
+        out.push('\n');
+        out.push_str(stats.as_str().unwrap_or(""));
+    }
+    if opts.name_status {
+        out.push('\n');
+        // Raw lines give the status with the similarity of renames and
+        // copies, then the paths; git's name-status separates both paths
+        // of a rename with a tab: `R087\told\tnew`.
+        diff.print(DiffFormat::Raw, |delta, _, line| {
+            let text = String::from_utf8_lossy(line.content());
+            let Some(status) = text.splitn(5, ' ').nth(4).and_then(|s| s.split('\t').next()) else { return true };
+            let path = |file: git2::DiffFile<'_>| file.path().map_or(String::new(), |p| p.to_string_lossy().into_owned());
+            let (old, new) = (path(delta.old_file()), path(delta.new_file()));
+            if old == new {
+                out.push_str(&format!("{status}\t{new}\n"));
+            } else {
+                out.push_str(&format!("{status}\t{old}\t{new}\n"));
+            }
+            true
+        })?;
+    }
+    if opts.patch {
+        out.push('\n');
+        if commit.parent_count() > 1 {
+            out.push_str(&combined_diff_text(repo, commit, paths, settings)?);
+        } else {
+            out.push_str(&diff_to_text(&diff)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Changes of `commit` against its first parent, or `None` when it changes
+/// nothing under `paths`.
+fn commit_diff<'r>(repo: &'r Repository, commit: &Commit<'_>, paths: &[String], settings: &DiffSettings) -> Result<Option<Diff<'r>>> {
+    let mut opts = settings.options();
+    for p in paths { opts.pathspec(p); }
+    let parent_tree = if commit.parent_count() > 0 { Some(commit.parent(0)?.tree()?) } else { None };
+    let diff = settings.diff_trees(repo, parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut opts))?;
+    Ok(if diff.deltas().len() > 0 { Some(diff) } else { None })
+}
+
+/// For `--follow`: the paths to show for `commit` if it changes `path`.
+/// When the file was renamed or copied here, both names are shown and
+/// `path` moves on to the old one.
+fn follow_commit(repo: &Repository, commit: &Commit<'_>, path: &mut String, settings: &DiffSettings) -> Result<Option<Vec<String>>> {
+    let tree = commit.tree()?;
+    let parent = if commit.parent_count() > 0 { Some(commit.parent(0)?.tree()?) } else { None };
+    let id = |tree: &git2::Tree<'_>| tree.get_path(Path::new(path.as_str())).ok().map(|e| e.id());
+    let (old, new) = (parent.as_ref().and_then(id), id(&tree));
+    if old == new { return Ok(None); }
+    if old.is_some() || new.is_none() { return Ok(Some(vec![path.clone()])); }
+
+    // Added here: look for the file it came from.
+    let source = settings.rename_source(repo, parent.as_ref(), &tree, path)?;
+    Ok(Some(match source {
+        Some(source) => vec![source.clone(), std::mem::replace(path, source)],
+        None => vec![path.clone()],
+    }))
+}
+
+/// A commit of the log view and its lines.
+#[derive(Debug, Clone)]
+pub struct LogCommit {
+    pub id: Oid,
+    pub lines: Vec<String>,
+}
+
+enum LogEvent {
+    Commit(LogCommit),
+    Error(String),
+}
+
+/// Formats the commits of a revision spec on a background thread, like
+/// tig reading `git log` output.
+pub struct LogLoader {
+    rx: Receiver<LogEvent>,
+    stop: Arc<AtomicBool>,
+    loading: bool,
+    error: Option<String>,
+}
+
+impl LogLoader {
+    pub fn spawn(git_dir: PathBuf, spec: RevSpec, opts: LogOptions, settings: DiffSettings) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        thread::spawn(move || {
+            let res = (|| -> Result<()> {
+                let repo = Repository::open(&git_dir)?;
+                let mut walk = repo.revwalk()?;
+                spec.apply(&repo, &mut walk)?;
+                walk.set_sorting(Sort::TIME)?;
+                // As in git, only a single file can be followed.
+                let mut follow = match spec.paths.as_slice() {
+                    [path] if opts.follow => Some(path.clone()),
+                    _ => None,
+                };
+                for oid in walk {
+                    if flag.load(Ordering::Relaxed) { break; }
+                    let commit = repo.find_commit(oid?)?;
+                    let paths = match follow.as_mut() {
+                        Some(path) => match follow_commit(&repo, &commit, path, &settings)? {
+                            Some(paths) => paths,
+                            None => continue,
+                        },
+                        None if spec.matches(&repo, &commit)? => spec.paths.clone(),
+                        None => continue,
+                    };
+                    let text = commit_log_text(&repo, &commit, opts, &paths, &settings)?;
+                    let lines = text.lines().map(str::to_string).collect();
+                    if tx.send(LogEvent::Commit(LogCommit { id: commit.id(), lines })).is_err() { break; }
+                }
+                Ok(())
+            })();
+            if let Err(e) = res { let _ = tx.send(LogEvent::Error(e.to_string())); }
+        });
+        Self { rx, stop, loading: true, error: None }
+    }
+
+    /// Commits formatted since the last call.
+    pub fn poll(&mut self) -> Vec<LogCommit> {
+        let mut out = Vec::new();
+        while self.loading {
+            match self.rx.try_recv() {
+                Ok(LogEvent::Commit(commit)) => out.push(commit),
+                Ok(LogEvent::Error(e)) => self.error = Some(e),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.loading = false,
+            }
+        }
+        out
+    }
+
+    pub fn is_loading(&self) -> bool { self.loading }
+
+    pub fn error(&self) -> Option<&str> { self.error.as_deref() }
+}
+
+impl Drop for LogLoader {
+    fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{branch, commit, poll_all, test_repo};
+
+    fn log_commits(repo: &Repository, args: &[&str], opts: &str) -> Vec<LogCommit> {
+        let spec = RevSpec::parse(repo, args).unwrap();
+        let mut loader = LogLoader::spawn(repo.path().to_path_buf(), spec, LogOptions::parse(opts).unwrap(), DiffSettings::default());
+        let commits = poll_all(&mut loader, LogLoader::is_loading, LogLoader::poll);
+        assert_eq!(loader.error(), None);
+        commits
+    }
+
+    fn log(repo: &Repository, args: &[&str], opts: &str) -> Vec<Oid> {
+        log_commits(repo, args, opts).into_iter().map(|c| c.id).collect()
+    }
+
+    #[test]
+    fn follow_a_renamed_file() {
+        let repo = test_repo("log-follow");
+        let text: String = (1..=10).map(|n| format!("line {n}\n")).collect();
+        let first = commit(&repo, "add", &[], &[("old", &text)]);
+        let renamed = commit(&repo, "rename", &[first], &[("new", &text.replace("line 1\n", "one\n"))]);
+        let changed = commit(&repo, "change", &[renamed], &[("new", &text.replace("line 1\n", "ONE\n"))]);
+        branch(&repo, "main", changed);
+
+        assert_eq!(log(&repo, &["main", "--", "new"], ""), [changed, renamed]);
+        assert_eq!(log(&repo, &["main", "--", "new"], "--follow"), [changed, renamed, first]);
+        let lines: Vec<String> = log_commits(&repo, &["main", "--", "new"], "--follow --name-status").into_iter().flat_map(|c| c.lines).collect();
+        assert!(lines.iter().any(|l| l == "R090\told\tnew"));
+        assert!(lines.iter().any(|l| l == "A\told"));
+    }
+
+    #[test]
+    fn parse_log_options() {
+        let parse = |args| LogOptions::parse(args).unwrap();
+        assert_eq!(parse(""), LogOptions::default());
+        assert_eq!(parse("--cc"), LogOptions { cc: true, patch: true, ..LogOptions::default() });
+        assert_eq!(parse("--cc --stat"), LogOptions { cc: true, stat: true, ..LogOptions::default() });
+        assert_eq!(parse("--cc --name-status"), LogOptions { cc: true, name_status: true, ..LogOptions::default() });
+        assert_eq!(parse("--cc --no-patch"), LogOptions { cc: true, ..LogOptions::default() });
+        assert_eq!(parse("-s -p"), LogOptions::default());
+        assert_eq!(parse("--patch --follow"), LogOptions { patch: true, follow: true, ..LogOptions::default() });
+        assert!(LogOptions::parse("--graph").is_err());
+    }
+
+    #[test]
+    fn log_text_by_options() {
+        let repo = test_repo("log-text");
+        let first = commit(&repo, "first", &[], &[("a", "1\n2\n")]);
+        let second = commit(&repo, "second\n\nbody", &[first], &[("a", "1\ntwo\n"), ("b", "new\n")]);
+        let side = commit(&repo, "side", &[first], &[("a", "one\n2\n")]);
+        let merge = commit(&repo, "merge", &[second, side], &[("a", "one\ntwo\n"), ("b", "new\n")]);
+        let text = |id, opts: &str| {
+            let commit = repo.find_commit(id).unwrap();
+            commit_log_text(&repo, &commit, LogOptions::parse(opts).unwrap(), &[], &DiffSettings::default()).unwrap()
+        };
+
+        let plain = text(second, "");
+        assert!(plain.starts_with(&format!("commit {second}\nAuthor: Test <test@example.com>\nDate:   ")));
+        assert!(plain.ends_with("\n\n    second\n    \n    body\n"));
+        let stat = text(second, "--stat");
+        assert!(stat.contains(" a | 2 +-\n") && stat.contains(" b | 1 +\n"), "{stat}");
+        assert!(stat.contains("2 files changed, 2 insertions(+), 1 deletion(-)"), "{stat}");
+        assert!(text(second, "--name-status").ends_with("\n\nM\ta\nA\tb\n"));
+        let patch = text(second, "-p");
+        assert!(patch.contains("diff --git a/a b/a\n") && patch.contains("-2\n+two\n"), "{patch}");
+        // Only the paths asked for.
+        let commit = repo.find_commit(second).unwrap();
+        let only_b = commit_log_text(&repo, &commit, LogOptions::parse("--name-status").unwrap(), &["b".into()], &DiffSettings::default()).unwrap();
+        assert!(only_b.ends_with("\n\nA\tb\n"));
+
+        // Merges show no changes without --cc, and a combined diff with it.
+        let merged = text(merge, "--stat -p");
+        assert!(merged.contains(&format!("Merge: {} {}\n", crate::short_id(&second), crate::short_id(&side))));
+        assert!(merged.ends_with("    merge\n"));
+        let combined = text(merge, "--cc");
+        assert!(combined.contains("diff --cc a\n"), "{combined}");
+        assert!(!text(merge, "--cc --no-patch").contains("diff"));
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{bail, Result};
+use git2::{ObjectType, Oid, Repository};
+use std::collections::HashMap;
+
+/// Reference types, in the order decorations are listed (see `refdb.c`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RefKind {
+    Head,
+    Branch,
+    TrackedRemote,
+    Remote,
+    Tag,
+    LocalTag,
+    Replace,
+    Stash,
+    Note,
+    Prefetch,
+    Other,
+}
+
+impl RefKind {
+    pub const ALL: [RefKind; 11] = [
+        RefKind::Head, RefKind::Branch, RefKind::TrackedRemote, RefKind::Remote, RefKind::Tag,
+        RefKind::LocalTag, RefKind::Replace, RefKind::Stash, RefKind::Note, RefKind::Prefetch,
+        RefKind::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RefKind::Head => "head",
+            RefKind::Branch => "branch",
+            RefKind::TrackedRemote => "tracked-remote",
+            RefKind::Remote => "remote",
+            RefKind::Tag => "tag",
+            RefKind::LocalTag => "local-tag",
+            RefKind::Replace => "replace",
+            RefKind::Stash => "stash",
+            RefKind::Note => "note",
+            RefKind::Prefetch => "prefetch",
+            RefKind::Other => "other",
+        }
+    }
+
+    pub fn is_tag(self) -> bool { matches!(self, RefKind::Tag | RefKind::LocalTag) }
+    pub fn is_remote(self) -> bool { matches!(self, RefKind::Remote | RefKind::TrackedRemote) }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefInfo {
+    /// Display name; tag, branch and remote prefixes are stripped.
+    pub name: String,
+    /// Full reference name, e.g. `refs/heads/main`.
+    pub full_name: String,
+    pub kind: RefKind,
+    /// The commit the reference (eventually) points to.
+    pub id: Oid,
+}
+
+/// All references of a repository, indexed by the commit they point at.
+#[derive(Debug, Default)]
+pub struct RefDb {
+    by_id: HashMap<Oid, Vec<RefInfo>>,
+    head: Option<RefInfo>,
+}
+
+impl RefDb {
+    pub fn load(repo: &Repository) -> Result<Self> {
+        let mut db = RefDb::default();
+        let head = repo.head().ok();
+        let head_branch = head.as_ref()
+            .filter(|h| h.is_branch())
+            .and_then(|h| h.name())
+            .map(str::to_string);
+        let tracked = head_branch.as_deref()
+            .and_then(|name| repo.branch_upstream_name(name).ok())
+            .and_then(|buf| buf.as_str().map(str::to_string));
+
+        for reference in repo.references()? {
+            let reference = match reference { Ok(r) => r, Err(_) => continue };
+            let full_name = match reference.name() { Some(n) => n.to_string(), None => continue };
+            let (kind, name) = if let Some(tag) = full_name.strip_prefix("refs/tags/") {
+                let annotated = reference.resolve().ok()
+                    .and_then(|r| r.target())
+                    .and_then(|oid| repo.find_object(oid, None).ok())
+                    .is_some_and(|obj| obj.kind() == Some(ObjectType::Tag));
+                (if annotated { RefKind::Tag } else { RefKind::LocalTag }, tag.to_string())
+            } else if let Some(remote) = full_name.strip_prefix("refs/remotes/") {
+                let kind = if tracked.as_deref() == Some(full_name.as_str()) { RefKind::TrackedRemote } else { RefKind::Remote };
+                (kind, remote.to_string())
+            } else if let Some(replaced) = full_name.strip_prefix("refs/replace/") {
+                // Decorate the replaced object, not the replacement.
+                if let Ok(id) = Oid::from_str(replaced) {
+                    db.add(RefInfo { name: "replaced".into(), full_name: full_name.clone(), kind: RefKind::Replace, id });
+                }
+                continue;
+            } else if let Some(branch) = full_name.strip_prefix("refs/heads/") {
+                let kind = if head_branch.as_deref() == Some(full_name.as_str()) { RefKind::Head } else { RefKind::Branch };
+                (kind, branch.to_string())
+            } else if full_name == "refs/stash" {
+                (RefKind::Stash, "stash".to_string())
+            } else if let Some(note) = full_name.strip_prefix("refs/notes/") {
+                (RefKind::Note, format!("notes/{note}"))
+            } else if let Some(prefetch) = full_name.strip_prefix("refs/prefetch/") {
+                (RefKind::Prefetch, format!("prefetch/{prefetch}"))
+            } else {
+                (RefKind::Other, full_name.trim_start_matches("refs/").to_string())
+            };
+            let id = match reference.peel_to_commit() { Ok(c) => c.id(), Err(_) => continue };
+            db.add(RefInfo { name, full_name, kind, id });
+        }
+
+        // Keep the refs/tags/ prefix when a branch of the same name exists.
+        for refs in db.by_id.values_mut() {
+            for r in refs.iter_mut().filter(|r| r.kind.is_tag()) {
+                if repo.find_reference(&format!("refs/heads/{}", r.name)).is_ok() {
+                    r.name = r.full_name.trim_start_matches("refs/").to_string();
+                }
+            }
+        }
+
+        if let Some(head) = head {
+            if let Ok(commit) = head.peel_to_commit() {
+                if head_branch.is_none() {
+                    // Detached HEAD gets its own decoration.
+                    db.add(RefInfo { name: "HEAD".into(), full_name: "HEAD".into(), kind: RefKind::Head, id: commit.id() });
+                }
+                db.head = db.get(&commit.id()).iter().find(|r| r.kind == RefKind::Head).cloned();
+            }
+        }
+        for refs in db.by_id.values_mut() { refs.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name))); }
+        Ok(db)
+    }
+
+    fn add(&mut self, info: RefInfo) { self.by_id.entry(info.id).or_default().push(info); }
+
+    /// References pointing at `id`, sorted by kind then name.
+    pub fn get(&self, id: &Oid) -> &[RefInfo] {
+        self.by_id.get(id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The checked out branch, or `HEAD` when detached.
+    pub fn head(&self) -> Option<&RefInfo> { self.head.as_ref() }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RefInfo> { self.by_id.values().flatten() }
+}
+
+/// Text drawn before and after a reference name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefDecoration {
+    pub start: String,
+    pub end: String,
+}
+
+/// Parsed `reference-format` option, e.g. `[branch] <tag> {remote} ~replace~`.
+/// Types prefixed with `hide:` map to `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefFormat {
+    formats: HashMap<RefKind, Option<RefDecoration>>,
+}
+
+impl RefFormat {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut fmt = RefFormat::default();
+        for arg in spec.split_whitespace() {
+            let found = (0..arg.len()).filter(|i| arg.is_char_boundary(*i)).find_map(|pos| {
+                RefKind::ALL.into_iter().find(|k| arg[pos..].starts_with(k.name())).map(|k| (pos, k))
+            });
+            let Some((pos, kind)) = found else { bail!("Unknown ref format: {arg}") };
+            let start = &arg[..pos];
+            let end = &arg[pos + kind.name().len()..];
+            let deco = if start == "hide:" { None } else { Some(RefDecoration { start: start.into(), end: end.into() }) };
+            fmt.formats.insert(kind, deco);
+        }
+        Ok(fmt)
+    }
+
+    /// Decoration for `kind`, falling back from local tags to tags, tracked
+    /// remotes to remotes, and anything else to branches.
+    pub fn get(&self, kind: RefKind) -> Option<RefDecoration> {
+        let lookup = |k: RefKind| self.formats.get(&k).cloned();
+        lookup(kind)
+            .or_else(|| if kind.is_tag() { lookup(RefKind::Tag) } else { None })
+            .or_else(|| if kind.is_remote() { lookup(RefKind::Remote) } else { None })
+            .or_else(|| lookup(RefKind::Branch))
+            .unwrap_or(Some(RefDecoration { start: String::new(), end: String::new() }))
+    }
+
+    /// Render `info` with its decoration, or `None` if the type is hidden.
+    pub fn format(&self, info: &RefInfo) -> Option<String> {
+        self.get(info.kind).map(|d| format!("{}{}{}", d.start, info.name, d.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str, kind: RefKind) -> RefInfo {
+        RefInfo { name: name.into(), full_name: format!("refs/{name}"), kind, id: Oid::zero() }
+    }
+
+    #[test]
+    fn parse_decorations() {
+        let fmt = RefFormat::parse("[branch] <tag> {remote} ~replace~ hide:stash").unwrap();
+        assert_eq!(fmt.format(&info("main", RefKind::Branch)).as_deref(), Some("[main]"));
+        assert_eq!(fmt.format(&info("v1", RefKind::Tag)).as_deref(), Some("<v1>"));
+        assert_eq!(fmt.format(&info("origin/main", RefKind::Remote)).as_deref(), Some("{origin/main}"));
+        assert_eq!(fmt.format(&info("stash", RefKind::Stash)), None);
+    }
+
+    #[test]
+    fn fall_back_to_related_types() {
+        let fmt = RefFormat::parse("[branch] <tag> {remote} <<local-tag>>").unwrap();
+        assert_eq!(fmt.format(&info("v1", RefKind::LocalTag)).as_deref(), Some("<<v1>>"));
+        assert_eq!(fmt.format(&info("origin/main", RefKind::TrackedRemote)).as_deref(), Some("{origin/main}"));
+        assert_eq!(fmt.format(&info("HEAD", RefKind::Head)).as_deref(), Some("[HEAD]"));
+        let plain = RefFormat::parse("").unwrap();
+        assert_eq!(plain.format(&info("main", RefKind::Branch)).as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn reject_unknown_types() {
+        assert!(RefFormat::parse("[branch] <label>").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use git2::{Oid, Repository};
+
+/// One reflog line: `HEAD@{index}` moved from `old` to `new`.
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub selector: String,
+    pub old: Oid,
+    pub new: Oid,
+    pub message: String,
+    pub time: i64,
+}
+
+/// Reflog of `name`, newest first. Short names such as `main` resolve
+/// like they do for `git reflog show`.
+pub fn reflog_entries(repo: &Repository, name: &str) -> Result<Vec<ReflogEntry>> {
+    let full_name = if name == "HEAD" {
+        name.to_string()
+    } else {
+        let reference = repo.resolve_reference_from_short_name(name)?;
+        reference.name().unwrap_or(name).to_string()
+    };
+    let reflog = repo.reflog(&full_name)?;
+    Ok(reflog.iter().enumerate().map(|(index, entry)| ReflogEntry {
+        selector: format!("{name}@{{{index}}}"),
+        old: entry.id_old(),
+        new: entry.id_new(),
+        message: entry.message().unwrap_or("").to_string(),
+        time: entry.committer().when().seconds(),
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{branch, commit, test_repo};
+
+    #[test]
+    fn reflog_newest_first() {
+        let repo = test_repo("reflog");
+        let first = commit(&repo, "first", &[], &[]);
+        let second = commit(&repo, "second", &[first], &[]);
+        branch(&repo, "main", first);
+        branch(&repo, "main", second);
+
+        let entries = reflog_entries(&repo, "main").unwrap();
+        let moves: Vec<(&str, Oid, Oid)> = entries.iter().map(|e| (e.selector.as_str(), e.old, e.new)).collect();
+        assert_eq!(moves, [("main@{0}", first, second), ("main@{1}", Oid::zero(), first)]);
+        assert_eq!(reflog_entries(&repo, "refs/heads/main").unwrap().len(), 2);
+        assert!(reflog_entries(&repo, "nosuch").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{Ident, Interner, RefDb, RefInfo, RefKind};
+use anyhow::{bail, Result};
+use git2::{build::CheckoutBuilder, BranchType, Oid, Repository, ResetType};
+
+/// A reference with its tip commit, as listed in the refs view.
+#[derive(Debug, Clone)]
+pub struct RefEntry {
+    pub info: RefInfo,
+    pub author: Ident,
+    pub committer: Ident,
+    /// Author time.
+    pub time: i64,
+    pub title: Box<str>,
+}
+
+/// Branches, remotes and tags sorted by name; stashes, notes, prefetch and
+/// replace refs are left out as in tig's refs view.
+pub fn ref_entries(repo: &Repository, refs: &RefDb) -> Result<Vec<RefEntry>> {
+    let mut idents = Interner::default();
+    let mut out = Vec::new();
+    for info in refs.iter() {
+        if matches!(info.kind, RefKind::Stash | RefKind::Note | RefKind::Prefetch | RefKind::Replace) { continue; }
+        let commit = repo.find_commit(info.id)?;
+        out.push(RefEntry {
+            info: info.clone(),
+            author: idents.ident(&commit.author()),
+            committer: idents.ident(&commit.committer()),
+            time: commit.author().when().seconds(),
+            title: commit.summary().unwrap_or("").into(),
+        });
+    }
+    out.sort_by(|a, b| a.info.name.cmp(&b.info.name).then_with(|| a.info.kind.cmp(&b.info.kind)));
+    Ok(out)
+}
+
+/// Check out `info` like `git checkout`: local branches become HEAD,
+/// anything else detaches it. Fails instead of overwriting local changes.
+pub fn checkout_ref(repo: &Repository, info: &RefInfo) -> Result<()> {
+    let commit = repo.find_commit(info.id)?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    if info.full_name.starts_with("refs/heads/") {
+        repo.set_head(&info.full_name)?;
+    } else {
+        repo.set_head_detached(info.id)?;
+    }
+    Ok(())
+}
+
+/// Check out `id` on a detached HEAD, keeping local changes safe.
+pub fn checkout_commit(repo: &Repository, id: Oid) -> Result<()> {
+    let commit = repo.find_commit(id)?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    repo.set_head_detached(id)?;
+    Ok(())
+}
+
+/// Move the current branch to `id` and discard all local changes, like
+/// `git reset --hard`.
+pub fn reset_hard(repo: &Repository, id: Oid) -> Result<()> {
+    let commit = repo.find_commit(id)?;
+    repo.reset(commit.as_object(), ResetType::Hard, None)?;
+    Ok(())
+}
+
+/// Delete a local branch even if it is not merged, like `git branch -D`.
+pub fn delete_branch(repo: &Repository, info: &RefInfo) -> Result<()> {
+    let Some(name) = info.full_name.strip_prefix("refs/heads/") else { bail!("{} is not a local branch", info.name) };
+    repo.find_branch(name, BranchType::Local)?.delete()?;
+    Ok(())
+}
+
+/// Create branch `name` at `target`, refusing to replace an existing one.
+pub fn create_branch(repo: &Repository, name: &str, target: Oid) -> Result<()> {
+    repo.branch(name, &repo.find_commit(target)?, false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{branch, checkout, commit, test_repo};
+
+    #[test]
+    fn list_check_out_and_delete_refs() {
+        let repo = test_repo("refs");
+        let first = commit(&repo, "first", &[], &[("file", "1\n")]);
+        let second = commit(&repo, "second", &[first], &[("file", "2\n")]);
+        checkout(&repo, second);
+        branch(&repo, "topic", first);
+        repo.tag_lightweight("v1", repo.find_commit(first).unwrap().as_object(), false).unwrap();
+
+        let entries = ref_entries(&repo, &RefDb::load(&repo).unwrap()).unwrap();
+        let listed: Vec<(&str, &str)> = entries.iter().map(|e| (e.info.name.as_str(), &*e.title)).collect();
+        assert_eq!(listed, [("main", "second"), ("topic", "first"), ("v1", "first")]);
+        let info = |name: &str| entries.iter().find(|e| e.info.name == name).unwrap().info.clone();
+
+        checkout_ref(&repo, &info("topic")).unwrap();
+        assert_eq!(repo.head().unwrap().shorthand(), Some("topic"));
+        checkout_ref(&repo, &info("v1")).unwrap();
+        assert!(repo.head_detached().unwrap());
+
+        assert!(create_branch(&repo, "main", first).is_err());
+        create_branch(&repo, "new", first).unwrap();
+        delete_branch(&repo, &info("main")).unwrap();
+        assert!(repo.find_branch("main", BranchType::Local).is_err());
+        assert!(delete_branch(&repo, &info("v1")).is_err());
+    }
+}
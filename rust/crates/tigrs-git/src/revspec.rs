@@ -0,0 +1,424 @@
+use anyhow::{bail, Context, Result};
+use git2::{Commit, DiffOptions, Oid, Repository, Revwalk, Sort};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// One revision argument, as accepted by `git log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevItem {
+    /// `foo`: include everything reachable from `foo`.
+    Include(String),
+    /// `^foo`: exclude everything reachable from `foo`.
+    Exclude(String),
+    /// `a..b`: reachable from `b` but not from `a`; after `--not`, the
+    /// other way around.
+    Range(String, String, bool),
+    /// `a...b`: reachable from either side but not from all their merge
+    /// bases; after `--not`, the merge bases without either side.
+    Symmetric(String, String, bool),
+    /// `--all`, `--branches`, `--tags`, `--remotes`.
+    Glob(RefGlob, bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefGlob { All, Branches, Tags, Remotes }
+
+impl RefGlob {
+    fn pattern(self) -> &'static str {
+        match self {
+            RefGlob::All => "refs/*",
+            RefGlob::Branches => "refs/heads/*",
+            RefGlob::Tags => "refs/tags/*",
+            RefGlob::Remotes => "refs/remotes/*",
+        }
+    }
+}
+
+/// Parsed revision arguments plus an optional pathspec, as in
+/// `tig [rev-args] [--] [paths]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevSpec {
+    pub revs: Vec<RevItem>,
+    pub paths: Vec<String>,
+}
+
+impl RevSpec {
+    /// Parse command line arguments. Arguments after `--` are always paths;
+    /// before it, anything that does not resolve as a revision but exists in
+    /// the work tree is taken as a path, like git does.
+    pub fn parse<S: AsRef<str>>(repo: &Repository, args: &[S]) -> Result<Self> {
+        let mut spec = RevSpec::default();
+        let mut negate = false;
+        let mut only_paths = false;
+        for arg in args.iter().map(AsRef::as_ref) {
+            if only_paths {
+                spec.paths.push(arg.to_string());
+                continue;
+            }
+            match arg {
+                "--" => { only_paths = true; continue; }
+                "--not" => { negate = !negate; continue; }
+                "--all" => { spec.revs.push(RevItem::Glob(RefGlob::All, negate)); continue; }
+                "--branches" => { spec.revs.push(RevItem::Glob(RefGlob::Branches, negate)); continue; }
+                "--tags" => { spec.revs.push(RevItem::Glob(RefGlob::Tags, negate)); continue; }
+                "--remotes" => { spec.revs.push(RevItem::Glob(RefGlob::Remotes, negate)); continue; }
+                _ => {}
+            }
+            if arg.starts_with('-') {
+                bail!("Unsupported revision option: {arg}");
+            }
+            match parse_rev(arg, negate) {
+                Some(item) if resolves(repo, &item) => spec.revs.push(item),
+                _ if is_worktree_path(repo, arg) => spec.paths.push(arg.to_string()),
+                _ => bail!("Unknown revision or path not in the working tree: {arg}"),
+            }
+        }
+        Ok(spec)
+    }
+
+    pub fn is_empty(&self) -> bool { self.revs.is_empty() && self.paths.is_empty() }
+
+    /// Push and hide the commits described by this spec on `walk`.
+    /// Defaults to HEAD when nothing is included.
+    pub fn apply(&self, repo: &Repository, walk: &mut Revwalk<'_>) -> Result<()> {
+        let mut included = false;
+        for item in &self.revs {
+            match item {
+                RevItem::Include(r) => { walk.push(resolve(repo, r)?)?; included = true; }
+                RevItem::Exclude(r) => walk.hide(resolve(repo, r)?)?,
+                RevItem::Range(a, b, negated) => {
+                    let (a, b) = (resolve(repo, a)?, resolve(repo, b)?);
+                    let (hide, push) = if *negated { (b, a) } else { (a, b) };
+                    walk.hide(hide)?;
+                    walk.push(push)?;
+                    included = true;
+                }
+                RevItem::Symmetric(a, b, negated) => {
+                    let (a, b) = (resolve(repo, a)?, resolve(repo, b)?);
+                    let bases = match repo.merge_bases(a, b) {
+                        Ok(bases) => bases.iter().copied().collect(),
+                        Err(_) => Vec::new(),
+                    };
+                    for id in [a, b] {
+                        if *negated { walk.hide(id)? } else { walk.push(id)? }
+                    }
+                    for id in bases {
+                        if *negated { walk.push(id)? } else { walk.hide(id)? }
+                    }
+                    included = true;
+                }
+                RevItem::Glob(glob, false) => {
+                    walk.push_glob(glob.pattern())?;
+                    if *glob == RefGlob::All { let _ = walk.push_head(); }
+                    included = true;
+                }
+                RevItem::Glob(glob, true) => walk.hide_glob(glob.pattern())?,
+            }
+        }
+        if !included { walk.push_head()?; }
+        Ok(())
+    }
+
+    /// Whether `commit` should be shown given the pathspec. Commits that are
+    /// tree-same to any parent for the given paths are dropped.
+    pub fn matches(&self, repo: &Repository, commit: &Commit<'_>) -> Result<bool> {
+        if self.paths.is_empty() { return Ok(true); }
+        if commit.parent_count() == 0 {
+            let diff = repo.diff_tree_to_tree(None, Some(&commit.tree()?), Some(&mut self.diff_options()))?;
+            return Ok(diff.deltas().len() > 0);
+        }
+        Ok(self.treesame_parent(repo, commit)?.is_none())
+    }
+
+    /// Whether some commits are hidden, as by `^foo` or `a..b`.
+    fn hides(&self) -> bool {
+        self.revs.iter().any(|item| !matches!(item, RevItem::Include(_) | RevItem::Glob(_, false)))
+    }
+
+    /// The first parent `commit` is tree-same to for the pathspec.
+    fn treesame_parent(&self, repo: &Repository, commit: &Commit<'_>) -> Result<Option<Oid>> {
+        let tree = commit.tree()?;
+        for parent in commit.parents() {
+            let diff = repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&tree), Some(&mut self.diff_options()))?;
+            if diff.deltas().len() == 0 { return Ok(Some(parent.id())); }
+        }
+        Ok(None)
+    }
+
+    fn diff_options(&self) -> DiffOptions {
+        let mut opts = DiffOptions::new();
+        for p in &self.paths { opts.pathspec(p); }
+        opts
+    }
+}
+
+/// How many more commits the walk reads before yielding a shown commit, so
+/// that a child dated before its parent can still be yielded first.
+const SKEW_WINDOW: usize = 256;
+
+/// Walks the commits a [`RevSpec`] shows, with their parents rewritten to
+/// the nearest ancestors that are shown too, as `git log --parents` does.
+/// Parents that are hidden are left out, so a graph of the walk has no
+/// lanes waiting for commits that never appear.
+///
+/// Commits stream in time order. As libgit2 would have to read the whole
+/// history for topological order, clock skew is handled by holding each
+/// shown commit back for [`SKEW_WINDOW`] commits and never yielding one
+/// while a held commit has it as a parent.
+pub(crate) struct SpecWalk<'r> {
+    repo: &'r Repository,
+    spec: &'r RevSpec,
+    ids: Box<dyn Iterator<Item = Result<Oid, git2::Error>> + 'r>,
+    /// Every commit the walk yields, when the spec hides some. The walk is
+    /// read whole to know them; ranges and exclusions are usually short.
+    walked: Option<HashSet<Oid>>,
+    /// The nearest shown ancestor of commits looked at ahead of the walk.
+    ancestors: HashMap<Oid, Option<Oid>>,
+    /// Shown commits not yielded yet, with how many commits were read
+    /// before them.
+    held: VecDeque<(usize, Oid, Vec<Oid>)>,
+    /// How many held commits have each commit as a parent.
+    held_children: HashMap<Oid, usize>,
+    read: usize,
+    done: bool,
+}
+
+impl<'r> SpecWalk<'r> {
+    pub(crate) fn new(repo: &'r Repository, spec: &'r RevSpec) -> Result<Self> {
+        let mut walk = repo.revwalk()?;
+        spec.apply(repo, &mut walk)?;
+        walk.set_sorting(Sort::TIME)?;
+        let (ids, walked): (Box<dyn Iterator<Item = _>>, _) = if spec.hides() {
+            let ids = walk.collect::<Result<Vec<_>, _>>()?;
+            let walked = ids.iter().copied().collect();
+            (Box::new(ids.into_iter().map(Ok)), Some(walked))
+        } else {
+            (Box::new(walk), None)
+        };
+        Ok(Self {
+            repo, spec, ids, walked, ancestors: HashMap::new(), held: VecDeque::new(), held_children: HashMap::new(),
+            read: 0, done: false,
+        })
+    }
+
+    /// The nearest ancestor of `id`, itself included, that the walk shows.
+    fn shown_ancestor(&mut self, id: Oid) -> Result<Option<Oid>> {
+        let mut dropped = Vec::new();
+        let mut next = Some(id);
+        let found = loop {
+            let Some(id) = next else { break None };
+            if let Some(found) = self.ancestors.get(&id) { break *found; }
+            if self.walked.as_ref().is_some_and(|walked| !walked.contains(&id)) { break None; }
+            if self.spec.paths.is_empty() { break Some(id); }
+            // Parents missing from a shallow clone are never shown.
+            let Ok(commit) = self.repo.find_commit(id) else { break None };
+            dropped.push(id);
+            if commit.parent_count() == 0 {
+                break self.spec.matches(self.repo, &commit)?.then_some(id);
+            }
+            match self.spec.treesame_parent(self.repo, &commit)? {
+                Some(parent) => next = Some(parent),
+                None => break Some(id),
+            }
+        };
+        for id in dropped { self.ancestors.insert(id, found); }
+        Ok(found)
+    }
+
+    /// The rewritten parents of walked commit `id`, if it is shown.
+    fn shown_parents(&mut self, id: Oid) -> Result<Option<Vec<Oid>>> {
+        let shown = self.shown_ancestor(id)? == Some(id);
+        // Children mostly come before their parents, so `id` is seldom
+        // looked up again.
+        self.ancestors.remove(&id);
+        if !shown { return Ok(None); }
+        let mut parents = Vec::new();
+        for parent in self.repo.find_commit(id)?.parent_ids() {
+            match self.shown_ancestor(parent)? {
+                Some(p) if !parents.contains(&p) => parents.push(p),
+                _ => {}
+            }
+        }
+        Ok(Some(parents))
+    }
+
+    /// The first held commit that was held long enough, or for good once
+    /// the walk is done, and has no held children.
+    fn release(&mut self) -> Option<(Oid, Vec<Oid>)> {
+        let (read, done) = (self.read, self.done);
+        let at = self.held.iter()
+            .take_while(|(at, ..)| done || at + SKEW_WINDOW <= read)
+            .position(|(_, id, _)| !self.held_children.contains_key(id))?;
+        let (_, id, parents) = self.held.remove(at)?;
+        for parent in &parents {
+            if let Some(count) = self.held_children.get_mut(parent) {
+                *count -= 1;
+                if *count == 0 { self.held_children.remove(parent); }
+            }
+        }
+        Some((id, parents))
+    }
+}
+
+impl Iterator for SpecWalk<'_> {
+    /// A walked commit, with its rewritten parents when it is shown.
+    /// Commits that are not shown come straight from the walk.
+    type Item = Result<(Oid, Option<Vec<Oid>>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((id, parents)) = self.release() { return Some(Ok((id, Some(parents)))); }
+            if self.done { return None; }
+            let id = match self.ids.next() {
+                Some(Ok(id)) => id,
+                Some(Err(e)) => return Some(Err(e.into())),
+                None => { self.done = true; continue; }
+            };
+            self.read += 1;
+            match self.shown_parents(id) {
+                Ok(Some(parents)) => {
+                    for parent in &parents { *self.held_children.entry(*parent).or_default() += 1; }
+                    self.held.push_back((self.read, id, parents));
+                }
+                Ok(None) => return Some(Ok((id, None))),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn parse_rev(arg: &str, negate: bool) -> Option<RevItem> {
+    let or_head = |s: &str| if s.is_empty() { "HEAD".to_string() } else { s.to_string() };
+    if let Some((a, b)) = arg.split_once("...") {
+        if a.is_empty() && b.is_empty() { return None; }
+        return Some(RevItem::Symmetric(or_head(a), or_head(b), negate));
+    }
+    if let Some((a, b)) = arg.split_once("..") {
+        if a.is_empty() && b.is_empty() { return None; }
+        return Some(RevItem::Range(or_head(a), or_head(b), negate));
+    }
+    if let Some(rev) = arg.strip_prefix('^') {
+        if rev.is_empty() { return None; }
+        return Some(if negate { RevItem::Include(rev.to_string()) } else { RevItem::Exclude(rev.to_string()) });
+    }
+    Some(if negate { RevItem::Exclude(arg.to_string()) } else { RevItem::Include(arg.to_string()) })
+}
+
+fn resolves(repo: &Repository, item: &RevItem) -> bool {
+    match item {
+        RevItem::Include(r) | RevItem::Exclude(r) => resolve(repo, r).is_ok(),
+        RevItem::Range(a, b, _) | RevItem::Symmetric(a, b, _) => resolve(repo, a).is_ok() && resolve(repo, b).is_ok(),
+        RevItem::Glob(..) => true,
+    }
+}
+
+fn resolve(repo: &Repository, rev: &str) -> Result<Oid> {
+    let obj = repo.revparse_single(rev).with_context(|| format!("Unknown revision: {rev}"))?;
+    let commit = obj.peel_to_commit().with_context(|| format!("Not a commit: {rev}"))?;
+    Ok(commit.id())
+}
+
+fn is_worktree_path(repo: &Repository, arg: &str) -> bool {
+    repo.workdir().map(|dir| dir.join(arg).exists()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{branch, commit, commit_at, test_repo};
+
+    fn walk(repo: &Repository, args: &[&str]) -> Vec<Oid> {
+        let spec = RevSpec::parse(repo, args).unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        spec.apply(repo, &mut walk).unwrap();
+        sorted(walk.map(Result::unwrap).collect())
+    }
+
+    fn sorted(mut ids: Vec<Oid>) -> Vec<Oid> { ids.sort(); ids }
+
+    #[test]
+    fn parse_rev_forms() {
+        let s = String::from;
+        assert_eq!(parse_rev("a..b", false), Some(RevItem::Range(s("a"), s("b"), false)));
+        assert_eq!(parse_rev("..b", false), Some(RevItem::Range(s("HEAD"), s("b"), false)));
+        assert_eq!(parse_rev("a...", true), Some(RevItem::Symmetric(s("a"), s("HEAD"), true)));
+        assert_eq!(parse_rev("^a", false), Some(RevItem::Exclude(s("a"))));
+        assert_eq!(parse_rev("^a", true), Some(RevItem::Include(s("a"))));
+        assert_eq!(parse_rev("a", true), Some(RevItem::Exclude(s("a"))));
+        assert_eq!(parse_rev("..", false), None);
+        assert_eq!(parse_rev("^", false), None);
+    }
+
+    #[test]
+    fn parse_revisions_and_paths() {
+        let repo = test_repo("revspec-parse");
+        let root = commit(&repo, "root", &[], &[]);
+        branch(&repo, "main", root);
+        std::fs::create_dir(repo.workdir().unwrap().join("src")).unwrap();
+
+        let spec = RevSpec::parse(&repo, &["main", "--not", "--tags", "src", "--", "main"]).unwrap();
+        assert_eq!(spec.revs, vec![RevItem::Include("main".into()), RevItem::Glob(RefGlob::Tags, true)]);
+        assert_eq!(spec.paths, vec!["src".to_string(), "main".to_string()]);
+        assert!(RevSpec::parse(&repo, &["nosuch"]).is_err());
+        assert!(RevSpec::parse(&repo, &["--reverse"]).is_err());
+        assert!(RevSpec::parse(&repo, &[] as &[&str]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn not_negates_ranges() {
+        let repo = test_repo("revspec-not");
+        let root = commit(&repo, "root", &[], &[]);
+        let a = commit(&repo, "a", &[root], &[]);
+        let b = commit(&repo, "b", &[a], &[]);
+        branch(&repo, "a", a);
+        branch(&repo, "b", b);
+        assert_eq!(walk(&repo, &["a..b"]), vec![b]);
+        assert_eq!(walk(&repo, &["b..a"]), vec![]);
+        assert_eq!(walk(&repo, &["--not", "b..a"]), vec![b]);
+        assert_eq!(walk(&repo, &["--not", "--not", "a..b"]), vec![b]);
+    }
+
+    #[test]
+    fn symmetric_hides_all_merge_bases() {
+        // A criss-cross merge: `left` and `right` have two merge bases.
+        let repo = test_repo("revspec-symmetric");
+        let root = commit(&repo, "root", &[], &[]);
+        let (a1, b1) = (commit(&repo, "a1", &[root], &[]), commit(&repo, "b1", &[root], &[]));
+        let (a2, b2) = (commit(&repo, "a2", &[a1, b1], &[]), commit(&repo, "b2", &[b1, a1], &[]));
+        let (left, right) = (commit(&repo, "left", &[a2], &[]), commit(&repo, "right", &[b2], &[]));
+        branch(&repo, "left", left);
+        branch(&repo, "right", right);
+        assert_eq!(walk(&repo, &["left...right"]), sorted(vec![left, right, a2, b2]));
+        assert_eq!(walk(&repo, &["left", "right", "--not", "left~...right~"]), sorted(vec![left, right]));
+        assert_eq!(walk(&repo, &["--not", "left...right"]), vec![]);
+    }
+
+    #[test]
+    fn children_come_before_parents_despite_clock_skew() {
+        // `skewed` claims to be older than its parent, which `newer` also
+        // reaches; time order alone walks `top`, then `parent`, then
+        // `skewed`.
+        let repo = test_repo("revspec-skew");
+        let root = commit_at(&repo, 100, "root", &[], &[]);
+        let parent = commit_at(&repo, 300, "parent", &[root], &[]);
+        let skewed = commit_at(&repo, 200, "skewed", &[parent], &[]);
+        let top = commit_at(&repo, 350, "top", &[skewed], &[]);
+        let newer = commit_at(&repo, 400, "newer", &[parent], &[]);
+        branch(&repo, "top", top);
+        branch(&repo, "newer", newer);
+        let spec = RevSpec::parse(&repo, &["top", "newer"]).unwrap();
+        let ids: Vec<Oid> = SpecWalk::new(&repo, &spec).unwrap().map(|entry| entry.unwrap().0).collect();
+        assert_eq!(ids, [newer, top, skewed, parent, root]);
+    }
+
+    #[test]
+    fn streams_without_reading_the_whole_walk() {
+        let repo = test_repo("revspec-stream");
+        let mut tip = commit(&repo, "0", &[], &[]);
+        for n in 1..=SKEW_WINDOW + 10 { tip = commit(&repo, &n.to_string(), &[tip], &[]); }
+        branch(&repo, "main", tip);
+        let spec = RevSpec::parse(&repo, &["main"]).unwrap();
+        let mut walk = SpecWalk::new(&repo, &spec).unwrap();
+        assert_eq!(walk.next().unwrap().unwrap().0, tip);
+        assert_eq!(walk.read, SKEW_WINDOW + 1);
+    }
+}
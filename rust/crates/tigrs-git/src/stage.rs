@@ -0,0 +1,480 @@
+use crate::status::{status_diff, StatusEntry, StatusSection};
+use crate::DiffSettings;
+use anyhow::{bail, Result};
+use git2::{ApplyLocation, Diff, DiffFormat, Repository};
+
+/// One line of a hunk, without its trailing newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    /// `' '`, `'+'` or `'-'`.
+    pub origin: char,
+    pub text: String,
+    /// Followed by `\ No newline at end of file`.
+    pub no_newline: bool,
+}
+
+impl DiffLine {
+    pub fn is_change(&self) -> bool { self.origin != ' ' }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text after the closing `@@`, usually the enclosing function.
+    pub context: String,
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!("@@ -{},{} +{},{} @@{}", self.old_start, self.old_lines, self.new_start, self.new_lines, self.context)
+    }
+
+    fn to_text(&self, out: &mut String) {
+        out.push_str(&self.header());
+        out.push('\n');
+        for line in &self.lines {
+            out.push(line.origin);
+            out.push_str(&line.text);
+            out.push('\n');
+            if line.no_newline { out.push_str("\\ No newline at end of file\n"); }
+        }
+    }
+
+    /// The same change seen from the other side.
+    fn reversed(&self) -> Hunk {
+        let lines = self.lines.iter().map(|l| DiffLine {
+            origin: match l.origin { '+' => '-', '-' => '+', c => c },
+            ..l.clone()
+        }).collect();
+        Hunk {
+            old_start: self.new_start,
+            old_lines: self.new_lines,
+            new_start: self.old_start,
+            new_lines: self.old_lines,
+            context: self.context.clone(),
+            lines,
+        }
+    }
+
+    /// Keep only the changes in `first..=last`: other removals become
+    /// context and other additions are dropped. The result applies on its
+    /// own, as if no other hunk of the file had been applied.
+    fn select(&self, first: usize, last: usize) -> Hunk {
+        let mut lines: Vec<DiffLine> = self.lines.iter().enumerate().filter_map(|(i, l)| {
+            if (first..=last).contains(&i) || l.origin == ' ' {
+                Some(l.clone())
+            } else if l.origin == '-' {
+                Some(DiffLine { origin: ' ', ..l.clone() })
+            } else {
+                None
+            }
+        }).collect();
+        // A last line kept as context cannot be followed by additions; it
+        // has to gain its newline first.
+        if let Some(i) = lines.iter().position(|l| l.origin == ' ' && l.no_newline) {
+            if lines[i + 1..].iter().any(|l| l.origin == '+') {
+                let with_newline = DiffLine { origin: '+', text: lines[i].text.clone(), no_newline: false };
+                lines[i].origin = '-';
+                lines.insert(i + 1, with_newline);
+            }
+        }
+        let old_lines = lines.iter().filter(|l| l.origin != '+').count() as u32;
+        let new_lines = lines.iter().filter(|l| l.origin != '-').count() as u32;
+        let new_start = if old_lines == 0 {
+            self.old_start + 1
+        } else if new_lines == 0 {
+            self.old_start.saturating_sub(1)
+        } else {
+            self.old_start
+        };
+        Hunk { old_start: self.old_start, old_lines, new_start, new_lines, context: self.context.clone(), lines }
+    }
+
+    /// Bounds of the run of changed lines around `line`.
+    pub fn change_block(&self, line: usize) -> (usize, usize) {
+        let first = (0..line).rev().take_while(|&i| self.lines[i].is_change()).last().unwrap_or(line);
+        let last = (line..self.lines.len()).take_while(|&i| self.lines[i].is_change()).last().unwrap_or(line);
+        (first, last)
+    }
+
+    /// Split at every run of context lines between two changes, like
+    /// `git add -p`. The shared context ends one hunk and starts the next.
+    pub fn split(&self) -> Vec<Hunk> {
+        let mut bounds = Vec::new();
+        let (mut start, mut seen_change, mut context_start) = (0, false, None);
+        for (i, line) in self.lines.iter().enumerate() {
+            if !line.is_change() {
+                if seen_change && context_start.is_none() { context_start = Some(i); }
+                continue;
+            }
+            if let Some(cs) = context_start.take() {
+                bounds.push((start, i));
+                start = cs;
+            }
+            seen_change = true;
+        }
+        bounds.push((start, self.lines.len()));
+
+        bounds.into_iter().enumerate().map(|(n, (a, b))| {
+            let before = &self.lines[..a];
+            let lines = self.lines[a..b].to_vec();
+            Hunk {
+                old_start: self.old_start + before.iter().filter(|l| l.origin != '+').count() as u32,
+                old_lines: lines.iter().filter(|l| l.origin != '+').count() as u32,
+                new_start: self.new_start + before.iter().filter(|l| l.origin != '-').count() as u32,
+                new_lines: lines.iter().filter(|l| l.origin != '-').count() as u32,
+                context: if n == 0 { self.context.clone() } else { String::new() },
+                lines,
+            }
+        }).collect()
+    }
+}
+
+/// Header lines and hunks of one file in the diff.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDiff {
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    /// Whether the file is created or deleted, which patches with only
+    /// part of its lines cannot do.
+    pub fn is_new_or_deleted(&self) -> bool {
+        self.header.iter().any(|l| l.starts_with("new file mode") || l.starts_with("deleted file mode"))
+    }
+}
+
+/// The diff shown by the stage view for one status entry.
+#[derive(Debug, Clone)]
+pub struct StagePatch {
+    pub entry: StatusEntry,
+    pub files: Vec<FileDiff>,
+}
+
+/// Which part of a hunk to stage, mirroring `status-update`,
+/// `stage-update-part` and `stage-update-line`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageSelection {
+    Hunk,
+    /// The run of changes around the given line.
+    Part(usize),
+    Line(usize),
+}
+
+fn parse_diff(diff: &Diff<'_>) -> Result<Vec<FileDiff>> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    diff.print(DiffFormat::Patch, |_, hunk, line| {
+        let text = String::from_utf8_lossy(line.content());
+        match line.origin() {
+            'F' => files.push(FileDiff { header: text.lines().map(str::to_string).collect(), hunks: Vec::new() }),
+            'B' => if let Some(file) = files.last_mut() { file.header.extend(text.lines().map(str::to_string)) },
+            'H' => if let (Some(file), Some(hunk)) = (files.last_mut(), hunk) {
+                let header = String::from_utf8_lossy(hunk.header());
+                let context = header.trim_end_matches('\n').splitn(3, "@@").nth(2).unwrap_or("").to_string();
+                file.hunks.push(Hunk {
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    context,
+                    lines: Vec::new(),
+                });
+            },
+            origin @ (' ' | '+' | '-') => if let Some(hunk) = files.last_mut().and_then(|f| f.hunks.last_mut()) {
+                let text = text.strip_suffix('\n').unwrap_or(&text).to_string();
+                hunk.lines.push(DiffLine { origin, text, no_newline: false });
+            },
+            '=' | '>' | '<' => if let Some(line) = files.last_mut().and_then(|f| f.hunks.last_mut()).and_then(|h| h.lines.last_mut()) {
+                line.no_newline = true;
+            },
+            _ => {}
+        }
+        true
+    })?;
+    Ok(files)
+}
+
+impl StagePatch {
+    /// The patch of `entry` with the context size and options of
+    /// `settings`; whitespace is never ignored, as the patch is applied.
+    pub fn load(repo: &Repository, entry: &StatusEntry, settings: &DiffSettings) -> Result<Self> {
+        let files = parse_diff(&status_diff(repo, entry, false, &settings.exact_whitespace())?)?;
+        Ok(Self { entry: entry.clone(), files })
+    }
+
+    pub fn is_empty(&self) -> bool { self.files.is_empty() }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            for line in &file.header {
+                out.push_str(line);
+                out.push('\n');
+            }
+            for hunk in &file.hunks { hunk.to_text(&mut out); }
+        }
+        out
+    }
+
+    /// Replace a hunk with its parts; returns how many there are.
+    pub fn split_hunk(&mut self, file: usize, hunk: usize) -> usize {
+        let Some(hunks) = self.files.get_mut(file).map(|f| &mut f.hunks) else { return 0 };
+        let Some(parts) = hunks.get(hunk).map(Hunk::split) else { return 0 };
+        let count = parts.len();
+        hunks.splice(hunk..=hunk, parts);
+        count
+    }
+
+    fn hunk(&self, file: usize, hunk: usize) -> Result<&Hunk> {
+        match self.files.get(file).and_then(|f| f.hunks.get(hunk)) {
+            Some(h) => Ok(h),
+            None => bail!("No such chunk"),
+        }
+    }
+
+    /// Apply `hunk` to the index, with the file header taken from the
+    /// reversed diff when `reverse` is set.
+    fn apply(&self, repo: &Repository, file: usize, hunk: &Hunk, reverse: bool) -> Result<()> {
+        // Only the file header is used, which the settings do not change.
+        let files = if reverse { parse_diff(&status_diff(repo, &self.entry, true, &DiffSettings::default())?)? } else { self.files.clone() };
+        let Some(header) = files.get(file).map(|f| &f.header) else { bail!("File is no longer changed") };
+        let mut text = String::new();
+        for line in header {
+            text.push_str(line);
+            text.push('\n');
+        }
+        hunk.to_text(&mut text);
+        let diff = Diff::from_buffer(text.as_bytes())?;
+        repo.apply(&diff, ApplyLocation::Index, None)?;
+        Ok(())
+    }
+
+    /// Stage part of an unstaged hunk, or unstage part of a staged one.
+    pub fn stage(&self, repo: &Repository, file: usize, hunk: usize, selection: StageSelection) -> Result<()> {
+        let reverse = match self.entry.section {
+            StatusSection::Unstaged => false,
+            StatusSection::Staged => true,
+            StatusSection::Untracked => bail!("Untracked files can only be staged as a whole"),
+        };
+        if selection != StageSelection::Hunk && self.files.get(file).is_some_and(FileDiff::is_new_or_deleted) {
+            bail!("Only whole chunks of new or deleted files can be staged");
+        }
+        let hunk = self.hunk(file, hunk)?;
+        let hunk = if reverse { hunk.reversed() } else { hunk.clone() };
+        let hunk = match selection {
+            StageSelection::Hunk => hunk.select(0, hunk.lines.len().saturating_sub(1)),
+            StageSelection::Part(line) => {
+                let (first, last) = hunk.change_block(line);
+                hunk.select(first, last)
+            }
+            StageSelection::Line(line) => hunk.select(line, line),
+        };
+        self.apply(repo, file, &hunk, reverse)
+    }
+
+    /// Discard an unstaged hunk from the work tree, putting back the lines
+    /// it replaced. libgit2 leaves the work tree side of a reversed diff
+    /// without an id, so its patch header would not apply.
+    pub fn revert(&self, repo: &Repository, file: usize, hunk: usize) -> Result<()> {
+        if self.entry.section != StatusSection::Unstaged { bail!("Only unstaged changes can be reverted"); }
+        let hunk = self.hunk(file, hunk)?;
+        let Some(dir) = repo.workdir() else { bail!("The stage view requires a working tree") };
+        let path = dir.join(&self.entry.path);
+        // A deleted file comes back whole.
+        let content = if path.exists() { std::fs::read(&path)? } else { Vec::new() };
+        let mut lines: Vec<&[u8]> = content.split_inclusive(|b| *b == b'\n').collect();
+        let side = |origin| {
+            hunk.lines.iter()
+                .filter(move |l| l.origin == ' ' || l.origin == origin)
+                .map(|l| if l.no_newline { l.text.clone() } else { format!("{}\n", l.text) })
+                .collect::<Vec<_>>()
+        };
+        let (old, new) = (side('-'), side('+'));
+        // An empty range starts after the line it is at.
+        let start = if new.is_empty() { hunk.new_start } else { hunk.new_start.saturating_sub(1) } as usize;
+        let current = lines.get(start..start + new.len());
+        if !current.is_some_and(|current| current.iter().zip(&new).all(|(a, b)| *a == b.as_bytes())) {
+            bail!("Chunk no longer matches the work tree file");
+        }
+        lines.splice(start..start + new.len(), old.iter().map(String::as_bytes));
+        std::fs::write(&path, lines.concat())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{checkout, commit, test_repo};
+    use std::path::Path;
+
+    /// A hunk from `lines` written as in a patch, `\` marking a line
+    /// without newline at the end of the file.
+    fn hunk(old_start: u32, new_start: u32, lines: &[&str]) -> Hunk {
+        let lines: Vec<DiffLine> = lines.iter().map(|l| {
+            let (text, no_newline) = match l[1..].strip_suffix('\\') { Some(t) => (t, true), None => (&l[1..], false) };
+            DiffLine { origin: l.chars().next().unwrap(), text: text.into(), no_newline }
+        }).collect();
+        let old_lines = lines.iter().filter(|l| l.origin != '+').count() as u32;
+        let new_lines = lines.iter().filter(|l| l.origin != '-').count() as u32;
+        Hunk { old_start, old_lines, new_start, new_lines, context: String::new(), lines }
+    }
+
+    /// Content of `file` in the index.
+    fn staged(repo: &Repository) -> String {
+        let entry = repo.index().unwrap().get_path(Path::new("file"), 0).unwrap();
+        String::from_utf8(repo.find_blob(entry.id).unwrap().content().to_vec()).unwrap()
+    }
+
+    fn worktree(repo: &Repository) -> String { std::fs::read_to_string(repo.workdir().unwrap().join("file")).unwrap() }
+
+    fn entry(section: StatusSection) -> StatusEntry {
+        StatusEntry { section, status: 'M', path: "file".into(), old_path: None }
+    }
+
+    fn text(hunk: &Hunk) -> String {
+        let mut out = String::new();
+        hunk.to_text(&mut out);
+        out
+    }
+
+    #[test]
+    fn select_keeps_other_removals_as_context() {
+        let h = hunk(10, 10, &[" a", "-b", "-c", "+C", " d", "+e"]);
+        assert_eq!(text(&h.select(1, 1)), "@@ -10,4 +10,3 @@\n a\n-b\n c\n d\n");
+        assert_eq!(text(&h.select(3, 3)), "@@ -10,4 +10,5 @@\n a\n b\n c\n+C\n d\n");
+        assert_eq!(h.select(0, 5), h);
+    }
+
+    #[test]
+    fn select_adds_a_newline_before_kept_additions() {
+        let h = hunk(1, 1, &[" a", "-b\\", "+b", "+c\\"]);
+        let expected = "@@ -1,2 +1,3 @@\n a\n-b\n\\ No newline at end of file\n+b\n+c\n\\ No newline at end of file\n";
+        assert_eq!(text(&h.select(3, 3)), expected);
+    }
+
+    #[test]
+    fn select_only_additions_or_removals() {
+        let added = hunk(4, 5, &["+a", "+b"]);
+        assert_eq!(text(&added.select(0, 0)), "@@ -4,0 +5,1 @@\n+a\n");
+        let removed = hunk(4, 3, &["-a", "-b"]);
+        assert_eq!(text(&removed.select(1, 1)), "@@ -4,2 +4,1 @@\n a\n-b\n");
+    }
+
+    #[test]
+    fn split_at_context_between_changes() {
+        let h = hunk(1, 1, &["-a", " x", " y", "+b", " z"]);
+        let parts: Vec<String> = h.split().iter().map(text).collect();
+        assert_eq!(parts, ["@@ -1,3 +1,2 @@\n-a\n x\n y\n", "@@ -2,3 +1,4 @@\n x\n y\n+b\n z\n"]);
+        assert_eq!(hunk(1, 1, &[" x", "-a", "+b", " y"]).split().len(), 1);
+    }
+
+    #[test]
+    fn change_block_spans_adjacent_changes() {
+        let h = hunk(1, 1, &[" a", "-b", "-c", "+C", " d", "+e"]);
+        assert_eq!(h.change_block(2), (1, 3));
+        assert_eq!(h.change_block(5), (5, 5));
+    }
+
+    #[test]
+    fn deleted_files_are_staged_whole() {
+        let repo = test_repo("stage-deleted");
+        let id = commit(&repo, "first", &[], &[("file", "a\nb\nc\n")]);
+        checkout(&repo, id);
+        std::fs::remove_file(repo.workdir().unwrap().join("file")).unwrap();
+
+        let entry = StatusEntry { section: StatusSection::Unstaged, status: 'D', path: "file".into(), old_path: None };
+        let patch = StagePatch::load(&repo, &entry, &DiffSettings::default()).unwrap();
+        assert!(patch.files[0].is_new_or_deleted());
+        assert!(patch.stage(&repo, 0, 0, StageSelection::Line(1)).is_err());
+        assert!(patch.stage(&repo, 0, 0, StageSelection::Part(1)).is_err());
+        patch.stage(&repo, 0, 0, StageSelection::Hunk).unwrap();
+        assert!(repo.index().unwrap().get_path(Path::new("file"), 0).is_none());
+    }
+
+    #[test]
+    fn whitespace_is_not_ignored_when_staging() {
+        let repo = test_repo("stage-whitespace");
+        let id = commit(&repo, "first", &[], &[("file", "a\n  b\nc\n")]);
+        checkout(&repo, id);
+        std::fs::write(repo.workdir().unwrap().join("file"), "a\nb\nc\nd\n").unwrap();
+
+        let entry = entry(StatusSection::Unstaged);
+        let settings = DiffSettings { ignore_whitespace: true, context: 1, ..DiffSettings::default() };
+        let patch = StagePatch::load(&repo, &entry, &settings).unwrap();
+        assert_eq!(patch.files[0].hunks[0].header(), "@@ -1,3 +1,4 @@");
+        let lines: Vec<String> = patch.files[0].hunks[0].lines.iter().map(|l| format!("{}{}", l.origin, l.text)).collect();
+        assert_eq!(lines, [" a", "-  b", "+b", " c", "+d"]);
+        patch.stage(&repo, 0, 0, StageSelection::Line(4)).unwrap();
+        assert_eq!(staged(&repo), "a\n  b\nc\nd\n");
+
+        let patch = StagePatch::load(&repo, &entry, &settings).unwrap();
+        patch.stage(&repo, 0, 0, StageSelection::Hunk).unwrap();
+        assert_eq!(staged(&repo), "a\nb\nc\nd\n");
+    }
+
+    /// A repository whose `file` has two hunks of unstaged changes.
+    fn two_hunks(name: &str) -> crate::test_repo::TestRepo {
+        let repo = test_repo(name);
+        let id = commit(&repo, "first", &[], &[("file", "1\n2\n3\n4\n5\n6\n7\n8\n9\n")]);
+        checkout(&repo, id);
+        std::fs::write(repo.workdir().unwrap().join("file"), "1\ntwo\n2b\n3\n4\n5\n6\n7\neight\n9\n").unwrap();
+        repo
+    }
+
+    #[test]
+    fn stage_a_line_after_another_hunk() {
+        let repo = two_hunks("stage-second-hunk");
+        let patch = StagePatch::load(&repo, &entry(StatusSection::Unstaged), &DiffSettings { context: 1, ..DiffSettings::default() }).unwrap();
+        assert_eq!(patch.files[0].hunks.len(), 2);
+        patch.stage(&repo, 0, 0, StageSelection::Hunk).unwrap();
+        assert_eq!(staged(&repo), "1\ntwo\n2b\n3\n4\n5\n6\n7\n8\n9\n");
+        // Reloaded, as the stage view does after every change: what was
+        // the second hunk now starts one line further down the index.
+        let patch = StagePatch::load(&repo, &entry(StatusSection::Unstaged), &DiffSettings { context: 1, ..DiffSettings::default() }).unwrap();
+        assert_eq!(patch.files[0].hunks.len(), 1);
+        assert_eq!(patch.files[0].hunks[0].header(), "@@ -8,3 +8,3 @@ two");
+        patch.stage(&repo, 0, 0, StageSelection::Line(1)).unwrap();
+        assert_eq!(staged(&repo), "1\ntwo\n2b\n3\n4\n5\n6\n7\n9\n");
+        assert_eq!(worktree(&repo), "1\ntwo\n2b\n3\n4\n5\n6\n7\neight\n9\n");
+    }
+
+    #[test]
+    fn unstage_a_removed_line() {
+        let repo = test_repo("stage-unstage-line");
+        let id = commit(&repo, "first", &[], &[("file", "a\nb\nc\nd\n")]);
+        checkout(&repo, id);
+        std::fs::write(repo.workdir().unwrap().join("file"), "a\nx\nd\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("file")).unwrap();
+        index.write().unwrap();
+
+        let patch = StagePatch::load(&repo, &entry(StatusSection::Staged), &DiffSettings::default()).unwrap();
+        let lines: Vec<String> = patch.files[0].hunks[0].lines.iter().map(|l| format!("{}{}", l.origin, l.text)).collect();
+        assert_eq!(lines, [" a", "-b", "-c", "+x", " d"]);
+        patch.stage(&repo, 0, 0, StageSelection::Line(2)).unwrap();
+        assert_eq!(staged(&repo), "a\nc\nx\nd\n");
+        assert_eq!(worktree(&repo), "a\nx\nd\n");
+        assert!(patch.revert(&repo, 0, 0).is_err());
+    }
+
+    #[test]
+    fn revert_a_hunk() {
+        let repo = two_hunks("stage-revert");
+        let patch = StagePatch::load(&repo, &entry(StatusSection::Unstaged), &DiffSettings { context: 1, ..DiffSettings::default() }).unwrap();
+        patch.revert(&repo, 0, 0).unwrap();
+        assert_eq!(worktree(&repo), "1\n2\n3\n4\n5\n6\n7\neight\n9\n");
+        assert_eq!(staged(&repo), "1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+        assert!(patch.revert(&repo, 0, 0).is_err());
+
+        std::fs::remove_file(repo.workdir().unwrap().join("file")).unwrap();
+        let deleted = StatusEntry { status: 'D', ..entry(StatusSection::Unstaged) };
+        StagePatch::load(&repo, &deleted, &DiffSettings::default()).unwrap().revert(&repo, 0, 0).unwrap();
+        assert_eq!(worktree(&repo), staged(&repo));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{diff_to_text, to_rfc3339, Interner};
+use anyhow::Result;
+use git2::{Oid, Repository};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct StashEntry {
+    /// Position in the stash list, as in `stash@{index}`.
+    pub index: usize,
+    pub id: Oid,
+    pub message: String,
+    pub author: Arc<str>,
+    pub time: i64,
+}
+
+impl StashEntry {
+    pub fn name(&self) -> String { format!("stash@{{{}}}", self.index) }
+}
+
+/// All stashes, newest first.
+pub fn stash_entries(repo: &mut Repository) -> Result<Vec<StashEntry>> {
+    let mut found = Vec::new();
+    repo.stash_foreach(|index, message, id| {
+        found.push((index, message.to_string(), *id));
+        true
+    })?;
+    let mut authors = Interner::default();
+    found.into_iter().map(|(index, message, id)| {
+        let commit = repo.find_commit(id)?;
+        let author = commit.author();
+        Ok(StashEntry {
+            index,
+            id,
+            message,
+            author: authors.intern(author.name().unwrap_or("<unknown>")),
+            time: author.when().seconds(),
+        })
+    }).collect()
+}
+
+/// `git stash show -p` of a stash, followed by its untracked files when it
+/// was made with `--include-untracked`.
+pub fn stash_diff_text(repo: &Repository, stash: &StashEntry) -> Result<String> {
+    let commit = repo.find_commit(stash.id)?;
+    let base = commit.parent(0)?.tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&base), Some(&commit.tree()?), None)?;
+
+    let mut out = format!("commit {}\n", commit.id());
+    if let Some(name) = commit.author().name() { out.push_str(&format!("Author: {name}\n")); }
+    out.push_str(&format!("Date:   {}\n\n    {}\n\n", to_rfc3339(stash.time), stash.message));
+    out.push_str(&diff_to_text(&diff)?);
+    // The third parent holds the untracked files.
+    if let Ok(untracked) = commit.parent(2) {
+        let diff = repo.diff_tree_to_tree(None, Some(&untracked.tree()?), None)?;
+        out.push_str(&diff_to_text(&diff)?);
+    }
+    Ok(out)
+}
+
+pub fn stash_apply(repo: &mut Repository, stash: &StashEntry) -> Result<()> {
+    repo.stash_apply(stash.index, None)?;
+    Ok(())
+}
+
+pub fn stash_pop(repo: &mut Repository, stash: &StashEntry) -> Result<()> {
+    repo.stash_pop(stash.index, None)?;
+    Ok(())
+}
+
+pub fn stash_drop(repo: &mut Repository, stash: &StashEntry) -> Result<()> {
+    repo.stash_drop(stash.index)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{checkout, commit, test_repo};
+    use git2::{Signature, StashFlags};
+
+    #[test]
+    fn stash_show_and_pop() {
+        let mut repo = test_repo("stash");
+        let id = commit(&repo, "first", &[], &[("file", "a\n")]);
+        checkout(&repo, id);
+        let dir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(dir.join("file"), "b\n").unwrap();
+        std::fs::write(dir.join("new"), "untracked\n").unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        repo.stash_save(&sig, "work in progress", Some(StashFlags::INCLUDE_UNTRACKED)).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("file")).unwrap(), "a\n");
+
+        let entries = stash_entries(&mut repo).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].name(), &*entries[0].author), ("stash@{0}".to_string(), "Test"));
+        assert!(entries[0].message.ends_with("work in progress"));
+        let text = stash_diff_text(&repo, &entries[0]).unwrap();
+        assert!(text.contains("diff --git a/file b/file\n") && text.contains("-a\n+b\n"));
+        assert!(text.contains("diff --git a/new b/new\n") && text.contains("+untracked\n"));
+
+        stash_pop(&mut repo, &entries[0]).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("file")).unwrap(), "b\n");
+        assert!(dir.join("new").exists());
+        assert!(stash_entries(&mut repo).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{diff_to_text, DiffSettings};
+use anyhow::Result;
+use git2::{
+    build::CheckoutBuilder, Delta, Diff, IndexAddOption, Repository, Status,
+    StatusOptions as GitStatusOptions,
+};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusSection { Staged, Unstaged, Untracked }
+
+impl StatusSection {
+    pub fn title(self) -> &'static str {
+        match self {
+            StatusSection::Staged => "Changes to be committed:",
+            StatusSection::Unstaged => "Changes not staged for commit:",
+            StatusSection::Untracked => "Untracked files:",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub section: StatusSection,
+    /// Short status as printed by `git diff --name-status`: `M`, `A`, `D`,
+    /// `R`, `T`, `U` (unmerged) or `?` (untracked).
+    pub status: char,
+    pub path: String,
+    /// Source path of a rename.
+    pub old_path: Option<String>,
+}
+
+/// Options mirroring tig's `status-show-untracked-*` settings.
+#[derive(Debug, Clone, Copy)]
+pub struct StatusOptions {
+    pub show_untracked_files: bool,
+    pub show_untracked_dirs: bool,
+}
+
+impl Default for StatusOptions {
+    fn default() -> Self { Self { show_untracked_files: true, show_untracked_dirs: true } }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RepoStatus {
+    /// "On branch main", "Not currently on any branch", ...
+    pub branch: String,
+    pub staged: Vec<StatusEntry>,
+    pub unstaged: Vec<StatusEntry>,
+    pub untracked: Vec<StatusEntry>,
+}
+
+impl RepoStatus {
+    pub fn section(&self, section: StatusSection) -> &[StatusEntry] {
+        match section {
+            StatusSection::Staged => &self.staged,
+            StatusSection::Unstaged => &self.unstaged,
+            StatusSection::Untracked => &self.untracked,
+        }
+    }
+}
+
+fn delta_char(delta: Delta) -> char {
+    match delta {
+        Delta::Added => 'A',
+        Delta::Deleted => 'D',
+        Delta::Renamed => 'R',
+        Delta::Copied => 'C',
+        Delta::Typechange => 'T',
+        Delta::Conflicted => 'U',
+        Delta::Untracked => '?',
+        _ => 'M',
+    }
+}
+
+pub fn load_status(repo: &Repository, opts: StatusOptions) -> Result<RepoStatus> {
+    if repo.is_bare() { anyhow::bail!("The status view requires a working tree"); }
+    let mut out = RepoStatus { branch: branch_label(repo), ..Default::default() };
+
+    let mut gopts = GitStatusOptions::new();
+    // No rename detection between the index and the work tree: like the
+    // `git diff-files` tig lists unstaged changes with, a deleted file and
+    // an untracked one stay separate entries rather than an unstaged
+    // rename whose diff would leave out the untracked side.
+    gopts.include_untracked(opts.show_untracked_files)
+        .recurse_untracked_dirs(opts.show_untracked_dirs)
+        .include_ignored(false)
+        .renames_head_to_index(true);
+    let statuses = repo.statuses(Some(&mut gopts))?;
+
+    for entry in statuses.iter() {
+        let bits = entry.status();
+        let Some(path) = entry.path().map(str::to_string) else { continue };
+        if bits.contains(Status::CONFLICTED) {
+            // Listed once, with the unstaged changes: staging resolves the
+            // conflict, while unstaging would throw its stages away.
+            out.unstaged.push(StatusEntry { section: StatusSection::Unstaged, status: 'U', path, old_path: None });
+            continue;
+        }
+        if let Some(delta) = entry.head_to_index() {
+            let new = delta.new_file().path().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| path.clone());
+            let old = delta.old_file().path().map(|p| p.to_string_lossy().into_owned());
+            let status = delta_char(delta.status());
+            let old_path = if status == 'R' { old } else { None };
+            out.staged.push(StatusEntry { section: StatusSection::Staged, status, path: new, old_path });
+        }
+        if bits.contains(Status::WT_NEW) {
+            out.untracked.push(StatusEntry { section: StatusSection::Untracked, status: '?', path, old_path: None });
+        } else if let Some(delta) = entry.index_to_workdir() {
+            let new = delta.new_file().path().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| path.clone());
+            let old = delta.old_file().path().map(|p| p.to_string_lossy().into_owned());
+            let status = delta_char(delta.status());
+            let old_path = if status == 'R' { old } else { None };
+            out.unstaged.push(StatusEntry { section: StatusSection::Unstaged, status, path: new, old_path });
+        }
+    }
+    for list in [&mut out.staged, &mut out.unstaged, &mut out.untracked] {
+        list.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    Ok(out)
+}
+
+fn branch_label(repo: &Repository) -> String {
+    match repo.head() {
+        Ok(head) if head.is_branch() => format!("On branch {}", head.shorthand().unwrap_or("?")),
+        Ok(_) => "Not currently on any branch".into(),
+        Err(_) => match repo.find_reference("HEAD").ok().and_then(|r| r.symbolic_target().map(str::to_string)) {
+            Some(target) => format!("Initial commit on branch {}", target.trim_start_matches("refs/heads/")),
+            None => "Not currently on any branch".into(),
+        },
+    }
+}
+
+/// Diff for one status entry: HEAD vs index for staged changes, index vs
+/// work tree for unstaged ones, and the whole file for untracked files.
+/// `reverse` swaps the two sides.
+pub(crate) fn status_diff<'r>(repo: &'r Repository, entry: &StatusEntry, reverse: bool, settings: &DiffSettings) -> Result<Diff<'r>> {
+    let mut opts = settings.options();
+    // Untracked directories ("dir/") must match the files below them.
+    opts.pathspec(&entry.path).disable_pathspec_match(entry.section != StatusSection::Untracked).reverse(reverse);
+    if let Some(old) = &entry.old_path { opts.pathspec(old); }
+    let diff = match entry.section {
+        StatusSection::Staged => {
+            let head = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+            let mut diff = repo.diff_tree_to_index(head.as_ref(), None, Some(&mut opts))?;
+            diff.find_similar(None)?;
+            diff
+        }
+        StatusSection::Unstaged => repo.diff_index_to_workdir(None, Some(&mut opts))?,
+        StatusSection::Untracked => {
+            opts.include_untracked(true).recurse_untracked_dirs(true).show_untracked_content(true);
+            repo.diff_index_to_workdir(None, Some(&mut opts))?
+        }
+    };
+    Ok(diff)
+}
+
+/// Patch text of [`status_diff`].
+pub fn status_diff_text(repo: &Repository, entry: &StatusEntry, settings: &DiffSettings) -> Result<String> {
+    diff_to_text(&status_diff(repo, entry, false, settings)?)
+}
+
+fn workdir_path(repo: &Repository, path: &str) -> Result<std::path::PathBuf> {
+    match repo.workdir() {
+        Some(dir) => Ok(dir.join(path)),
+        None => anyhow::bail!("The status view requires a working tree"),
+    }
+}
+
+/// Stage, or unstage when in the staged section, every entry in `entries`.
+pub fn update_entries(repo: &Repository, entries: &[StatusEntry]) -> Result<()> {
+    let (staged, rest): (Vec<&StatusEntry>, Vec<&StatusEntry>) =
+        entries.iter().partition(|e| e.section == StatusSection::Staged);
+
+    if !rest.is_empty() {
+        let mut index = repo.index()?;
+        for entry in rest {
+            if entry.path.ends_with('/') {
+                // A collapsed untracked directory.
+                index.add_all([entry.path.as_str()], IndexAddOption::DEFAULT, None)?;
+                continue;
+            }
+            if let Some(old) = &entry.old_path { index.remove_path(Path::new(old))?; }
+            if workdir_path(repo, &entry.path)?.exists() {
+                index.add_path(Path::new(&entry.path))?;
+            } else {
+                index.remove_path(Path::new(&entry.path))?;
+            }
+        }
+        index.write()?;
+    }
+
+    if !staged.is_empty() {
+        let paths: Vec<&str> = staged.iter()
+            .flat_map(|e| std::iter::once(e.path.as_str()).chain(e.old_path.as_deref()))
+            .collect();
+        match repo.head().and_then(|h| h.peel_to_commit()) {
+            Ok(head) => repo.reset_default(Some(head.as_object()), paths)?,
+            Err(_) => {
+                // No HEAD yet: unstaging means dropping the path from the index.
+                let mut index = repo.index()?;
+                for path in paths { index.remove_path(Path::new(path))?; }
+                index.write()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Discard work tree changes to an unstaged file, like `git checkout -- <file>`.
+/// Unmerged files are first reset to their HEAD version.
+pub fn revert_entry(repo: &Repository, entry: &StatusEntry) -> Result<()> {
+    match entry.section {
+        StatusSection::Staged => anyhow::bail!("Cannot revert changes to staged files"),
+        StatusSection::Untracked => anyhow::bail!("Cannot revert changes to untracked files"),
+        StatusSection::Unstaged => {}
+    }
+    if entry.status == 'U' {
+        let head = repo.head()?.peel_to_commit()?;
+        if head.tree()?.get_path(Path::new(&entry.path)).is_err() {
+            let mut index = repo.index()?;
+            index.remove_path(Path::new(&entry.path))?;
+            index.write()?;
+            return Ok(());
+        }
+        repo.reset_default(Some(head.as_object()), [entry.path.as_str()])?;
+    }
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force().update_index(false).path(&entry.path);
+    if let Some(old) = &entry.old_path { checkout.path(old); }
+    repo.checkout_index(None, Some(&mut checkout))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{checkout, commit, test_repo, TestRepo};
+
+    /// A repository in the middle of a merge that conflicts on `file`.
+    fn conflicted_repo(name: &str) -> TestRepo {
+        let repo = test_repo(name);
+        let base = commit(&repo, "base", &[], &[("file", "a\n")]);
+        let ours = commit(&repo, "ours", &[base], &[("file", "b\n")]);
+        let theirs = commit(&repo, "theirs", &[base], &[("file", "c\n")]);
+        checkout(&repo, ours);
+        repo.merge(&[&repo.find_annotated_commit(theirs).unwrap()], None, None).unwrap();
+        repo
+    }
+
+    #[test]
+    fn sections_and_untracked_options() {
+        let repo = test_repo("status-sections");
+        let head = commit(&repo, "first", &[], &[("changed", "a\n"), ("removed", "b\n")]);
+        checkout(&repo, head);
+        let dir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(dir.join("changed"), "A\n").unwrap();
+        std::fs::write(dir.join("added"), "c\n").unwrap();
+        std::fs::remove_file(dir.join("removed")).unwrap();
+        std::fs::create_dir(dir.join("dir")).unwrap();
+        std::fs::write(dir.join("dir/one"), "1\n").unwrap();
+        std::fs::write(dir.join("dir/two"), "2\n").unwrap();
+        std::fs::write(dir.join("loose"), "3\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("added")).unwrap();
+        index.remove_path(Path::new("removed")).unwrap();
+        index.write().unwrap();
+
+        let paths = |entries: &[StatusEntry]| entries.iter().map(|e| (e.status, e.path.clone())).collect::<Vec<_>>();
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        assert_eq!(status.branch, "On branch main");
+        assert_eq!(paths(&status.staged), [('A', "added".into()), ('D', "removed".into())]);
+        assert_eq!(paths(&status.unstaged), [('M', "changed".into())]);
+        assert_eq!(paths(&status.untracked), [('?', "dir/one".into()), ('?', "dir/two".into()), ('?', "loose".into())]);
+        assert!(status.section(StatusSection::Untracked).iter().all(|e| e.section == StatusSection::Untracked));
+
+        let dirs = load_status(&repo, StatusOptions { show_untracked_dirs: false, ..StatusOptions::default() }).unwrap();
+        assert_eq!(paths(&dirs.untracked), [('?', "dir/".into()), ('?', "loose".into())]);
+        let files = load_status(&repo, StatusOptions { show_untracked_files: false, ..StatusOptions::default() }).unwrap();
+        assert!(files.untracked.is_empty());
+        assert_eq!(paths(&files.staged), paths(&status.staged));
+        assert_eq!(paths(&files.unstaged), paths(&status.unstaged));
+    }
+
+    /// Status and path of every entry in each section.
+    fn sections(repo: &Repository, opts: StatusOptions) -> [Vec<(char, String)>; 3] {
+        let status = load_status(repo, opts).unwrap();
+        [&status.staged, &status.unstaged, &status.untracked]
+            .map(|entries| entries.iter().map(|e| (e.status, e.path.clone())).collect())
+    }
+
+    #[test]
+    fn conflicts_are_listed_once() {
+        let repo = conflicted_repo("status-conflict");
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        assert!(status.staged.is_empty());
+        let unmerged = StatusEntry { section: StatusSection::Unstaged, status: 'U', path: "file".into(), old_path: None };
+        assert_eq!(status.unstaged, [unmerged]);
+    }
+
+    #[test]
+    fn staging_resolves_conflicts() {
+        let repo = conflicted_repo("status-resolve");
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        update_entries(&repo, &status.unstaged).unwrap();
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        assert!(!repo.index().unwrap().has_conflicts());
+        assert_eq!(status.staged.iter().map(|e| (e.status, e.path.as_str())).collect::<Vec<_>>(), [('M', "file")]);
+    }
+
+    #[test]
+    fn stage_and_unstage_files() {
+        let repo = test_repo("status-stage");
+        let head = commit(&repo, "first", &[], &[("changed", "a\n")]);
+        checkout(&repo, head);
+        let dir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(dir.join("changed"), "b\n").unwrap();
+        std::fs::write(dir.join("loose"), "c\n").unwrap();
+        std::fs::create_dir(dir.join("dir")).unwrap();
+        std::fs::write(dir.join("dir/one"), "1\n").unwrap();
+        std::fs::write(dir.join("dir/two"), "2\n").unwrap();
+        let opts = StatusOptions { show_untracked_dirs: false, ..StatusOptions::default() };
+
+        let status = load_status(&repo, opts).unwrap();
+        update_entries(&repo, &[status.unstaged.clone(), status.untracked.clone()].concat()).unwrap();
+        let staged = |paths: &[&str]| paths.iter().map(|p| ('A', p.to_string())).collect::<Vec<_>>();
+        let mut expected = vec![('M', "changed".to_string())];
+        expected.extend(staged(&["dir/one", "dir/two", "loose"]));
+        assert_eq!(sections(&repo, opts), [expected, vec![], vec![]]);
+
+        let status = load_status(&repo, opts).unwrap();
+        update_entries(&repo, &status.staged[..2]).unwrap();
+        // `dir` is no longer collapsed, as `dir/two` is in the index.
+        assert_eq!(
+            sections(&repo, opts),
+            [staged(&["dir/two", "loose"]), vec![('M', "changed".into())], vec![('?', "dir/one".into())]],
+        );
+    }
+
+    #[test]
+    fn unstaging_a_rename_resets_both_paths() {
+        let repo = test_repo("status-unstage-rename");
+        let head = commit(&repo, "first", &[], &[("old", "some content\nthat moves\n")]);
+        checkout(&repo, head);
+        let dir = repo.workdir().unwrap().to_path_buf();
+        std::fs::rename(dir.join("old"), dir.join("new")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("old")).unwrap();
+        index.add_path(Path::new("new")).unwrap();
+        index.write().unwrap();
+
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        assert_eq!(status.staged.len(), 1);
+        assert_eq!((status.staged[0].status, status.staged[0].old_path.as_deref()), ('R', Some("old")));
+        update_entries(&repo, &status.staged).unwrap();
+        assert_eq!(
+            sections(&repo, StatusOptions::default()),
+            [vec![], vec![('D', "old".into())], vec![('?', "new".into())]],
+        );
+    }
+
+    #[test]
+    fn unstaging_without_head() {
+        let repo = test_repo("status-unborn");
+        std::fs::write(repo.workdir().unwrap().join("file"), "a\n").unwrap();
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        update_entries(&repo, &status.untracked).unwrap();
+        assert_eq!(sections(&repo, StatusOptions::default()), [vec![('A', "file".into())], vec![], vec![]]);
+
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        update_entries(&repo, &status.staged).unwrap();
+        assert_eq!(sections(&repo, StatusOptions::default()), [vec![], vec![], vec![('?', "file".into())]]);
+    }
+
+    #[test]
+    fn revert_modified_and_deleted_files() {
+        let repo = test_repo("status-revert");
+        let head = commit(&repo, "first", &[], &[("changed", "a\n"), ("removed", "b\n"), ("staged", "c\n")]);
+        checkout(&repo, head);
+        let dir = repo.workdir().unwrap().to_path_buf();
+        std::fs::write(dir.join("changed"), "A\n").unwrap();
+        std::fs::remove_file(dir.join("removed")).unwrap();
+        std::fs::write(dir.join("staged"), "C\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("staged")).unwrap();
+        index.write().unwrap();
+        std::fs::write(dir.join("staged"), "CC\n").unwrap();
+
+        let status = load_status(&repo, StatusOptions::default()).unwrap();
+        for entry in &status.unstaged { revert_entry(&repo, entry).unwrap(); }
+        assert!(revert_entry(&repo, &status.staged[0]).is_err());
+        assert_eq!(std::fs::read_to_string(dir.join("changed")).unwrap(), "a\n");
+        assert_eq!(std::fs::read_to_string(dir.join("removed")).unwrap(), "b\n");
+        // Reverting goes back to the staged version, not to HEAD.
+        assert_eq!(std::fs::read_to_string(dir.join("staged")).unwrap(), "C\n");
+        assert_eq!(sections(&repo, StatusOptions::default()), [vec![('M', "staged".into())], vec![], vec![]]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Throwaway repositories for unit tests, also available to other crates'
+//! tests with the `test-support` feature.
+
+use git2::{build::CheckoutBuilder, Oid, Repository, Signature, Time};
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Commit times, a minute apart so commits sort in the order they are made.
+static CLOCK: AtomicI64 = AtomicI64::new(1_700_000_000);
+
+/// A repository in the temp dir, removed again when dropped.
+pub struct TestRepo {
+    repo: Repository,
+    dir: PathBuf,
+}
+
+impl Deref for TestRepo {
+    type Target = Repository;
+    fn deref(&self) -> &Repository { &self.repo }
+}
+
+impl DerefMut for TestRepo {
+    fn deref_mut(&mut self) -> &mut Repository { &mut self.repo }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.dir); }
+}
+
+/// A fresh repository in the temp dir, named after the test.
+pub fn test_repo(name: &str) -> TestRepo {
+    let dir = std::env::temp_dir().join(format!("tigrs-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    TestRepo { repo: Repository::init(&dir).unwrap(), dir }
+}
+
+/// Commit a tree holding `files` (path and content, top level only) on top
+/// of `parents`, without touching any ref.
+pub fn commit(repo: &Repository, message: &str, parents: &[Oid], files: &[(&str, &str)]) -> Oid {
+    commit_at(repo, CLOCK.fetch_add(60, Ordering::Relaxed), message, parents, files)
+}
+
+/// [`commit`] with the author and committer time set to `time`.
+pub fn commit_at(repo: &Repository, time: i64, message: &str, parents: &[Oid], files: &[(&str, &str)]) -> Oid {
+    let sig = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+    let mut tree = repo.treebuilder(None).unwrap();
+    for (path, content) in files {
+        tree.insert(path, repo.blob(content.as_bytes()).unwrap(), 0o100644).unwrap();
+    }
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let parents: Vec<_> = parents.iter().map(|id| repo.find_commit(*id).unwrap()).collect();
+    let parents: Vec<_> = parents.iter().collect();
+    repo.commit(None, &sig, &sig, message, &tree, &parents).unwrap()
+}
+
+/// Point branch `name` at `id`.
+pub fn branch(repo: &Repository, name: &str, id: Oid) {
+    repo.branch(name, &repo.find_commit(id).unwrap(), true).unwrap();
+}
+
+/// Point branch `main` at `id`, make it HEAD and check it out, replacing
+/// whatever the work tree and index held.
+pub fn checkout(repo: &Repository, id: Oid) {
+    branch(repo, "main", id);
+    repo.set_head("refs/heads/main").unwrap();
+    repo.checkout_head(Some(CheckoutBuilder::new().force())).unwrap();
+}
+
+/// Poll a background loader until it is done, collecting what it yields.
+pub fn poll_all<L, T>(loader: &mut L, is_loading: impl Fn(&L) -> bool, mut poll: impl FnMut(&mut L) -> Vec<T>) -> Vec<T> {
+    let mut out = Vec::new();
+    while is_loading(loader) {
+        out.extend(poll(loader));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    out
+}
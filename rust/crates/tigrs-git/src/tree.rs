@@ -0,0 +1,172 @@
+use crate::{Ident, Interner};
+use anyhow::Result;
+use git2::{ObjectType, Oid, Repository, Sort, Tree};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub name: String,
+    pub id: Oid,
+    /// Git file mode, e.g. `0o100644`.
+    pub mode: i32,
+    pub kind: Option<ObjectType>,
+    /// Blob size in bytes; `None` for trees and submodules.
+    pub size: Option<usize>,
+}
+
+impl TreeEntry {
+    pub fn is_dir(&self) -> bool { self.kind == Some(ObjectType::Tree) }
+
+    /// `ls -l` style mode string, as in tig's `mode` column.
+    pub fn mode_str(&self) -> &'static str {
+        match self.mode {
+            0o040000 => "drwxr-xr-x",
+            0o100755 => "-rwxr-xr-x",
+            0o120000 => "lrwxrwxrwx",
+            0o160000 => "m---------",
+            _ => "-rw-r--r--",
+        }
+    }
+}
+
+fn subtree<'r>(repo: &'r Repository, commit: Oid, dir: &str) -> Result<Tree<'r>> {
+    let root = repo.find_commit(commit)?.tree()?;
+    let dir = dir.trim_end_matches('/');
+    if dir.is_empty() { return Ok(root); }
+    Ok(root.get_path(Path::new(dir))?.to_object(repo)?.peel_to_tree()?)
+}
+
+/// Entries of directory `dir` (empty or ending in `/`) at `commit`,
+/// directories first, then by name.
+pub fn tree_entries(repo: &Repository, commit: Oid, dir: &str) -> Result<Vec<TreeEntry>> {
+    let tree = subtree(repo, commit, dir)?;
+    // Only the object header, not the whole blob, is read for the size.
+    let odb = repo.odb()?;
+    let mut out: Vec<TreeEntry> = tree.iter().map(|e| {
+        let kind = e.kind();
+        let size = if kind == Some(ObjectType::Blob) { odb.read_header(e.id()).ok().map(|(size, _)| size) } else { None };
+        TreeEntry { name: e.name().unwrap_or("").to_string(), id: e.id(), mode: e.filemode(), kind, size }
+    }).collect();
+    out.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| a.name.cmp(&b.name)));
+    Ok(out)
+}
+
+/// The commit that last changed a tree entry.
+#[derive(Debug, Clone)]
+pub struct TreeCommit {
+    pub id: Oid,
+    pub author: Ident,
+    pub committer: Ident,
+    /// Author time.
+    pub time: i64,
+}
+
+/// Finds the last commit touching each entry of a directory on a
+/// background thread, like tig reading `git log --raw -- <dir>`.
+pub struct TreeHistoryLoader {
+    rx: Receiver<(String, TreeCommit)>,
+    stop: Arc<AtomicBool>,
+    loading: bool,
+}
+
+impl TreeHistoryLoader {
+    pub fn spawn(git_dir: PathBuf, commit: Oid, dir: String, names: Vec<String>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        thread::spawn(move || {
+            let _ = (|| -> Result<()> {
+                let repo = Repository::open(&git_dir)?;
+                let mut pending: HashSet<String> = names.into_iter().collect();
+                let mut walk = repo.revwalk()?;
+                walk.push(commit)?;
+                walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+                let mut idents = Interner::default();
+                let entry_id = |tree: &Option<Tree>, name: &str| tree.as_ref().and_then(|t| t.get_name(name).map(|e| e.id()));
+
+                for oid in walk {
+                    if pending.is_empty() || flag.load(Ordering::Relaxed) { break; }
+                    let c = repo.find_commit(oid?)?;
+                    let tree = subtree(&repo, c.id(), &dir).ok();
+                    let parents: Vec<Option<Tree>> = c.parent_ids().map(|p| subtree(&repo, p, &dir).ok()).collect();
+                    let tree_id = tree.as_ref().map(Tree::id);
+                    // Skip commits leaving the directory as one of their parents had it.
+                    if parents.iter().any(|p| p.as_ref().map(Tree::id) == tree_id) { continue; }
+
+                    let changed: Vec<String> = pending.iter()
+                        .filter(|name| {
+                            let id = entry_id(&tree, name);
+                            parents.iter().all(|p| entry_id(p, name) != id)
+                        })
+                        .cloned()
+                        .collect();
+                    if changed.is_empty() { continue; }
+                    let info = TreeCommit {
+                        id: c.id(),
+                        author: idents.ident(&c.author()),
+                        committer: idents.ident(&c.committer()),
+                        time: c.author().when().seconds(),
+                    };
+                    for name in changed {
+                        pending.remove(&name);
+                        if tx.send((name, info.clone())).is_err() { return Ok(()); }
+                    }
+                }
+                Ok(())
+            })();
+        });
+        Self { rx, stop, loading: true }
+    }
+
+    /// Entries resolved since the last call.
+    pub fn poll(&mut self) -> Vec<(String, TreeCommit)> {
+        let mut out = Vec::new();
+        while self.loading {
+            match self.rx.try_recv() {
+                Ok(found) => out.push(found),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.loading = false,
+            }
+        }
+        out
+    }
+
+    pub fn is_loading(&self) -> bool { self.loading }
+}
+
+impl Drop for TreeHistoryLoader {
+    fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::{commit, poll_all, test_repo};
+    use std::collections::HashMap;
+
+    #[test]
+    fn last_commit_of_each_entry() {
+        let repo = test_repo("tree-history");
+        let first = commit(&repo, "first", &[], &[("a", "1\n"), ("b", "1\n")]);
+        let second = commit(&repo, "second", &[first], &[("a", "2\n"), ("b", "1\n")]);
+        let third = commit(&repo, "third", &[second], &[("a", "2\n"), ("b", "1\n"), ("c", "three\n")]);
+
+        let entries = tree_entries(&repo, third, "").unwrap();
+        let names: Vec<(&str, Option<usize>)> = entries.iter().map(|e| (e.name.as_str(), e.size)).collect();
+        assert_eq!(names, [("a", Some(2)), ("b", Some(2)), ("c", Some(6))]);
+
+        let mut loader = TreeHistoryLoader::spawn(repo.path().to_path_buf(), third, String::new(), vec!["a".into(), "b".into(), "c".into()]);
+        let found: HashMap<_, _> = poll_all(&mut loader, TreeHistoryLoader::is_loading, TreeHistoryLoader::poll)
+            .into_iter().map(|(name, commit)| (name, commit.id)).collect();
+        assert_eq!(found, HashMap::from([("a".to_string(), second), ("b".to_string(), first), ("c".to_string(), third)]));
+    }
+}
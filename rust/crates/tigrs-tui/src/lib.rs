@@ -1,5 +1,14 @@
-use crossterm::event::Event;
-use ratatui::{layout::Rect, Frame};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Clear, Paragraph},
+    Frame,
+};
+use regex::{Regex, RegexBuilder};
+use std::{fs, path::PathBuf};
+use tigrs_core::{prompt_completions, split_size, CommandVars, ExternalCommand, IgnoreCase, Key, KeyLookup, Keymaps, LineType, Request, Settings};
 
 pub type TuiFrame<'a> = Frame<'a>;
 
@@ -9,46 +18,543 @@ pub enum Transition<S> {
     Back,
     Push(Box<dyn View<S>>),
     Replace(Box<dyn View<S>>),
+    /// Show a view beside the current one, as tig shows a diff beside the
+    /// main view.
+    Split(Box<dyn View<S>>),
+    /// Go back to the view on the stack with the same keymap, or open this
+    /// one when there is none, as tig does when asked for a view it shows.
+    Show(Box<dyn View<S>>),
+    /// Reload the views shown after the view changed the repository, as
+    /// the stage view does to the status list beside it. Views must not
+    /// answer [`Request::Refresh`] with it.
+    Refresh,
 }
 
 pub trait View<S> {
     fn title(&self) -> String;
+    /// Keymap the view's keys are looked up in before `generic`.
+    fn keymap(&self) -> &'static str;
     fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &S);
-    fn on_event(&mut self, ev: &Event, state: &mut S) -> Transition<S>;
+    /// Handle what a key is bound to.
+    fn on_request(&mut self, req: &Request, state: &mut S) -> Transition<S>;
+    /// Whether keys should go to [`View::on_event`] as they are, such as
+    /// while the view shows a prompt or a question.
+    fn wants_input(&self) -> bool { false }
+    /// Handle events that are not looked up in the keymaps: keys while the
+    /// view wants input, and mouse and resize events.
+    fn on_event(&mut self, _ev: &Event, _state: &mut S) -> Transition<S> { Transition::None }
+    /// The text of each line `/` and `?` search, and the line the cursor
+    /// is on; views that return `None` cannot be searched.
+    fn search_lines(&self, _state: &S) -> Option<(Vec<String>, usize)> { None }
+    /// How many lines [`View::search_lines`] returns, or `None` when the
+    /// view cannot be searched; views override it to count them without
+    /// building them.
+    fn line_count(&self, state: &S) -> Option<usize> { self.search_lines(state).map(|(lines, _)| lines.len()) }
+    /// The line of [`View::search_lines`] the cursor is on, for `%(text)`;
+    /// views override it to build only that line.
+    fn cursor_text(&self, state: &S) -> Option<String> { self.search_lines(state).and_then(|(lines, cursor)| lines.into_iter().nth(cursor)) }
+    /// Move the cursor to a line a search found.
+    fn select_line(&mut self, _line: usize, _state: &mut S) {}
+    /// Whether Enter on the current line opens a view beside this one that
+    /// should follow the cursor, as with tig's `send-child-enter`.
+    fn sends_child_enter(&self) -> bool { false }
+    /// Set the `%(name)` variables of external commands from the selected line.
+    fn selected_vars(&self, _vars: &mut CommandVars, _state: &S) {}
+}
+
+/// What is left to show of an external command that ran.
+pub enum RunOutput<S> {
+    None,
+    /// The command runs once the views are suspended, or in the background;
+    /// the views are to be refreshed once it is done, see [`Router::refresh`].
+    Deferred,
+    /// The first line of its output, for `+` commands.
+    Echo(String),
+    /// A view of its output, for `:!` commands.
+    View(Box<dyn View<S>>),
+}
+
+/// The last `/` or `?` search; one for all views, as in tig.
+#[derive(Default)]
+pub struct Search {
+    pub pattern: String,
+    regex: Option<Regex>,
+}
+
+impl Search {
+    /// Start searching for `pattern`; on error there is no search.
+    pub fn set(&mut self, pattern: &str, ignore_case: IgnoreCase) -> Result<(), String> {
+        self.pattern = pattern.to_string();
+        self.regex = None;
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case.applies_to(pattern))
+            .build()
+            .map_err(|e| format!("Search failed: {}", e.to_string().lines().last().unwrap_or_default().trim()))?;
+        self.regex = Some(regex);
+        Ok(())
+    }
+
+    pub fn regex(&self) -> Option<&Regex> { self.regex.as_ref() }
+
+    /// Patch `style` onto the parts of `line` that match the search.
+    pub fn highlight(&self, line: Line<'static>, style: Style) -> Line<'static> {
+        let Some(regex) = &self.regex else { return line };
+        let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+        let matches: Vec<_> = regex.find_iter(&text).filter(|m| !m.is_empty()).map(|m| m.range()).collect();
+        if matches.is_empty() { return line; }
+        let mut spans = Vec::new();
+        let mut start = 0;
+        for span in &line.spans {
+            let end = start + span.content.len();
+            let mut pos = start;
+            for m in matches.iter().filter(|m| m.start < end && m.end > start) {
+                let (from, to) = (m.start.max(start), m.end.min(end));
+                if from > pos { spans.push(Span::styled(text[pos..from].to_string(), span.style)); }
+                spans.push(Span::styled(text[from..to].to_string(), span.style.patch(style)));
+                pos = to;
+            }
+            if pos < end { spans.push(Span::styled(text[pos..end].to_string(), span.style)); }
+            start = end;
+        }
+        Line { spans, ..line }
+    }
+}
+
+/// What the router needs from the app state: the options it follows, the
+/// search, and the styles of what it draws.
+pub trait RouterState: Sized {
+    fn settings(&self) -> &Settings;
+    fn search(&self) -> &Search;
+    fn search_mut(&mut self) -> &mut Search;
+    fn style(&self, view: &str, ty: LineType) -> Style;
+    /// The `%(name)` variables that do not depend on the view, such as
+    /// `%(repo:head)`.
+    fn command_vars(&self) -> CommandVars;
+    /// Run an external command, given with its variables expanded.
+    fn run(&mut self, command: &ExternalCommand, argv: Vec<String>) -> Result<RunOutput<Self>, String>;
 }
 
 pub struct Router<S> {
     stack: Vec<Box<dyn View<S>>>,
+    /// View shown beside the top of the stack.
+    child: Option<Box<dyn View<S>>>,
+    /// Whether keys go to `child` rather than the top of the stack.
+    focus_child: bool,
+    keymaps: Keymaps,
+    /// Keys read so far of a binding that takes several.
+    pending: Vec<Key>,
+    /// What the status line is reading, if anything.
+    input: Option<Input>,
+    history: History,
+    /// Search result or error shown in the status line until the next key.
+    message: Option<String>,
+    /// How many views at the bottom of the stack were hidden during a
+    /// refresh, and reload once shown again.
+    stale: usize,
+}
+
+enum Input {
+    /// The `/` or `?` prompt, and whether it searches backwards.
+    Search(Prompt, bool),
+    Command(CommandPrompt),
+    /// A `%(prompt)` of an external command.
+    Argument(Prompt, PendingRun),
+    /// The question of a `?` external command, with its expanded arguments.
+    Confirm(Confirm<(ExternalCommand, Vec<String>)>),
+}
+
+/// An external command waiting for the answers to its `%(prompt)`s.
+struct PendingRun {
+    command: ExternalCommand,
+    vars: CommandVars,
+    answers: Vec<String>,
 }
 
-impl<S> Router<S> {
-    pub fn new(root: Box<dyn View<S>>) -> Self {
-        Self { stack: vec![root] }
+/// The keys tigrc names a key press with; Alt combinations are read as
+/// `<Esc>` followed by the key, as terminals send them.
+pub fn keys_of(key: &KeyEvent) -> Vec<Key> {
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    let k = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => Key::Ctrl(c.to_ascii_lowercase()),
+        KeyCode::Char(c) => Key::Char(c),
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::BackTab => Key::BackTab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete if shift => Key::ShiftDelete,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Home if shift => Key::ShiftHome,
+        KeyCode::Home => Key::Home,
+        KeyCode::End if shift => Key::ShiftEnd,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Up if shift => Key::ScrollBack,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down if shift => Key::ScrollFwd,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left if shift => Key::ShiftLeft,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right if shift => Key::ShiftRight,
+        KeyCode::Right => Key::Right,
+        KeyCode::F(n) => Key::F(n),
+        _ => return Vec::new(),
+    };
+    if key.modifiers.contains(KeyModifiers::ALT) { vec![Key::Esc, k] } else { vec![k] }
+}
+
+impl<S: RouterState> Router<S> {
+    pub fn new(root: Box<dyn View<S>>, keymaps: Keymaps, history: History) -> Self {
+        Self {
+            stack: vec![root], child: None, focus_child: false, keymaps, pending: Vec::new(), input: None, history,
+            message: None, stale: 0,
+        }
     }
 
-    pub fn current(&self) -> Option<&Box<dyn View<S>>> { self.stack.last() }
-    pub fn current_mut(&mut self) -> Option<&mut Box<dyn View<S>>> { self.stack.last_mut() }
+    /// The view keys go to.
+    pub fn current(&self) -> Option<&dyn View<S>> {
+        match &self.child {
+            Some(child) if self.focus_child => Some(child.as_ref()),
+            _ => self.stack.last().map(|v| v.as_ref()),
+        }
+    }
+    pub fn current_mut(&mut self) -> Option<&mut Box<dyn View<S>>> {
+        match &mut self.child {
+            Some(child) if self.focus_child => Some(child),
+            _ => self.stack.last_mut(),
+        }
+    }
 
     pub fn render(&mut self, f: &mut TuiFrame<'_>, area: Rect, state: &S) {
-        if let Some(view) = self.current_mut() {
-            view.render(f, area, state);
-        }
+        let Some(view) = self.stack.last_mut() else { return };
+        let keymap = match &mut self.child {
+            None => { view.render(f, area, state); view.keymap() }
+            Some(child) => {
+                let (parent_area, child_area) = split_areas(area, state.settings());
+                view.render(f, parent_area, state);
+                child.render(f, child_area, state);
+                // Blur the title of the view keys do not go to.
+                let (blurred, area, focused) = if self.focus_child {
+                    (view.keymap(), parent_area, child.keymap())
+                } else {
+                    (child.keymap(), child_area, view.keymap())
+                };
+                let title = Rect { height: area.height.min(1), ..area };
+                f.buffer_mut().set_style(title, Style::reset().patch(state.style(blurred, LineType::TitleBlur)));
+                focused
+            }
+        };
+        let status = match (&self.input, &self.message) {
+            (Some(Input::Search(prompt, _)), _) => Span::styled(prompt.prompt(), state.style(keymap, LineType::Prompt)),
+            (Some(Input::Command(prompt)), _) => Span::styled(prompt.prompt(), state.style(keymap, LineType::Prompt)),
+            (Some(Input::Argument(prompt, _)), _) => Span::styled(prompt.prompt(), state.style(keymap, LineType::Prompt)),
+            (Some(Input::Confirm(confirm)), _) => Span::styled(confirm.prompt(), state.style(keymap, LineType::Prompt)),
+            (None, Some(message)) => Span::styled(message.clone(), state.style(keymap, LineType::Status)),
+            (None, None) => return,
+        };
+        let line = Rect { y: area.bottom().saturating_sub(1), height: area.height.min(1), ..area };
+        f.render_widget(Clear, line);
+        f.render_widget(Paragraph::new(Line::from(status)), line);
     }
 
     pub fn handle_event(&mut self, ev: &Event, state: &mut S) -> bool {
-        let transition = match self.current_mut() {
-            Some(view) => view.on_event(ev, state),
-            None => Transition::Quit,
+        if matches!(ev, Event::Key(_)) { self.message = None; }
+        if let Some(input) = self.input.take() {
+            match input {
+                Input::Command(mut prompt) => match prompt.handle(ev, &self.history) {
+                    PromptResult::Pending => self.input = Some(Input::Command(prompt)),
+                    PromptResult::Cancel => {}
+                    PromptResult::Submit(text) => {
+                        self.history.add(&text);
+                        match Request::from_prompt(&text) {
+                            Ok(req) => return self.dispatch(vec![req], state),
+                            Err(e) => self.message = Some(e.to_string()),
+                        }
+                    }
+                },
+                Input::Argument(mut prompt, mut run) => match prompt.handle(ev) {
+                    PromptResult::Pending => self.input = Some(Input::Argument(prompt, run)),
+                    PromptResult::Cancel => {}
+                    PromptResult::Submit(text) => {
+                        run.answers.push(text);
+                        return self.prepare_run(run, state);
+                    }
+                },
+                Input::Confirm(confirm) => match confirm.answer(ev) {
+                    Answer::Pending(confirm) => self.input = Some(Input::Confirm(confirm)),
+                    Answer::No => {}
+                    Answer::Yes((command, argv)) => return self.run(command, argv, state),
+                },
+                Input::Search(mut prompt, backward) => match prompt.handle(ev) {
+                    PromptResult::Pending => self.input = Some(Input::Search(prompt, backward)),
+                    PromptResult::Cancel => {}
+                    // An empty search repeats the last one.
+                    PromptResult::Submit(text) if text.is_empty() => {
+                        if state.search().regex().is_some() { self.find(backward, state); }
+                    }
+                    PromptResult::Submit(text) => {
+                        let ignore_case = state.settings().ignore_case;
+                        match state.search_mut().set(&text, ignore_case) {
+                            Ok(()) => self.find(backward, state),
+                            Err(e) => self.message = Some(e),
+                        }
+                    }
+                },
+            }
+            return false;
+        }
+        let from_child = self.child.is_some() && self.focus_child;
+        let Some(view) = self.current_mut() else { return true };
+        let key = match ev {
+            Event::Key(key) if !view.wants_input() => key,
+            _ => {
+                let transition = view.on_event(ev, state);
+                self.pending.clear();
+                return self.apply(transition, from_child, state);
+            }
+        };
+        let keymap = view.keymap();
+        let mut requests = Vec::new();
+        for k in keys_of(key) {
+            self.pending.push(k);
+            loop {
+                match self.keymaps.lookup(keymap, &self.pending) {
+                    KeyLookup::Request(req) => { requests.push(req); self.pending.clear(); }
+                    // Wait for the rest of the sequence.
+                    KeyLookup::Prefix(_) => {}
+                    KeyLookup::Unbound if self.pending.len() > 1 => {
+                        // The last key does not continue the sequence: run
+                        // what the keys before it are bound to, then read it
+                        // on its own.
+                        let last = self.pending.pop().into_iter().collect();
+                        if let KeyLookup::Prefix(Some(req)) = self.keymaps.lookup(keymap, &self.pending) { requests.push(req); }
+                        self.pending = last;
+                        continue;
+                    }
+                    KeyLookup::Unbound => self.pending.clear(),
+                }
+                break;
+            }
+        }
+        self.dispatch(requests, state)
+    }
+
+    /// Run requests read from keys or the `:` prompt; returns whether to quit.
+    fn dispatch(&mut self, requests: Vec<Request>, state: &mut S) -> bool {
+        for req in requests.into_iter().filter(|req| *req != Request::None) {
+            let split = self.child.is_some();
+            let from_child = split && self.focus_child;
+            let Some(view) = self.current_mut() else { return true };
+            match req {
+                Request::Prompt => {
+                    self.input = Some(Input::Command(CommandPrompt::new()));
+                    break;
+                }
+                Request::GotoLine(lineno) => {
+                    match view.line_count(state) {
+                        Some(count) if count > 0 => view.select_line(lineno.saturating_sub(1).min(count - 1), state),
+                        Some(_) => {}
+                        None => self.message = Some("Line numbers are not supported in this view".into()),
+                    }
+                    self.follow_parent(state);
+                    continue;
+                }
+                Request::Search | Request::SearchBack if view.line_count(state).is_some() => {
+                    let backward = req == Request::SearchBack;
+                    self.input = Some(Input::Search(Prompt::new(if backward { "?" } else { "/" }), backward));
+                    break;
+                }
+                Request::FindNext | Request::FindPrev if view.line_count(state).is_some() => {
+                    self.find(req == Request::FindPrev, state);
+                    continue;
+                }
+                Request::Run(command) => {
+                    let mut vars = state.command_vars();
+                    if let Some(text) = view.cursor_text(state) { vars.set("text", text); }
+                    view.selected_vars(&mut vars, state);
+                    return self.prepare_run(PendingRun { command, vars, answers: Vec::new() }, state);
+                }
+                Request::ViewNext if split => {
+                    self.focus_child = !self.focus_child;
+                    continue;
+                }
+                Request::Maximize => {
+                    if split { self.maximize(from_child); }
+                    continue;
+                }
+                // As in tig, the child moves the cursor of the view it was
+                // opened from and shows what is on the new line.
+                Request::Next | Request::Previous if from_child => {
+                    let Some(parent) = self.stack.last_mut() else { return true };
+                    parent.on_request(if req == Request::Next { &Request::MoveDown } else { &Request::MoveUp }, state);
+                    self.enter_parent(state);
+                    continue;
+                }
+                _ => {}
+            }
+            let moved = is_move(&req);
+            let transition = view.on_request(&req, state);
+            let stop = !matches!(transition, Transition::None);
+            if self.apply(transition, from_child, state) { return true; }
+            if moved { self.follow_parent(state); }
+            if stop { break; }
+        }
+        false
+    }
+
+    /// Ask for the next `%(prompt)` of an external command, or its `?`
+    /// question, then run it; returns whether to quit.
+    fn prepare_run(&mut self, run: PendingRun, state: &mut S) -> bool {
+        if let Some(message) = run.command.prompts().get(run.answers.len()) {
+            self.input = Some(Input::Argument(Prompt::new(message.clone()), run));
+            return false;
+        }
+        let argv = match run.command.expand(&run.vars, &run.answers) {
+            Ok(argv) => argv,
+            Err(e) => { self.message = Some(e.to_string()); return false; }
         };
+        if run.command.confirm {
+            let quoted: Vec<String> = argv.iter().map(|a| if a.contains(' ') { format!("\"{a}\"") } else { a.clone() }).collect();
+            let question = format!("Run `{}`{}?", quoted.join(" "), if run.command.exit { " and exit" } else { "" });
+            self.input = Some(Input::Confirm(Confirm::new(question, (run.command, argv))));
+            return false;
+        }
+        self.run(run.command, argv, state)
+    }
+
+    /// Run an external command and show what it left; returns whether to quit.
+    fn run(&mut self, command: ExternalCommand, argv: Vec<String>, state: &mut S) -> bool {
+        let output = state.run(&command, argv);
+        if command.exit { return true; }
+        match output {
+            Ok(RunOutput::None) => return self.refresh(state),
+            Ok(RunOutput::Deferred) => {}
+            Ok(RunOutput::Echo(line)) => {
+                self.message = Some(line).filter(|l| !l.is_empty());
+                return self.refresh(state);
+            }
+            Ok(RunOutput::View(v)) => {
+                let from_child = self.child.is_some() && self.focus_child;
+                return self.apply(Transition::Push(v), from_child, state);
+            }
+            Err(e) => self.message = Some(e),
+        }
+        false
+    }
+
+    /// Have the views shown reload what an external command may have
+    /// changed; returns whether to quit.
+    pub fn refresh(&mut self, state: &mut S) -> bool {
+        if let Some(child) = self.child.as_mut() {
+            let transition = child.on_request(&Request::Refresh, state);
+            if self.apply(transition, true, state) { return true; }
+        }
+        self.stale = self.stack.len().saturating_sub(1);
+        let Some(view) = self.stack.last_mut() else { return true };
+        let transition = view.on_request(&Request::Refresh, state);
+        self.apply(transition, false, state)
+    }
+
+    /// With `send-child-enter`, make the child follow the cursor of the
+    /// focused parent.
+    fn follow_parent(&mut self, state: &mut S) {
+        if self.child.is_some() && !self.focus_child && state.settings().send_child_enter { self.enter_parent(state); }
+    }
+
+    /// Show in the child what Enter in the parent opens.
+    fn enter_parent(&mut self, state: &mut S) {
+        let Some(parent) = self.stack.last_mut() else { return };
+        if !parent.sends_child_enter() { return; }
+        if let Transition::Split(v) = parent.on_request(&Request::Enter, state) { self.child = Some(v); }
+    }
+
+    /// Show only the child when it has the focus, else only the parent.
+    fn maximize(&mut self, child: bool) {
+        if let Some(v) = self.child.take() {
+            if child { self.stack.push(v); }
+        }
+        self.focus_child = false;
+    }
+
+    /// Move the current view's cursor to the next line matching the search,
+    /// wrapping around its end when `wrap-search` is set.
+    fn find(&mut self, backward: bool, state: &mut S) {
+        let Some(regex) = state.search().regex() else {
+            self.message = Some("No previous search".into());
+            return;
+        };
+        let Some((lines, cursor)) = self.current().and_then(|view| view.search_lines(state)) else { return };
+        let matches: Vec<usize> = lines.iter().enumerate().filter(|(_, l)| regex.is_match(l)).map(|(i, _)| i).collect();
+        let wrap = state.settings().wrap_search;
+        let found = if backward {
+            matches.iter().rposition(|&i| i < cursor).or_else(|| wrap.then(|| matches.len().checked_sub(1)).flatten())
+        } else {
+            matches.iter().position(|&i| i > cursor).or_else(|| (wrap && !matches.is_empty()).then_some(0))
+        };
+        let pattern = &state.search().pattern;
+        self.message = Some(match found {
+            Some(n) => format!("Line {} matches '{pattern}' ({} of {})", matches[n] + 1, n + 1, matches.len()),
+            None => format!("No match found for '{pattern}'"),
+        });
+        if let (Some(n), Some(view)) = (found, self.current_mut()) {
+            view.select_line(matches[n], state);
+            self.follow_parent(state);
+        }
+    }
+
+    /// Carry out a transition of the child when `from_child`, else of the
+    /// top of the stack; returns whether to quit.
+    fn apply(&mut self, transition: Transition<S>, from_child: bool, state: &mut S) -> bool {
         match transition {
             Transition::None => false,
+            Transition::Refresh => self.refresh(state),
             Transition::Quit => true,
-            Transition::Back => { self.pop(); false }
-            Transition::Push(v) => { self.push(v); false }
-            Transition::Replace(v) => { self.replace(v); false }
+            Transition::Back if from_child => { self.maximize(false); false }
+            // Closing the last view quits, as in tig.
+            Transition::Back if self.stack.len() <= 1 => true,
+            Transition::Back => {
+                self.maximize(false);
+                self.pop();
+                self.reload_stale(state)
+            }
+            Transition::Push(v) => { self.maximize(from_child); self.push(v); false }
+            Transition::Show(v) => {
+                self.maximize(from_child);
+                match self.stack.iter().rposition(|view| view.keymap() == v.keymap()) {
+                    Some(i) => self.stack.truncate(i + 1),
+                    None => self.push(v),
+                }
+                self.reload_stale(state)
+            }
+            Transition::Replace(v) if from_child => { self.child = Some(v); false }
+            Transition::Replace(v) => { self.maximize(false); self.replace(v); false }
+            Transition::Split(v) => {
+                if from_child { self.maximize(true); }
+                self.child = Some(v);
+                self.focus_child = state.settings().focus_child;
+                false
+            }
         }
     }
 
+    /// Reload the top of the stack if it was hidden during a refresh;
+    /// returns whether to quit.
+    fn reload_stale(&mut self, state: &mut S) -> bool {
+        if self.stale < self.stack.len() { return false; }
+        self.stale = self.stack.len() - 1;
+        let Some(view) = self.stack.last_mut() else { return true };
+        let transition = view.on_request(&Request::Refresh, state);
+        self.apply(transition, false, state)
+    }
+
+    /// Show `message` in the status line until the next key.
+    pub fn report(&mut self, message: impl Into<String>) { self.message = Some(message.into()); }
+
+    pub fn keymaps(&self) -> &Keymaps { &self.keymaps }
+    pub fn keymaps_mut(&mut self) -> &mut Keymaps { &mut self.keymaps }
+
     pub fn push(&mut self, v: Box<dyn View<S>>) { self.stack.push(v); }
     pub fn pop(&mut self) { if self.stack.len() > 1 { self.stack.pop(); } }
     pub fn replace(&mut self, v: Box<dyn View<S>>) {
@@ -56,3 +562,563 @@ impl<S> Router<S> {
         self.stack.push(v);
     }
 }
+
+fn is_move(req: &Request) -> bool {
+    matches!(req,
+        Request::MoveUp | Request::MoveDown | Request::MovePageUp | Request::MovePageDown | Request::MoveHalfPageUp
+            | Request::MoveHalfPageDown | Request::MoveFirstLine | Request::MoveLastLine)
+}
+
+/// Areas of the parent and child of a split: side by side or one above the
+/// other as `vertical-split` says, the child sized by `split-view-width` or
+/// `split-view-height`.
+fn split_areas(area: Rect, settings: &Settings) -> (Rect, Rect) {
+    let (direction, total, size) = if settings.vertical_split.is_vertical(area.width, area.height) {
+        (Direction::Horizontal, area.width, &settings.split_view_width)
+    } else {
+        (Direction::Vertical, area.height, &settings.split_view_height)
+    };
+    // Leave each view room for its borders and a line or column.
+    let child = split_size(size, total).unwrap_or(total / 2).max(4).min(total.saturating_sub(4));
+    let chunks = Layout::default()
+        .direction(direction)
+        .constraints([Constraint::Length(total - child), Constraint::Length(child)])
+        .split(area);
+    (chunks[0], chunks[1])
+}
+
+/// A yes/no question a view shows in its status line before running `action`.
+pub struct Confirm<A> {
+    pub question: String,
+    pub action: A,
+}
+
+impl<A> Confirm<A> {
+    pub fn new(question: impl Into<String>, action: A) -> Self {
+        Self { question: question.into(), action }
+    }
+
+    pub fn prompt(&self) -> String { format!("{} [y/N]", self.question) }
+
+    /// Resolve the question with the next key press: the action on `y`,
+    /// no on any other key. Mouse and resize events leave it open.
+    pub fn answer(self, ev: &Event) -> Answer<A> {
+        match ev {
+            Event::Key(key) if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) => Answer::Yes(self.action),
+            Event::Key(_) => Answer::No,
+            _ => Answer::Pending(self),
+        }
+    }
+}
+
+/// Outcome of feeding an event to a [`Confirm`].
+pub enum Answer<A> {
+    Pending(Confirm<A>),
+    No,
+    Yes(A),
+}
+
+/// Outcome of feeding a key to a [`Prompt`].
+pub enum PromptResult {
+    Pending,
+    Cancel,
+    Submit(String),
+}
+
+/// A one-line text input a view shows in its status line.
+pub struct Prompt {
+    pub label: String,
+    pub text: String,
+}
+
+impl Prompt {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self { label: label.into(), text: String::new() }
+    }
+
+    pub fn prompt(&self) -> String { format!("{}{}", self.label, self.text) }
+
+    /// Edit the text; Enter submits it and Esc cancels, as does
+    /// Backspace on an empty line.
+    pub fn handle(&mut self, ev: &Event) -> PromptResult {
+        let Event::Key(key) = ev else { return PromptResult::Pending };
+        match key.code {
+            KeyCode::Enter => PromptResult::Submit(std::mem::take(&mut self.text)),
+            KeyCode::Esc => PromptResult::Cancel,
+            KeyCode::Backspace if self.text.is_empty() => PromptResult::Cancel,
+            KeyCode::Backspace => { self.text.pop(); PromptResult::Pending }
+            KeyCode::Char(c) => { self.text.push(c); PromptResult::Pending }
+            _ => PromptResult::Pending,
+        }
+    }
+}
+
+/// Lines entered at the `:` prompt, oldest first, kept in a file between runs.
+pub struct History {
+    lines: Vec<String>,
+    /// Most lines kept; 0 keeps none.
+    size: usize,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Read the history kept at `path`, if any, keeping its last `size` lines.
+    pub fn load(path: Option<PathBuf>, size: usize) -> Self {
+        let mut history = Self { lines: Vec::new(), size, path };
+        if size == 0 { return history; }
+        if let Some(text) = history.path.as_ref().and_then(|p| fs::read_to_string(p).ok()) {
+            for line in text.lines() { history.push(line); }
+        }
+        history
+    }
+
+    pub fn lines(&self) -> &[String] { &self.lines }
+
+    /// Add a line, dropping an earlier copy of it, and save the history.
+    pub fn add(&mut self, line: &str) {
+        if self.size == 0 || line.trim().is_empty() { return; }
+        self.push(line);
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() { let _ = fs::create_dir_all(dir); }
+            let _ = fs::write(path, self.lines.iter().map(|l| format!("{l}\n")).collect::<String>());
+        }
+    }
+
+    fn push(&mut self, line: &str) {
+        self.lines.retain(|l| l != line);
+        self.lines.push(line.to_string());
+        let excess = self.lines.len().saturating_sub(self.size);
+        self.lines.drain(..excess);
+    }
+}
+
+/// The `:` prompt: a [`Prompt`] where Up and Down go through the history
+/// and Tab completes the last word, cycling through the choices.
+pub struct CommandPrompt {
+    prompt: Prompt,
+    /// Index of the history line shown while going through it.
+    browsing: Option<usize>,
+    /// The text before the word being completed, the choices and the one shown.
+    completion: Option<(String, Vec<String>, usize)>,
+}
+
+impl Default for CommandPrompt {
+    fn default() -> Self { Self::new() }
+}
+
+impl CommandPrompt {
+    pub fn new() -> Self { Self { prompt: Prompt::new(":"), browsing: None, completion: None } }
+
+    pub fn prompt(&self) -> String { self.prompt.prompt() }
+
+    pub fn handle(&mut self, ev: &Event, history: &History) -> PromptResult {
+        let Event::Key(key) = ev else { return PromptResult::Pending };
+        let text = &mut self.prompt.text;
+        match key.code {
+            KeyCode::Tab => {
+                if let Some((base, choices, i)) = self.completion.as_mut() {
+                    *i = (*i + 1) % choices.len();
+                    *text = format!("{base}{}", choices[*i]);
+                    return PromptResult::Pending;
+                }
+                let choices = prompt_completions(text);
+                let base = text.trim_end_matches(|c: char| !c.is_whitespace()).to_string();
+                match choices.len() {
+                    0 => {}
+                    1 => *text = format!("{base}{} ", choices[0]),
+                    _ => {
+                        *text = format!("{base}{}", choices[0]);
+                        self.completion = Some((base, choices, 0));
+                    }
+                }
+                return PromptResult::Pending;
+            }
+            KeyCode::Up => {
+                let i = self.browsing.unwrap_or(history.lines().len());
+                if let Some(i) = i.checked_sub(1) {
+                    *text = history.lines()[i].clone();
+                    self.browsing = Some(i);
+                }
+                return PromptResult::Pending;
+            }
+            KeyCode::Down => {
+                if let Some(i) = self.browsing {
+                    self.browsing = (i + 1 < history.lines().len()).then_some(i + 1);
+                    *text = self.browsing.map(|i| history.lines()[i].clone()).unwrap_or_default();
+                }
+                return PromptResult::Pending;
+            }
+            _ => {}
+        }
+        self.completion = None;
+        self.prompt.handle(ev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Color;
+    use std::{cell::Cell, rc::Rc};
+    use tigrs_core::VerticalSplit;
+
+    #[derive(Default)]
+    struct TestState {
+        settings: Settings,
+        search: Search,
+        /// Commands run, with their variables expanded.
+        ran: Vec<Vec<String>>,
+    }
+
+    impl RouterState for TestState {
+        fn settings(&self) -> &Settings { &self.settings }
+        fn search(&self) -> &Search { &self.search }
+        fn search_mut(&mut self) -> &mut Search { &mut self.search }
+        fn style(&self, _view: &str, _ty: LineType) -> Style { Style::default() }
+        fn command_vars(&self) -> CommandVars { CommandVars::default() }
+        fn run(&mut self, _command: &ExternalCommand, argv: Vec<String>) -> Result<RunOutput<Self>, String> {
+            self.ran.push(argv);
+            Ok(RunOutput::None)
+        }
+    }
+
+    /// Lines with a cursor; Enter shows the line the cursor is on beside them.
+    struct Lines {
+        lines: Vec<String>,
+        cursor: usize,
+        /// How many times the lines were built for searching.
+        built: Rc<Cell<usize>>,
+        keymap: &'static str,
+    }
+
+    fn lines(lines: &[&str]) -> Lines { Lines { lines: lines.iter().map(|l| l.to_string()).collect(), cursor: 0, built: Rc::default(), keymap: "main" } }
+
+    impl View<TestState> for Lines {
+        fn title(&self) -> String { self.lines.join(" ") }
+        fn keymap(&self) -> &'static str { self.keymap }
+        fn render(&mut self, _f: &mut TuiFrame<'_>, _area: Rect, _state: &TestState) {}
+        fn on_request(&mut self, req: &Request, _state: &mut TestState) -> Transition<TestState> {
+            match req {
+                Request::MoveDown => self.cursor = (self.cursor + 1).min(self.lines.len() - 1),
+                Request::MoveUp => self.cursor = self.cursor.saturating_sub(1),
+                Request::Enter => return Transition::Split(Box::new(lines(&[&self.lines[self.cursor]]))),
+                Request::ViewClose => return Transition::Back,
+                _ => {}
+            }
+            Transition::None
+        }
+        fn search_lines(&self, _state: &TestState) -> Option<(Vec<String>, usize)> {
+            self.built.set(self.built.get() + 1);
+            Some((self.lines.clone(), self.cursor))
+        }
+        fn line_count(&self, _state: &TestState) -> Option<usize> { Some(self.lines.len()) }
+        fn cursor_text(&self, _state: &TestState) -> Option<String> { self.lines.get(self.cursor).cloned() }
+        fn select_line(&mut self, line: usize, _state: &mut TestState) { self.cursor = line; }
+        fn sends_child_enter(&self) -> bool { true }
+    }
+
+    fn router(root: &[&str], history: History) -> Router<TestState> { Router::new(Box::new(lines(root)), Keymaps::default(), history) }
+
+    fn press(router: &mut Router<TestState>, state: &mut TestState, code: KeyCode) -> bool {
+        router.handle_event(&Event::Key(KeyEvent::new(code, KeyModifiers::NONE)), state)
+    }
+
+    /// Press the keys of `text`, a character each.
+    fn type_text(router: &mut Router<TestState>, state: &mut TestState, text: &str) {
+        for c in text.chars() { press(router, state, KeyCode::Char(c)); }
+    }
+
+    fn cursor(router: &Router<TestState>, state: &TestState) -> usize { router.current().unwrap().search_lines(state).unwrap().1 }
+
+    #[test]
+    fn highlight_splits_styled_spans() {
+        let mut search = Search::default();
+        let red = Style::default().fg(Color::Red);
+        let found = Style::default().bg(Color::Yellow);
+        let line = Line::from(vec![Span::styled("héllo wö", red), Span::raw("rld, wörld")]);
+        assert_eq!(search.highlight(line.clone(), found), line);
+
+        search.set("wör", IgnoreCase::No).unwrap();
+        let spans: Vec<(String, Style)> = search.highlight(line.clone(), found).spans.into_iter().map(|s| (s.content.into_owned(), s.style)).collect();
+        assert_eq!(spans, [
+            ("héllo ".to_string(), red), ("wö".to_string(), red.patch(found)), ("r".to_string(), found),
+            ("ld, ".to_string(), Style::default()), ("wör".to_string(), found), ("ld".to_string(), Style::default()),
+        ]);
+        // Empty matches highlight nothing.
+        search.set("x*", IgnoreCase::No).unwrap();
+        assert_eq!(search.highlight(line, found).spans.len(), 2);
+    }
+
+    #[test]
+    fn smart_case() {
+        let mut search = Search::default();
+        let matches = |search: &mut Search, pattern: &str, ignore_case: IgnoreCase, text: &str| {
+            search.set(pattern, ignore_case).unwrap();
+            search.regex().unwrap().is_match(text)
+        };
+        assert!(matches(&mut search, "readme", IgnoreCase::SmartCase, "README"));
+        assert!(!matches(&mut search, "ReadMe", IgnoreCase::SmartCase, "README"));
+        assert!(matches(&mut search, "ReadMe", IgnoreCase::Yes, "README"));
+        assert!(!matches(&mut search, "readme", IgnoreCase::No, "README"));
+        assert!(search.set("(", IgnoreCase::No).unwrap_err().starts_with("Search failed: "));
+        assert!(search.regex().is_none());
+    }
+
+    #[test]
+    fn find_with_and_without_wrap() {
+        let mut state = TestState::default();
+        let mut router = router(&["one", "two", "three", "two again"], History::load(None, 0));
+        press(&mut router, &mut state, KeyCode::Char('n'));
+        assert_eq!(router.message.as_deref(), Some("No previous search"));
+
+        type_text(&mut router, &mut state, "/two");
+        press(&mut router, &mut state, KeyCode::Enter);
+        assert_eq!((cursor(&router, &state), router.message.as_deref()), (1, Some("Line 2 matches 'two' (1 of 2)")));
+        press(&mut router, &mut state, KeyCode::Char('n'));
+        assert_eq!((cursor(&router, &state), router.message.as_deref()), (3, Some("Line 4 matches 'two' (2 of 2)")));
+        press(&mut router, &mut state, KeyCode::Char('n'));
+        assert_eq!(cursor(&router, &state), 1);
+
+        state.settings.wrap_search = false;
+        press(&mut router, &mut state, KeyCode::Char('N'));
+        assert_eq!((cursor(&router, &state), router.message.as_deref()), (1, Some("No match found for 'two'")));
+        type_text(&mut router, &mut state, "/four");
+        press(&mut router, &mut state, KeyCode::Enter);
+        assert_eq!((cursor(&router, &state), router.message.as_deref()), (1, Some("No match found for 'four'")));
+    }
+
+    #[test]
+    fn find_next_and_previous_after_searching_back() {
+        let mut state = TestState::default();
+        let mut router = router(&["one", "two", "three", "two again"], History::load(None, 0));
+        type_text(&mut router, &mut state, "?two");
+        press(&mut router, &mut state, KeyCode::Enter);
+        assert_eq!(cursor(&router, &state), 3);
+        // As in tig, `n` searches down and `N` up whichever way the search went.
+        press(&mut router, &mut state, KeyCode::Char('N'));
+        assert_eq!(cursor(&router, &state), 1);
+        press(&mut router, &mut state, KeyCode::Char('n'));
+        assert_eq!(cursor(&router, &state), 3);
+        // An empty search repeats the last one, in its own direction.
+        type_text(&mut router, &mut state, "?");
+        press(&mut router, &mut state, KeyCode::Enter);
+        assert_eq!(cursor(&router, &state), 1);
+    }
+
+    #[test]
+    fn lines_are_built_only_to_find() {
+        let mut state = TestState::default();
+        let view = lines(&["one", "two", "three"]);
+        let built = view.built.clone();
+        let mut router = Router::new(Box::new(view), Keymaps::default(), History::load(None, 0));
+        type_text(&mut router, &mut state, ":3");
+        press(&mut router, &mut state, KeyCode::Enter);
+        type_text(&mut router, &mut state, "/t");
+        assert_eq!(built.get(), 0);
+        press(&mut router, &mut state, KeyCode::Enter);
+        press(&mut router, &mut state, KeyCode::Char('n'));
+        assert_eq!(built.get(), 2);
+        assert_eq!(cursor(&router, &state), 2);
+    }
+
+    fn prompt_keys(prompt: &mut CommandPrompt, history: &History, codes: &[KeyCode]) -> String {
+        for code in codes { prompt.handle(&Event::Key(KeyEvent::new(*code, KeyModifiers::NONE)), history); }
+        prompt.prompt()
+    }
+
+    #[test]
+    fn complete_command_and_option_names() {
+        let history = History::load(None, 0);
+        let chars = |text: &str| text.chars().map(KeyCode::Char).collect::<Vec<_>>();
+        let mut prompt = CommandPrompt::new();
+        // One choice completes the word and starts the next.
+        assert_eq!(prompt_keys(&mut prompt, &history, &[chars("set wrap-l"), vec![KeyCode::Tab]].concat()), ":set wrap-lines ");
+        // Several are cycled through, until another key is typed.
+        let mut prompt = CommandPrompt::new();
+        assert_eq!(prompt_keys(&mut prompt, &history, &[chars("toggle ignore-"), vec![KeyCode::Tab]].concat()), ":toggle ignore-case");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Tab]), ":toggle ignore-space");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Tab]), ":toggle ignore-case");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Char(' '), KeyCode::Tab]), ":toggle ignore-case ");
+        let mut prompt = CommandPrompt::new();
+        assert_eq!(prompt_keys(&mut prompt, &history, &[chars("vie"), vec![KeyCode::Tab]].concat()), ":view-blame");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Tab]), ":view-blob");
+        let mut prompt = CommandPrompt::new();
+        assert_eq!(prompt_keys(&mut prompt, &history, &[chars("so"), vec![KeyCode::Tab]].concat()), ":source ");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[chars("xyz"), vec![KeyCode::Tab]].concat()), ":source xyz");
+    }
+
+    #[test]
+    fn browse_history() {
+        let mut history = History::load(None, 10);
+        for line in ["set tab-size = 4", "toggle wrap-lines", "goto HEAD"] { history.add(line); }
+        let mut prompt = CommandPrompt::new();
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Up]), ":goto HEAD");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Up, KeyCode::Up]), ":set tab-size = 4");
+        // Up stops at the oldest line, Down goes past the newest to an empty line.
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Up]), ":set tab-size = 4");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Down]), ":toggle wrap-lines");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Down, KeyCode::Down]), ":");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Down]), ":");
+        assert_eq!(prompt_keys(&mut prompt, &history, &[KeyCode::Up]), ":goto HEAD");
+    }
+
+    #[test]
+    fn history_size_bounds_lines() {
+        let mut history = History::load(None, 2);
+        for line in ["a", "b", "  ", "c"] { history.add(line); }
+        assert_eq!(history.lines(), ["b", "c"]);
+        // A repeated line moves to the end rather than being kept twice.
+        history.add("b");
+        assert_eq!(history.lines(), ["c", "b"]);
+        let mut none = History::load(None, 0);
+        none.add("a");
+        assert!(none.lines().is_empty());
+    }
+
+    #[test]
+    fn history_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tig-rs").join("history");
+        let mut state = TestState::default();
+        let mut router = router(&["one", "two"], History::load(Some(path.clone()), 3));
+        for line in ["2", "1", "toggle wrap-lines", "2"] {
+            type_text(&mut router, &mut state, &format!(":{line}"));
+            press(&mut router, &mut state, KeyCode::Enter);
+        }
+        assert_eq!(cursor(&router, &state), 1);
+        let saved = fs::read_to_string(&path).unwrap();
+        let lines = |size| History::load(Some(path.clone()), size).lines().to_vec();
+        assert_eq!(saved, "1\ntoggle wrap-lines\n2\n");
+        assert_eq!(lines(3), ["1", "toggle wrap-lines", "2"]);
+        assert_eq!(lines(1), ["2"]);
+        assert!(lines(0).is_empty());
+    }
+
+    #[test]
+    fn split_areas_by_direction_and_size() {
+        let split = |width, height, vertical_split, size: &str| {
+            let settings = Settings { vertical_split, split_view_height: size.into(), split_view_width: size.into(), ..Settings::default() };
+            split_areas(Rect::new(0, 0, width, height), &settings)
+        };
+        assert_eq!(split(100, 30, VerticalSplit::Horizontal, "67%"), (Rect::new(0, 0, 100, 10), Rect::new(0, 10, 100, 20)));
+        assert_eq!(split(100, 30, VerticalSplit::Vertical, "0.25"), (Rect::new(0, 0, 75, 30), Rect::new(75, 0, 25, 30)));
+        assert_eq!(split(100, 30, VerticalSplit::Vertical, "30"), (Rect::new(0, 0, 70, 30), Rect::new(70, 0, 30, 30)));
+        // `auto` splits side by side when the terminal is wide for its height.
+        assert_eq!(split(100, 30, VerticalSplit::Auto, "50%").1, Rect::new(0, 15, 100, 15));
+        assert_eq!(split(100, 20, VerticalSplit::Auto, "50%").1, Rect::new(50, 0, 50, 20));
+        assert_eq!(split(200, 60, VerticalSplit::Auto, "50%").1, Rect::new(100, 0, 100, 60));
+        // Each view keeps room for its borders; bad sizes split in half.
+        assert_eq!(split(100, 30, VerticalSplit::Horizontal, "100%").1, Rect::new(0, 4, 100, 26));
+        assert_eq!(split(100, 30, VerticalSplit::Horizontal, "1").1, Rect::new(0, 26, 100, 4));
+        assert_eq!(split(100, 30, VerticalSplit::Horizontal, "tall").1, Rect::new(0, 15, 100, 15));
+        assert_eq!(split(100, 6, VerticalSplit::Horizontal, "50%").1, Rect::new(0, 4, 100, 2));
+    }
+
+    fn titles(router: &Router<TestState>) -> (String, Option<String>, String) {
+        let parent = router.stack.last().unwrap().title();
+        (parent, router.child.as_ref().map(|child| child.title()), router.current().unwrap().title())
+    }
+
+    fn split_router(state: &mut TestState) -> Router<TestState> {
+        let mut router = router(&["one", "two", "three"], History::load(None, 0));
+        press(&mut router, state, KeyCode::Enter);
+        router
+    }
+
+    #[test]
+    fn switch_focus() {
+        let mut state = TestState::default();
+        let mut router = split_router(&mut state);
+        assert_eq!(titles(&router), ("one two three".into(), Some("one".into()), "one".into()));
+        press(&mut router, &mut state, KeyCode::Tab);
+        assert_eq!(titles(&router).2, "one two three");
+        press(&mut router, &mut state, KeyCode::Tab);
+        assert_eq!(titles(&router).2, "one");
+        // Closing the child gives the focus back to the parent.
+        press(&mut router, &mut state, KeyCode::Char('q'));
+        assert_eq!(titles(&router), ("one two three".into(), None, "one two three".into()));
+
+        state.settings.focus_child = false;
+        press(&mut router, &mut state, KeyCode::Enter);
+        assert_eq!(titles(&router), ("one two three".into(), Some("one".into()), "one two three".into()));
+    }
+
+    #[test]
+    fn maximize_focused_view() {
+        let mut state = TestState::default();
+        let mut router = split_router(&mut state);
+        press(&mut router, &mut state, KeyCode::Char('O'));
+        assert_eq!((router.stack.len(), titles(&router)), (2, ("one".into(), None, "one".into())));
+        // Closing the maximized child goes back to the parent alone.
+        press(&mut router, &mut state, KeyCode::Char('q'));
+        assert_eq!((router.stack.len(), titles(&router)), (1, ("one two three".into(), None, "one two three".into())));
+
+        press(&mut router, &mut state, KeyCode::Enter);
+        press(&mut router, &mut state, KeyCode::Tab);
+        press(&mut router, &mut state, KeyCode::Char('O'));
+        assert_eq!((router.stack.len(), titles(&router)), (1, ("one two three".into(), None, "one two three".into())));
+    }
+
+    #[test]
+    fn show_goes_back_to_a_view_of_the_same_kind() {
+        let mut state = TestState::default();
+        let mut router = split_router(&mut state);
+        router.push(Box::new(Lines { keymap: "diff", ..lines(&["diff"]) }));
+        router.apply(Transition::Show(Box::new(lines(&["new"]))), false, &mut state);
+        assert_eq!((router.stack.len(), titles(&router)), (1, ("one two three".into(), None, "one two three".into())));
+        // Without one in the stack, the view is opened on top.
+        router.apply(Transition::Show(Box::new(Lines { keymap: "tree", ..lines(&["tree"]) })), true, &mut state);
+        assert_eq!((router.stack.len(), titles(&router)), (2, ("tree".into(), None, "tree".into())));
+    }
+
+    #[test]
+    fn child_follows_parent_cursor() {
+        let mut state = TestState { settings: Settings { focus_child: false, ..Settings::default() }, ..TestState::default() };
+        let mut router = split_router(&mut state);
+        press(&mut router, &mut state, KeyCode::Char('j'));
+        assert_eq!(titles(&router).1.as_deref(), Some("two"));
+        type_text(&mut router, &mut state, "/three");
+        press(&mut router, &mut state, KeyCode::Enter);
+        assert_eq!(titles(&router).1.as_deref(), Some("three"));
+        // From the child, next and previous move the parent's cursor.
+        press(&mut router, &mut state, KeyCode::Tab);
+        router.dispatch(vec![Request::Previous], &mut state);
+        assert_eq!(titles(&router), ("one two three".into(), Some("two".into()), "two".into()));
+
+        state.settings.send_child_enter = false;
+        press(&mut router, &mut state, KeyCode::Tab);
+        press(&mut router, &mut state, KeyCode::Char('k'));
+        assert_eq!((cursor(&router, &state), titles(&router).1.as_deref()), (0, Some("two")));
+    }
+
+    #[test]
+    fn run_with_the_selected_line() {
+        let mut state = TestState::default();
+        let mut keymaps = Keymaps::default();
+        keymaps.bind("main", "x", &["@echo".into(), "%(text)".into(), "%(commit)".into()]).unwrap();
+        let view = lines(&["one", "two"]);
+        let built = view.built.clone();
+        let mut router = Router::new(Box::new(view), keymaps, History::load(None, 0));
+        press(&mut router, &mut state, KeyCode::Char('j'));
+        press(&mut router, &mut state, KeyCode::Char('x'));
+        assert_eq!(state.ran, [["echo", "two", "HEAD"]]);
+        assert_eq!(built.get(), 0);
+    }
+
+    #[test]
+    fn confirm_before_running() {
+        let mut state = TestState::default();
+        let mut keymaps = Keymaps::default();
+        keymaps.bind("main", "x", &["?@echo".into(), "%(text)".into()]).unwrap();
+        let mut router = Router::new(Box::new(lines(&["one"])), keymaps, History::load(None, 0));
+        press(&mut router, &mut state, KeyCode::Char('x'));
+        press(&mut router, &mut state, KeyCode::Char('n'));
+        assert!(router.input.is_none() && state.ran.is_empty());
+
+        press(&mut router, &mut state, KeyCode::Char('x'));
+        router.handle_event(&Event::Resize(80, 24), &mut state);
+        assert!(matches!(router.input, Some(Input::Confirm(_))));
+        press(&mut router, &mut state, KeyCode::Char('y'));
+        assert_eq!(state.ran, [["echo", "one"]]);
+    }
+}